@@ -0,0 +1,49 @@
+/*
+ * C API of ksynth-midi-renderer.
+ * Build the library with `cargo build --release --lib --features capi` and link against
+ * the resulting cdylib (ksynth_midi_renderer.dll / libksynth_midi_renderer.so / .dylib).
+ */
+
+#ifndef KSYNTH_MIDI_RENDERER_H
+#define KSYNTH_MIDI_RENDERER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define KSYNTH_OK 0
+#define KSYNTH_ERROR_INVALID_ARGUMENT (-1)
+#define KSYNTH_ERROR_MIDI (-2)
+#define KSYNTH_ERROR_OUTPUT (-3)
+
+typedef struct KSynthRenderer KSynthRenderer;
+
+/* Creates a synth with the built-in samples. thread_count 0 uses one instance per CPU.
+ * Returns NULL if an argument is invalid. */
+KSynthRenderer *ksynth_renderer_create(uint32_t sample_rate, uint16_t num_channel,
+                                       uint32_t max_polyphony, uint32_t thread_count);
+
+void ksynth_renderer_destroy(KSynthRenderer *renderer);
+
+/* Queues a packed short MIDI message (status | data1 << 8 | data2 << 16) */
+void ksynth_renderer_queue_midi_cmd(KSynthRenderer *renderer, uint32_t cmd);
+
+/* Renders len interleaved samples (frames * channels) into buffer */
+void ksynth_renderer_fill_buffer(KSynthRenderer *renderer, float *buffer, size_t len);
+
+uint32_t ksynth_renderer_get_polyphony(const KSynthRenderer *renderer);
+
+/* Renders a Standard MIDI file to a 32-bit float WAV with the built-in samples.
+ * Returns KSYNTH_OK or one of the KSYNTH_ERROR_* codes. */
+int32_t ksynth_render_file(const char *midi_path, const char *output_path,
+                           uint32_t sample_rate, uint16_t num_channel,
+                           uint32_t max_polyphony);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KSYNTH_MIDI_RENDERER_H */
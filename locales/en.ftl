@@ -0,0 +1,191 @@
+# English messages, the fallback for messages missing from other catalogs
+
+error-prefix = Error:
+warning-prefix = Warning:
+
+## Settings
+
+title = KSynth MIDI Renderer
+sample-rate = Sample Rate: { $rate } Hz
+oversampling = Oversampling: { $factor }x
+channels = Channels: { $channels }
+bit-depth-16 = Bit Depth: 16-bit (Dither: { $dither })
+bit-depth-32 = Bit Depth: 32-bit float
+limiter-disabled = Limiter Disabled: { $value }
+master-gain = Master Gain: { $gain } dB
+max-polyphony = Max Polyphony: { $voices }
+thread-count = Thread Count: { $threads }
+pinned-cores = Render threads pinned to cores: { $cores }
+core-types-unknown = Couldn't tell the performance cores apart, --p-cores-only is ignored
+core-pin-failed = Couldn't pin a render thread to core { $core }: { $error }
+core-pool-failed = Couldn't set up the pinned render threads: { $error }
+sample-folder-path = Sample Folder Path: { $path }
+earrape-noise-mode = Earrape noise mode: { $value }
+max-render-speed = Max Render Speed: { $speed }
+midi-clock-connected = Sending MIDI clock to { $port }
+tail = Tail: { $tail }
+cut-mode = Cut Mode: { $mode }
+seed = Seed: { $seed }
+strum = Strum: { $ms } ms between chord notes
+humanize = Humanize: timing ±{ $timing } ms, velocity ±{ $velocity }
+scala-tuning = Scala Tuning: { $path }
+keyboard-mapping = Keyboard Mapping: { $path }
+mts-sysex = MTS SysEx: { $value }
+live-mode = Live Mode: { $value }
+preview = Preview: { $value }
+dry-run = Dry Run: { $value }
+
+## Samples and synth
+
+creating-samples-hashmap = Creating Samples HashMap...
+created-samples-hashmap = Samples HashMap Created!
+loading-sample = Loading sample...
+loading-samples-from-folder = Loading samples from folder: { $path }
+loading-samples = Loading samples...
+generating-piano-samples = Generating piano samples...
+generating-drum-samples = Generating drum samples...
+samples-loaded = Samples loaded!
+piano-samples-generated = Piano samples generated!
+drum-samples-generated = Drum samples generated!
+sample-loaded = Sample Loaded!
+creating-ksynth = Creating KSynth...
+auto-tune-ignored = --auto-tune only applies with --thread-count 0, ignoring it
+ksynth-ready = KSynth Ready!
+
+## Live mode
+
+audio-output = Audio Output: { $device }
+midi-input = MIDI Input: { $port }
+live-ready = Live mode ready! Press Enter to stop.
+live-stopped = Live mode stopped.
+
+## MIDI
+
+reading-midi-from-stdin = Reading MIDI from stdin...
+no-midi-file-selected = No MIDI file selected. Exiting.
+dialog-midi-file = Select the MIDI file to render
+dialog-sample-folder = Select the sample folder (cancel for the built-in samples)
+dialog-output = Save the rendered WAV file as
+last-locations-not-saved = Failed to save the last used folders: { $error }
+saved-prefs-ignored = Ignoring the saved options: { $error }
+prefs-not-saved = Failed to save the options for the next run: { $error }
+midi-unwrapped = Unwrapped MIDI from RMIDI/compressed container
+loading-midi = Loading MIDI: { $name }
+midi-loaded = MIDI Loaded!
+midi2-clip-detected = MIDI 2.0 clip file detected
+fixed-midi-saved = Fixed MIDI saved to { $path }
+calculating-midi-statistics = Calculating MIDI Statistics
+calculated-midi-statistics = Calculated MIDI Statistics
+midi-statistics-calculated = MIDI Statistics Calculated!
+midi-duration = MIDI Duration: { $duration }
+note-count = Note Count: { $count } ({ $short })
+missing-sample = key { $key } on channel { $channel } used { $count } times but no sample loaded
+missing-drum-sample = drum key { $key } on channel { $channel } used { $count } times but no sample loaded
+planned-polyphony = Planned Polyphony: peak { $peak } at { $time }, { $p99 } for 99% of the time
+suggested-polyphony = Suggested: --max-polyphony { $max_polyphony } --thread-count { $threads } ({ $voices } voices per instance)
+applied-polyphony-plan = Applied the polyphony plan
+markers-exported = Exported { $count } markers to { $path }
+tempo-map-exported = Exported tempo map to { $path }
+key-heatmap-written = Key heatmap written to { $path }
+chapters-written = Chapters written to { $path }
+lint-duplicate-note-ons = { $count } note ons of a key that is already held, the first at { $time }
+lint-orphan-note-offs = { $count } note offs of a key that isn't held, the first at { $time }
+lint-absurd-tempos = { $count } tempos below 1 or above 10000 BPM, the first at { $time }
+lint-events-after-end = { $count } events after the end of their track, the first in track { $track }
+lint-clean = No suspicious MIDI content found
+lint-issues = Found { $count } suspicious events in the MIDI
+midi-analyzed = MIDI Analysis: { $notes } note ons on { $channels } channels, { $controllers } controller changes, { $tempos } tempos, at most { $peak } notes at once at { $time }
+midi-analysis-written = MIDI analysis written to { $path }
+
+## Dry run
+
+estimated-output-size = Estimated Output Size: { $size } ({ $bytes } bytes)
+output-format = Output Format: { $format }
+all-keys-have-samples = All used keys have samples loaded.
+keys-without-samples = Keys Without Samples: { $count }
+dry-run-finished = Dry run finished, no audio was rendered.
+samples-exported = Exported { $count } samples to { $path }
+
+## Rendering
+
+preparing-audio-encoder = Preparing audio encoder...
+writing-rf64 = Output is larger than 4 GB, writing RF64 instead of WAV
+audio-encoder-created = Audio Encoder Created!
+stream-connected = Streaming to { $target }
+rtp-payload-type = RTP payload type { $payload_type }: L16/{ $rate }/{ $channels }
+rendering-started = Rendering Started
+status-time = Time: { $time } / { $duration }
+status-voices = Voices: { $active } (Peak: { $peak }) / { $max }
+status-rt = RT: { $percent }%
+status-levels = Levels (RMS per instance): { $levels } dB
+status-instances = Instances (voices, RT, notes): { $instances }
+telemetry-listening = Telemetry server listening on port { $port }
+hotkeys-hint = Press p to pause, r to resume, q to stop and save what has been rendered
+control-command = Control command: { $command }
+paused = Paused (press r to resume)
+resumed = Resumed
+system-resumed = Resumed after { $seconds }s of system sleep
+battery-low-paused = Paused while the battery is discharging below { $percent }%
+battery-resumed = Resumed, the battery is charging or above the threshold again
+stream-underrun = Rendering fell { $late_ms } ms behind the speed limit, more than --stream-buffer-ms, a live stream of the output has a gap here
+auto-tune-settled = Auto-tune settled on { $instances } instances
+auto-tune-trying = Auto-tune: trying { $instances } instances
+rendering-cancelled = Rendering cancelled, finalizing the output written so far
+
+## Finishing
+
+frames-dumped = Wrote { $count } frame snapshots
+trimmed-leading-silence = Trimmed { $seconds }s of leading silence
+unprocessed-output-written = Unprocessed output written to { $path }
+click-track-written = Click track written to { $path }
+note-log-written = Note log written to { $path }
+processed-midi-written = Processed MIDI written to { $path }
+polyphony-log-written = Polyphony log written to { $path }
+output-split = Output split into { $count } files
+output-written = Output written to { $path }
+replaygain = ReplayGain: { $gain } dB, peak { $peak }
+replaygain-written = ReplayGain written to { $path }
+replaygain-skipped = The output is too short or silent to measure its ReplayGain
+rendering-finished = Rendering finished!
+total-time = Total time: { $time }
+realtime-ratio = Real-time ratio: { $ratio }x
+stream-underruns = Rendering fell behind the speed limit { $count } times
+press-enter-to-exit = Press Enter to exit
+
+## Profile
+
+profile = Profile:
+profile-stage = Stage
+profile-time = Time
+profile-share = Share
+
+## Job list
+
+job-started = Job { $index }/{ $count } started: { $input }
+job-finished = Job { $index }/{ $count } finished in { $seconds }s: { $input }
+job-failed = Job { $index }/{ $count } failed with exit code { $code }: { $input }: { $message }
+jobs-finished = Finished { $count } jobs in { $seconds }s, { $failed } failed
+
+## Render farm
+
+farm-listening = Render farm listening on { $address } with { $segments } segments to render
+farm-worker-joined = Worker { $worker } joined
+farm-segment-done = Segment { $index }/{ $count } rendered by { $worker }
+farm-worker-lost = Lost worker { $worker } ({ $error }), its segment goes to another worker
+farm-joining = Joining the segments
+farm-finished = Rendered and joined the segments in { $seconds }s: { $path }
+parallel-segments = Rendering { $segments } segments at once
+parallel-segment-done = { $done }/{ $count } segments rendered
+farm-connecting = Connecting to the render farm at { $address }
+farm-segment-started = Rendering segment { $index } ({ $range })
+farm-segment-failed = Segment { $index } failed: { $error }
+farm-worker-finished = The render farm has no segments left
+
+init-sample-folder = Sample folder, archive or URL (empty for the built-in samples)
+init-sample-rate = Sample rate
+init-bit-depth = Bit depth of the output, 16 or 32 (float)
+init-midi-files = MIDI files to render, separated by commas (empty to add them later)
+init-output-folder = Folder for the rendered WAV files
+init-overwrite = { $path } already exists, overwrite it? (y/n)
+init-not-found = { $path } not found
+init-written = Wrote { $path }, render it with --job-list { $path }
@@ -0,0 +1,191 @@
+# 日本語のメッセージ、ここにないメッセージは英語で表示される
+
+error-prefix = エラー:
+warning-prefix = 警告:
+
+## 設定
+
+title = KSynth MIDI Renderer
+sample-rate = サンプルレート: { $rate } Hz
+oversampling = オーバーサンプリング: { $factor }x
+channels = チャンネル数: { $channels }
+bit-depth-16 = ビット深度: 16ビット (ディザ: { $dither })
+bit-depth-32 = ビット深度: 32ビット浮動小数点
+limiter-disabled = リミッター無効: { $value }
+master-gain = マスターゲイン: { $gain } dB
+max-polyphony = 最大同時発音数: { $voices }
+thread-count = スレッド数: { $threads }
+pinned-cores = レンダリングスレッドを固定したコア: { $cores }
+core-types-unknown = パフォーマンスコアを判別できないため --p-cores-only は無視されます
+core-pin-failed = レンダリングスレッドをコア { $core } に固定できませんでした: { $error }
+core-pool-failed = 固定したレンダリングスレッドを用意できませんでした: { $error }
+sample-folder-path = サンプルフォルダ: { $path }
+earrape-noise-mode = 爆音ノイズモード: { $value }
+max-render-speed = 最大レンダリング速度: { $speed }
+midi-clock-connected = MIDIクロックを { $port } に送信します
+tail = テール: { $tail }
+cut-mode = 終了モード: { $mode }
+seed = シード: { $seed }
+strum = ストラム: 和音の音の間隔 { $ms } ms
+humanize = ヒューマナイズ: タイミング ±{ $timing } ms、ベロシティ ±{ $velocity }
+scala-tuning = Scalaチューニング: { $path }
+keyboard-mapping = キーボードマッピング: { $path }
+mts-sysex = MTS SysEx: { $value }
+live-mode = ライブモード: { $value }
+preview = プレビュー: { $value }
+dry-run = ドライラン: { $value }
+
+## サンプルとシンセ
+
+creating-samples-hashmap = サンプルのハッシュマップを作成中...
+created-samples-hashmap = サンプルのハッシュマップを作成しました
+loading-sample = サンプルを読み込み中...
+loading-samples-from-folder = フォルダからサンプルを読み込み中: { $path }
+loading-samples = サンプルを読み込み中...
+generating-piano-samples = ピアノのサンプルを生成中...
+generating-drum-samples = ドラムのサンプルを生成中...
+samples-loaded = サンプルを読み込みました
+piano-samples-generated = ピアノのサンプルを生成しました
+drum-samples-generated = ドラムのサンプルを生成しました
+sample-loaded = サンプルを読み込みました
+creating-ksynth = KSynthを作成中...
+auto-tune-ignored = --auto-tune は --thread-count 0 の時のみ有効なため無視します
+ksynth-ready = KSynthの準備ができました
+
+## ライブモード
+
+audio-output = オーディオ出力: { $device }
+midi-input = MIDI入力: { $port }
+live-ready = ライブモードの準備ができました。Enterキーで停止します。
+live-stopped = ライブモードを停止しました。
+
+## MIDI
+
+reading-midi-from-stdin = 標準入力からMIDIを読み込み中...
+no-midi-file-selected = MIDIファイルが選択されていないため終了します。
+dialog-midi-file = レンダリングするMIDIファイルを選択
+dialog-sample-folder = サンプルフォルダを選択（キャンセルで内蔵サンプル）
+dialog-output = レンダリングしたWAVファイルの保存先
+last-locations-not-saved = 前回使用したフォルダを保存できませんでした: { $error }
+saved-prefs-ignored = 保存されたオプションを無視します: { $error }
+prefs-not-saved = 次回用のオプションを保存できませんでした: { $error }
+midi-unwrapped = RMIDI/圧縮コンテナからMIDIを展開しました
+loading-midi = MIDIを読み込み中: { $name }
+midi-loaded = MIDIを読み込みました
+midi2-clip-detected = MIDI 2.0 クリップファイルを検出しました
+fixed-midi-saved = 修正したMIDIを { $path } に保存しました
+calculating-midi-statistics = MIDIの統計を計算中
+calculated-midi-statistics = MIDIの統計を計算しました
+midi-statistics-calculated = MIDIの統計の計算が完了しました
+midi-duration = MIDIの長さ: { $duration }
+note-count = ノーツ数: { $count } ({ $short })
+missing-sample = チャンネル { $channel } のキー { $key } が { $count } 回使われていますが、サンプルがありません
+missing-drum-sample = チャンネル { $channel } のドラムキー { $key } が { $count } 回使われていますが、サンプルがありません
+planned-polyphony = 同時発音数の予測: { $time } に最大 { $peak }、99%の時間は { $p99 } 以下
+suggested-polyphony = 推奨: --max-polyphony { $max_polyphony } --thread-count { $threads } (インスタンスあたり { $voices } ボイス)
+applied-polyphony-plan = 同時発音数の予測を適用しました
+markers-exported = { $count } 個のマーカーを { $path } に書き出しました
+tempo-map-exported = テンポマップを { $path } に書き出しました
+key-heatmap-written = キーのヒートマップを { $path } に書き出しました
+chapters-written = チャプターを { $path } に書き出しました
+lint-duplicate-note-ons = 既に押されているキーのノートオンが { $count } 個あります (最初は { $time })
+lint-orphan-note-offs = 押されていないキーのノートオフが { $count } 個あります (最初は { $time })
+lint-absurd-tempos = 1 BPM 未満または 10000 BPM を超えるテンポが { $count } 個あります (最初は { $time })
+lint-events-after-end = トラックの終端より後のイベントが { $count } 個あります (最初はトラック { $track })
+lint-clean = MIDIに不審な内容は見つかりませんでした
+lint-issues = MIDIに不審なイベントが { $count } 個見つかりました
+midi-analyzed = MIDI解析: { $channels } チャンネルで { $notes } 回のノートオン、コントロールチェンジ { $controllers } 回、テンポ { $tempos } 個、同時に最大 { $peak } ノーツ ({ $time })
+midi-analysis-written = MIDI解析を { $path } に書き出しました
+
+## ドライラン
+
+estimated-output-size = 推定出力サイズ: { $size } ({ $bytes } バイト)
+output-format = 出力形式: { $format }
+all-keys-have-samples = 使われているすべてのキーにサンプルがあります。
+keys-without-samples = サンプルのないキー: { $count }
+dry-run-finished = ドライランが完了しました。音声はレンダリングされていません。
+samples-exported = { $count } 個のサンプルを { $path } に書き出しました。
+
+## レンダリング
+
+preparing-audio-encoder = オーディオエンコーダーを準備中...
+writing-rf64 = 出力が4GBを超えるため、WAVの代わりにRF64で書き出します
+audio-encoder-created = オーディオエンコーダーを作成しました
+stream-connected = { $target } へ配信しています
+rtp-payload-type = RTP ペイロードタイプ { $payload_type }: L16/{ $rate }/{ $channels }
+rendering-started = レンダリング開始
+status-time = 時間: { $time } / { $duration }
+status-voices = ボイス: { $active } (ピーク: { $peak }) / { $max }
+status-rt = RT: { $percent }%
+status-levels = レベル (インスタンスごとのRMS): { $levels } dB
+status-instances = インスタンス (ボイス, RT, ノーツ): { $instances }
+telemetry-listening = テレメトリサーバーをポート { $port } で待ち受け中
+hotkeys-hint = p で一時停止、r で再開、q で停止してレンダリング済みの部分を保存します
+control-command = 制御コマンド: { $command }
+paused = 一時停止中 (r で再開)
+resumed = 再開しました
+system-resumed = { $seconds } 秒間のシステムスリープから復帰しました
+battery-low-paused = バッテリーが { $percent }% 未満で放電中のため一時停止しています
+battery-resumed = バッテリーの充電が始まったか残量が回復したため再開しました
+stream-underrun = レンダリングが速度制限から { $late_ms } ms 遅れました。--stream-buffer-ms を超えているため、出力のライブ配信にはここで途切れが生じます
+auto-tune-settled = 自動調整: { $instances } インスタンスに決定しました
+auto-tune-trying = 自動調整: { $instances } インスタンスを試行中
+rendering-cancelled = レンダリングがキャンセルされました。ここまでの出力を保存します
+
+## 終了処理
+
+frames-dumped = { $count } 個のフレームスナップショットを書き出しました
+trimmed-leading-silence = 先頭の無音を { $seconds } 秒削除しました
+unprocessed-output-written = 未処理の出力を { $path } に書き出しました
+click-track-written = クリックトラックを { $path } に書き出しました
+note-log-written = ノートログを { $path } に書き出しました
+processed-midi-written = 処理後のMIDIを { $path } に書き出しました
+polyphony-log-written = 同時発音数のログを { $path } に書き出しました
+output-split = 出力を { $count } 個のファイルに分割しました
+output-written = { $path } に出力しました
+replaygain = ReplayGain: { $gain } dB、ピーク { $peak }
+replaygain-written = ReplayGain を { $path } に書き出しました
+replaygain-skipped = 出力が短すぎるか無音のため ReplayGain を測定できません
+rendering-finished = レンダリング完了！
+total-time = 合計時間: { $time }
+realtime-ratio = 実時間比: { $ratio }x
+stream-underruns = レンダリングが速度制限から { $count } 回遅れました
+press-enter-to-exit = Enter キーを押すと終了します
+
+## プロファイル
+
+profile = プロファイル:
+profile-stage = ステージ
+profile-time = 時間
+profile-share = 割合
+
+## ジョブリスト
+
+job-started = ジョブ { $index }/{ $count } 開始: { $input }
+job-finished = ジョブ { $index }/{ $count } 完了 ({ $seconds } 秒): { $input }
+job-failed = ジョブ { $index }/{ $count } が終了コード { $code } で失敗しました: { $input }: { $message }
+jobs-finished = { $count } 個のジョブが { $seconds } 秒で完了、{ $failed } 個失敗
+
+## レンダーファーム
+
+farm-listening = { $address } でレンダーファームを待ち受けています。レンダリングするセグメントは { $segments } 個です
+farm-worker-joined = ワーカー { $worker } が参加しました
+farm-segment-done = セグメント { $index }/{ $count } を { $worker } がレンダリングしました
+farm-worker-lost = ワーカー { $worker } との接続が切れました ({ $error })。セグメントは別のワーカーに割り当てます
+farm-joining = セグメントを結合しています
+farm-finished = セグメントのレンダリングと結合が { $seconds } 秒で完了しました: { $path }
+parallel-segments = { $segments } 個のセグメントを同時にレンダリングしています
+parallel-segment-done = { $done }/{ $count } 個のセグメントをレンダリングしました
+farm-connecting = { $address } のレンダーファームに接続しています
+farm-segment-started = セグメント { $index } をレンダリングしています ({ $range })
+farm-segment-failed = セグメント { $index } が失敗しました: { $error }
+farm-worker-finished = レンダーファームにセグメントが残っていません
+
+init-sample-folder = サンプルフォルダ、アーカイブまたはURL（空欄で内蔵サンプル）
+init-sample-rate = サンプルレート
+init-bit-depth = 出力のビット深度、16 または 32（浮動小数点）
+init-midi-files = レンダリングするMIDIファイル、カンマ区切り（空欄で後から追加）
+init-output-folder = レンダリングしたWAVファイルの出力先フォルダ
+init-overwrite = { $path } は既に存在します。上書きしますか？ (y/n)
+init-not-found = { $path } が見つかりません
+init-written = { $path } を書き出しました。--job-list { $path } でレンダリングできます
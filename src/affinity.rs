@@ -0,0 +1,98 @@
+//! `--pin-cores` and `--p-cores-only`: runs the rayon workers that render the synth instances
+//! with one worker per chosen core, so realtime-speed renders aren't moved between cores by the
+//! scheduler or onto the efficiency cores of hybrid CPUs. Pinning works on Linux and Windows,
+//! the core types are read on Linux.
+
+use log::warn;
+
+use crate::i18n::t;
+
+/// Cores given to `--pin-cores`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreList(pub Vec<usize>);
+
+/// Parses a core list like `0-7` or `0,2,4-6`
+pub fn parse_core_list(s: &str) -> Result<CoreList, String> {
+    let parse = |core: &str| {
+        core.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid core `{}`", core.trim()))
+    };
+    let mut cores = Vec::new();
+    for part in s.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    return Err(format!("invalid core range `{}`", part.trim()));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse(part)?),
+        }
+    }
+    cores.sort_unstable();
+    cores.dedup();
+    Ok(CoreList(cores))
+}
+
+/// Performance cores of a hybrid CPU, `None` if the CPU isn't hybrid or the core types can't be
+/// read
+pub fn performance_cores() -> Option<Vec<usize>> {
+    let cpus = std::fs::read_to_string("/sys/devices/cpu_core/cpus").ok()?;
+    parse_core_list(cpus.trim()).ok().map(|cores| cores.0)
+}
+
+#[cfg(target_os = "linux")]
+fn pin_current_thread(core: usize) -> Result<(), String> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err("no such core".to_string());
+    }
+    // SAFETY: `set` is a valid, zeroed cpu_set_t and `core` is within it
+    unsafe {
+        let mut set = std::mem::zeroed::<libc::cpu_set_t>();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn pin_current_thread(core: usize) -> Result<(), String> {
+    unsafe extern "system" {
+        fn GetCurrentThread() -> *mut std::ffi::c_void;
+        fn SetThreadAffinityMask(thread: *mut std::ffi::c_void, mask: usize) -> usize;
+    }
+    // Cores past the first processor group would need the group affinity functions
+    if core >= usize::BITS as usize {
+        return Err("no such core".to_string());
+    }
+    // SAFETY: the pseudo handle of the current thread is always valid
+    if unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << core) } == 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn pin_current_thread(_core: usize) -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
+
+/// Sets up the global rayon pool with one worker pinned to each of `cores`, before anything
+/// else starts it
+pub fn pin_rayon_workers(cores: Vec<usize>) -> Result<(), String> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(cores.len())
+        .thread_name(|index| format!("render-{}", index))
+        .start_handler(move |index| {
+            let core = cores[index];
+            if let Err(e) = pin_current_thread(core) {
+                warn!(event = "core_pin_failed", core; "{}", t!("core-pin-failed", core = core, error = e));
+            }
+        })
+        .build_global()
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,78 @@
+//! `--auto-tune`: picks the instance count during the first seconds of rendering. Starting
+//! with one instance, the count is doubled while the synthesis time per second of audio keeps
+//! dropping, then the best count measured is kept.
+
+use std::time::Duration;
+
+/// Seconds of audio rendered with each instance count before it's measured
+const TRIAL_SECS: f64 = 1.0;
+/// Relative speedup needed to keep adding instances
+const MIN_IMPROVEMENT: f64 = 0.05;
+
+pub struct AutoTuner {
+    max_instances: usize,
+    instances: usize,
+    /// Best instance count so far with its synthesis seconds per second of audio
+    best: Option<(usize, f64)>,
+    trial_audio_secs: f64,
+    trial_synthesis_time: Duration,
+    finished: bool,
+}
+
+impl AutoTuner {
+    pub fn new(max_instances: usize) -> Self {
+        AutoTuner {
+            max_instances: max_instances.max(1),
+            instances: 1,
+            best: None,
+            trial_audio_secs: 0.0,
+            trial_synthesis_time: Duration::ZERO,
+            finished: false,
+        }
+    }
+
+    /// Instance count being tried, or the final one once finished
+    pub fn instances(&self) -> usize {
+        self.instances
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Records a synthesized block, returns the instance count to switch to if it changes
+    pub fn record(&mut self, audio_secs: f64, synthesis_time: Duration) -> Option<usize> {
+        if self.finished {
+            return None;
+        }
+        self.trial_audio_secs += audio_secs;
+        self.trial_synthesis_time += synthesis_time;
+        if self.trial_audio_secs < TRIAL_SECS {
+            return None;
+        }
+
+        let ratio = self.trial_synthesis_time.as_secs_f64() / self.trial_audio_secs;
+        self.trial_audio_secs = 0.0;
+        self.trial_synthesis_time = Duration::ZERO;
+
+        let improved = self
+            .best
+            .is_none_or(|(_, best_ratio)| ratio < best_ratio * (1.0 - MIN_IMPROVEMENT));
+        if improved {
+            self.best = Some((self.instances, ratio));
+        }
+        if improved && self.instances < self.max_instances {
+            self.instances = (self.instances * 2).min(self.max_instances);
+            return Some(self.instances);
+        }
+
+        self.finished = true;
+        let best_instances = self.best.map_or(self.instances, |(instances, _)| instances);
+        if best_instances != self.instances {
+            self.instances = best_instances;
+            Some(best_instances)
+        } else {
+            None
+        }
+    }
+}
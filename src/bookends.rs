@@ -0,0 +1,77 @@
+//! Audio files for `--prepend` and `--append`, converted to the render's sample rate and
+//! channel count so they can be written before and after the MIDI render as-is.
+
+use std::path::Path;
+
+/// Reads a WAV file as interleaved float frames at `sample_rate` with `num_channel` channels
+pub fn load_bookend(
+    path: impl AsRef<Path>,
+    sample_rate: u32,
+    num_channel: u16,
+) -> Result<Vec<f32>, String> {
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()
+        }
+    }
+    .map_err(|e| e.to_string())?;
+    if spec.channels == 0 || spec.sample_rate == 0 {
+        return Err("invalid WAV format".to_string());
+    }
+
+    let frames = remix(&samples, spec.channels as usize, num_channel as usize);
+    Ok(resample(
+        &frames,
+        num_channel as usize,
+        spec.sample_rate,
+        sample_rate,
+    ))
+}
+
+/// Converts the channel count. Mono is copied to every channel, a downmix to mono averages
+/// the channels, otherwise channels are kept in order and missing ones are left silent.
+fn remix(samples: &[f32], from: usize, to: usize) -> Vec<f32> {
+    if from == to {
+        return samples.to_vec();
+    }
+    samples
+        .chunks_exact(from)
+        .flat_map(|frame| {
+            (0..to).map(move |channel| match (from, to) {
+                (1, _) => frame[0],
+                (_, 1) => frame.iter().sum::<f32>() / from as f32,
+                _ => frame.get(channel).copied().unwrap_or(0.0),
+            })
+        })
+        .collect()
+}
+
+/// Linear interpolation from `from_rate` to `to_rate`
+fn resample(samples: &[f32], num_channel: usize, from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate {
+        return samples.to_vec();
+    }
+    let frame_count = samples.len() / num_channel;
+    let output_frames = (frame_count as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let step = from_rate as f64 / to_rate as f64;
+    let mut output = Vec::with_capacity(output_frames * num_channel);
+    for frame in 0..output_frames {
+        let position = frame as f64 * step;
+        let index = position as usize;
+        let fraction = (position - index as f64) as f32;
+        let next = (index + 1).min(frame_count - 1);
+        for channel in 0..num_channel {
+            let a = samples[index * num_channel + channel];
+            let b = samples[next * num_channel + channel];
+            output.push(a + (b - a) * fraction);
+        }
+    }
+    output
+}
@@ -0,0 +1,226 @@
+//! The built-in samples used when no sample folder is given, generated the same way for the
+//! renderer and the library hosts so they all sound the same.
+
+use std::collections::HashMap;
+
+use ksynth_core::{
+    drum_kit::DrumKit,
+    sample::{Sample, SampleData},
+};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    multi_synth::MultiSynth,
+    predefined_drum_samples::{
+        DrumKitStyle, VelocityLayer, generate_kit_drum_sample, velocity_layer_sample,
+    },
+    predefined_sample::{
+        BuiltinInstrument, BuiltinQuality, builtin_loop_points, generate_builtin_sample,
+        sample_rng, stereo_piano_sample,
+    },
+    tuning::{SampleSource, Tuning},
+};
+
+/// Keys of the built-in drum kits, the MIDI GS drum map
+pub const DRUM_NOTES: [u8; 52] = [
+    35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58,
+    59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82,
+    83, 84, 88, 89, // Rimshot and brush swirl past the GM map
+];
+
+/// How the built-in samples are generated, the `--builtin-*` and drum options of the renderer
+#[derive(Debug, Clone, Copy)]
+pub struct BuiltinOptions {
+    pub instrument: BuiltinInstrument,
+    pub quality: BuiltinQuality,
+    /// Length of the melodic samples in seconds
+    pub seconds: f32,
+    pub seed: Option<u64>,
+    /// Spread the piano samples by key, for stereo output
+    pub stereo: bool,
+    /// Synthesize the drums without a generator of their own from the closest one
+    pub synthesize_missing_drums: bool,
+    /// Velocity layers of every drum kit, 1-3
+    pub drum_velocity_layers: u8,
+}
+
+impl BuiltinOptions {
+    /// The renderer's defaults for output with `num_channel` channels
+    pub fn new(num_channel: u16) -> Self {
+        BuiltinOptions {
+            instrument: BuiltinInstrument::Piano,
+            quality: BuiltinQuality::Normal,
+            seconds: 10.0,
+            seed: None,
+            stereo: num_channel == 2,
+            synthesize_missing_drums: false,
+            drum_velocity_layers: 1,
+        }
+    }
+}
+
+/// Generates the melodic sample of `key`, at the pitch of `tuning` if given
+pub fn builtin_melodic_source(
+    options: &BuiltinOptions,
+    sample_rate: u32,
+    tuning: Option<&Tuning>,
+    key: u8,
+) -> SampleSource {
+    let freq = match tuning {
+        Some(tuning) => tuning.frequency(key) as f32,
+        None => 440.0 * 2f32.powf((key as f32 - 69.0) / 12.0),
+    };
+    let sample_count = (sample_rate as f32 * options.seconds) as usize;
+    let mut rng = sample_rng(options.seed, key, false);
+    let sample_vec = generate_builtin_sample(
+        options.instrument,
+        sample_rate,
+        freq,
+        sample_count,
+        options.quality,
+        &mut rng,
+    );
+    let data = if options.stereo && matches!(options.instrument, BuiltinInstrument::Piano) {
+        SampleData::Stereo(stereo_piano_sample(&sample_vec, key))
+    } else {
+        SampleData::Mono(sample_vec)
+    };
+    SampleSource {
+        sample_rate,
+        data,
+        frequency: freq as f64,
+        loop_points: builtin_loop_points(options.instrument, sample_rate),
+    }
+}
+
+/// Generates the melodic samples of all 128 keys, calling `progress` for every key
+pub fn builtin_melodic_sources(
+    options: &BuiltinOptions,
+    sample_rate: u32,
+    tuning: Option<&Tuning>,
+    progress: impl Fn() + Sync,
+) -> Vec<(u8, SampleSource)> {
+    (0u8..128)
+        .into_par_iter()
+        .map(|key| {
+            progress();
+            (
+                key,
+                builtin_melodic_source(options, sample_rate, tuning, key),
+            )
+        })
+        .collect()
+}
+
+/// Generates the drum of `key` in the `style` kit and velocity `layer`, empty if the kit doesn't
+/// have one
+pub fn builtin_drum_sample(
+    options: &BuiltinOptions,
+    sample_rate: u32,
+    style: DrumKitStyle,
+    layer: VelocityLayer,
+    key: u8,
+) -> Vec<i16> {
+    let mut rng = sample_rng(options.seed, key, true);
+    let sample_vec = generate_kit_drum_sample(
+        key,
+        style,
+        sample_rate,
+        options.synthesize_missing_drums,
+        &mut rng,
+    );
+    velocity_layer_sample(sample_vec, layer, sample_rate)
+}
+
+/// Builds a drum kit of `(key, samples)` pairs
+pub fn drum_kit_of(sample_rate: u32, samples: Vec<(u8, Vec<i16>)>) -> DrumKit {
+    let kit_map = samples
+        .into_iter()
+        .map(|(key, sample_vec)| {
+            let sample = Sample::new(sample_rate, SampleData::Mono(sample_vec), None);
+            (key, sample)
+        })
+        .collect();
+    DrumKit::new(kit_map)
+}
+
+/// Generates the `style` kit in velocity `layer`
+pub fn builtin_drum_kit(
+    options: &BuiltinOptions,
+    sample_rate: u32,
+    style: DrumKitStyle,
+    layer: VelocityLayer,
+) -> DrumKit {
+    let samples = DRUM_NOTES
+        .iter()
+        .map(|&key| {
+            let sample_vec = builtin_drum_sample(options, sample_rate, style, layer, key);
+            (key, sample_vec)
+        })
+        .collect();
+    drum_kit_of(sample_rate, samples)
+}
+
+/// Generates the `style` kit in each of the velocity layers of `options`, with the highest
+/// velocity each plays, for `MultiSynth::set_drum_layers`
+pub fn builtin_drum_kit_layers(
+    options: &BuiltinOptions,
+    sample_rate: u32,
+    style: DrumKitStyle,
+) -> Vec<(u8, DrumKit)> {
+    VelocityLayer::layers(options.drum_velocity_layers)
+        .into_par_iter()
+        .map(|&(top, layer)| (top, builtin_drum_kit(options, sample_rate, style, layer)))
+        .collect()
+}
+
+/// Generates the kits of `styles` other than the standard kit in their velocity layers, for
+/// `MultiSynth::set_drum_kit_styles`
+pub fn builtin_drum_kit_styles(
+    options: &BuiltinOptions,
+    sample_rate: u32,
+    styles: &[DrumKitStyle],
+) -> Vec<(DrumKitStyle, Vec<(u8, DrumKit)>)> {
+    styles
+        .into_par_iter()
+        .filter(|&&style| style != DrumKitStyle::Standard)
+        .map(|&style| (style, builtin_drum_kit_layers(options, sample_rate, style)))
+        .collect()
+}
+
+/// Gives `multi_synth` the velocity layers of the standard kit and the kits of `styles` to switch
+/// to on drum program changes, the standard kit is the one it was created with
+pub fn load_builtin_drum_kits(
+    multi_synth: &mut MultiSynth,
+    options: &BuiltinOptions,
+    sample_rate: u32,
+    styles: &[DrumKitStyle],
+) {
+    if options.drum_velocity_layers > 1 {
+        let layers = builtin_drum_kit_layers(options, sample_rate, DrumKitStyle::Standard);
+        multi_synth.set_drum_layers(layers);
+    }
+    multi_synth.set_drum_kit_styles(builtin_drum_kit_styles(options, sample_rate, styles));
+}
+
+/// Generates the melodic samples of all 128 keys and the standard drum kit, for hosts that
+/// don't retune keys. Give the synth the other kits with `load_builtin_drum_kits`.
+pub fn builtin_samples(
+    options: &BuiltinOptions,
+    sample_rate: u32,
+) -> (HashMap<u8, Sample>, DrumKit) {
+    let samples_map = builtin_melodic_sources(options, sample_rate, None, || {})
+        .into_iter()
+        .map(|(key, source)| {
+            let frequency = source.frequency;
+            (key, source.into_sample(frequency))
+        })
+        .collect();
+    let drum_kit = builtin_drum_kit(
+        options,
+        sample_rate,
+        DrumKitStyle::Standard,
+        VelocityLayer::Normal,
+    );
+    (samples_map, drum_kit)
+}
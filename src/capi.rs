@@ -0,0 +1,235 @@
+//! C API for embedding the engine in non-Rust hosts (C/C++, Python via ctypes, ...).
+//! Build with `--features capi`, the declarations are in `include/ksynth_midi_renderer.h`.
+
+use std::{
+    ffi::{CStr, c_char},
+    sync::{Arc, RwLock},
+};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use ksynth_core::Channel;
+use midi_toolkit::{
+    events::MIDIEvent,
+    io::MIDIFile,
+    pipe,
+    sequence::{
+        TimeCaster,
+        event::{cancel_tempo_events, merge_events_array, scale_event_time},
+        to_vec, unwrap_items,
+    },
+};
+
+use crate::{
+    builtin_samples::{BuiltinOptions, builtin_samples, load_builtin_drum_kits},
+    limiter::Limiter,
+    multi_synth::MultiSynth,
+    predefined_drum_samples::DrumKitStyle,
+    synth_event::SynthEvent,
+};
+
+pub const KSYNTH_OK: i32 = 0;
+pub const KSYNTH_ERROR_INVALID_ARGUMENT: i32 = -1;
+pub const KSYNTH_ERROR_MIDI: i32 = -2;
+pub const KSYNTH_ERROR_OUTPUT: i32 = -3;
+
+/// Length of the release tail rendered after the last event by `ksynth_render_file`
+const RENDER_TAIL_SECS: f64 = 1.0;
+
+/// Opaque synth handle handed out to C
+pub struct KSynthRenderer {
+    synth: MultiSynth,
+}
+
+fn create_synth(
+    sample_rate: u32,
+    num_channel: u16,
+    max_polyphony: u32,
+    thread_count: u32,
+) -> Option<MultiSynth> {
+    if sample_rate == 0 || max_polyphony == 0 {
+        return None;
+    }
+    let ksynth_num_channel: Channel = num_channel.try_into().ok()?;
+    let thread_count = match thread_count {
+        0 => num_cpus::get(),
+        count => count as usize,
+    };
+    let options = BuiltinOptions::new(num_channel);
+    let (samples_map, drum_kit) = builtin_samples(&options, sample_rate);
+    let mut synth = MultiSynth::new(
+        sample_rate,
+        ksynth_num_channel,
+        max_polyphony,
+        (sample_rate as f64 * 0.1) as u64,
+        Arc::new(RwLock::new(samples_map)),
+        Some(drum_kit),
+        thread_count,
+    );
+    load_builtin_drum_kits(&mut synth, &options, sample_rate, &DrumKitStyle::ALL);
+    Some(synth)
+}
+
+/// Creates a synth with the built-in samples. `thread_count` 0 uses one instance per CPU.
+/// Returns null if an argument is invalid.
+#[unsafe(no_mangle)]
+pub extern "C" fn ksynth_renderer_create(
+    sample_rate: u32,
+    num_channel: u16,
+    max_polyphony: u32,
+    thread_count: u32,
+) -> *mut KSynthRenderer {
+    match create_synth(sample_rate, num_channel, max_polyphony, thread_count) {
+        Some(synth) => Box::into_raw(Box::new(KSynthRenderer { synth })),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `renderer` must come from `ksynth_renderer_create` and not be used afterwards
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ksynth_renderer_destroy(renderer: *mut KSynthRenderer) {
+    if !renderer.is_null() {
+        drop(unsafe { Box::from_raw(renderer) });
+    }
+}
+
+/// Queues a packed short MIDI message (status | data1 << 8 | data2 << 16)
+///
+/// # Safety
+/// `renderer` must be null or a live handle from `ksynth_renderer_create`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ksynth_renderer_queue_midi_cmd(renderer: *mut KSynthRenderer, cmd: u32) {
+    if let Some(renderer) = unsafe { renderer.as_mut() } {
+        renderer.synth.queue_midi_cmd(cmd);
+    }
+}
+
+/// Renders `len` interleaved samples (frames * channels) into `buffer`
+///
+/// # Safety
+/// `renderer` must be null or a live handle from `ksynth_renderer_create`,
+/// `buffer` must be valid for `len` writes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ksynth_renderer_fill_buffer(
+    renderer: *mut KSynthRenderer,
+    buffer: *mut f32,
+    len: usize,
+) {
+    let Some(renderer) = (unsafe { renderer.as_mut() }) else {
+        return;
+    };
+    if buffer.is_null() || len == 0 {
+        return;
+    }
+    let buffer = unsafe { std::slice::from_raw_parts_mut(buffer, len) };
+    buffer.fill(0.0);
+    renderer.synth.fill_buffer(buffer);
+}
+
+/// # Safety
+/// `renderer` must be null or a live handle from `ksynth_renderer_create`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ksynth_renderer_get_polyphony(renderer: *const KSynthRenderer) -> u32 {
+    match unsafe { renderer.as_ref() } {
+        Some(renderer) => renderer.synth.get_polyphony(),
+        None => 0,
+    }
+}
+
+fn render_file(
+    midi_path: &str,
+    output_path: &str,
+    sample_rate: u32,
+    num_channel: u16,
+    max_polyphony: u32,
+) -> i32 {
+    let Some(mut synth) = create_synth(sample_rate, num_channel, max_polyphony, 0) else {
+        return KSYNTH_ERROR_INVALID_ARGUMENT;
+    };
+    let Ok(midi) = MIDIFile::open(midi_path, None) else {
+        return KSYNTH_ERROR_MIDI;
+    };
+    let spec = WavSpec {
+        channels: num_channel,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let Ok(mut writer) = WavWriter::create(output_path, spec) else {
+        return KSYNTH_ERROR_OUTPUT;
+    };
+
+    let mut limiter = Limiter::new(sample_rate as f32, 0.0, 100.0, 20.0);
+    let mut render = |synth: &mut MultiSynth, frame_count: usize| -> hound::Result<()> {
+        let mut buffer = vec![0.0f32; frame_count * num_channel as usize];
+        synth.fill_buffer(&mut buffer);
+        limiter.process(&mut buffer);
+        buffer
+            .iter()
+            .try_for_each(|&sample| writer.write_sample(sample))
+    };
+
+    let ppq = midi.ppq();
+    let events = pipe!(
+        midi.iter_all_tracks()
+        |>to_vec()
+        |>merge_events_array()
+        |>TimeCaster::<f64>::cast_event_delta()
+        |>cancel_tempo_events(250000)
+        |>scale_event_time(1.0 / ppq as f64)
+        |>unwrap_items()
+    );
+
+    let mut time_acc = 0.0;
+    for merged_event in events {
+        time_acc += merged_event.delta * sample_rate as f64;
+        let frame_count = time_acc.floor() as usize;
+        time_acc -= frame_count as f64;
+        if frame_count > 0 && render(&mut synth, frame_count).is_err() {
+            return KSYNTH_ERROR_OUTPUT;
+        }
+        if let Some(event) = merged_event.event.as_u32().and_then(SynthEvent::from_midi1) {
+            synth.queue_event(&event);
+        }
+    }
+
+    let tail_frames = (RENDER_TAIL_SECS * sample_rate as f64) as usize;
+    if render(&mut synth, tail_frames).is_err() {
+        return KSYNTH_ERROR_OUTPUT;
+    }
+    match writer.finalize() {
+        Ok(()) => KSYNTH_OK,
+        Err(_) => KSYNTH_ERROR_OUTPUT,
+    }
+}
+
+/// Renders a Standard MIDI file to a 32-bit float WAV with the built-in samples.
+/// Returns `KSYNTH_OK` or one of the `KSYNTH_ERROR_*` codes.
+///
+/// # Safety
+/// `midi_path` and `output_path` must be null or NUL-terminated UTF-8 strings
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ksynth_render_file(
+    midi_path: *const c_char,
+    output_path: *const c_char,
+    sample_rate: u32,
+    num_channel: u16,
+    max_polyphony: u32,
+) -> i32 {
+    if midi_path.is_null() || output_path.is_null() {
+        return KSYNTH_ERROR_INVALID_ARGUMENT;
+    }
+    let (Ok(midi_path), Ok(output_path)) = (
+        unsafe { CStr::from_ptr(midi_path) }.to_str(),
+        unsafe { CStr::from_ptr(output_path) }.to_str(),
+    ) else {
+        return KSYNTH_ERROR_INVALID_ARGUMENT;
+    };
+    render_file(
+        midi_path,
+        output_path,
+        sample_rate,
+        num_channel,
+        max_polyphony,
+    )
+}
@@ -0,0 +1,101 @@
+//! Volume (CC 7) and expression (CC 11) smoothing for `--cc-smoothing-ms`. KSynth applies
+//! controller changes at once, so coarse volume automation steps audibly. The changes are
+//! held back here and ramped to in small steps while rendering instead.
+
+const VOLUME: u8 = 7;
+const EXPRESSION: u8 = 11;
+const RESET_ALL_CONTROLLERS: u8 = 121;
+
+#[derive(Debug, Clone, Copy)]
+struct Ramp {
+    value: f32,
+    target: f32,
+    /// Controller steps per second towards the target
+    rate: f32,
+    /// Value last sent to the instances
+    sent: u8,
+}
+
+pub struct CcSmoothing {
+    /// Time a change is ramped over, in seconds
+    time: f32,
+    /// Volume and expression of each channel, `None` until the MIDI sets them
+    ramps: [[Option<Ramp>; 2]; 16],
+}
+
+fn cc_cmd(channel: u8, controller: u8, value: u8) -> u32 {
+    (0xB0 | channel) as u32 | ((controller as u32) << 8) | ((value as u32 & 0x7F) << 16)
+}
+
+impl CcSmoothing {
+    pub fn new(time: f32) -> Self {
+        CcSmoothing {
+            time,
+            ramps: [[None; 2]; 16],
+        }
+    }
+
+    /// Returns the command to send in place of a control change, `None` if it's ramped to
+    pub fn control_change(&mut self, channel: u8, controller: u8, value: u8) -> Option<u32> {
+        let index = channel as usize & 0xF;
+        let slot = match controller {
+            VOLUME => 0,
+            EXPRESSION => 1,
+            RESET_ALL_CONTROLLERS => {
+                // Resets expression but not volume, the instances do the same
+                self.ramps[index][1] = None;
+                return Some(cc_cmd(channel, controller, value));
+            }
+            _ => return Some(cc_cmd(channel, controller, value)),
+        };
+        match self.ramps[index][slot].as_mut() {
+            Some(ramp) => {
+                ramp.target = value as f32;
+                ramp.rate = (ramp.target - ramp.value).abs() / self.time;
+                None
+            }
+            // The first value of a song is set at once, there's nothing playing to ramp
+            None => {
+                self.ramps[index][slot] = Some(Ramp {
+                    value: value as f32,
+                    target: value as f32,
+                    rate: 0.0,
+                    sent: value,
+                });
+                Some(cc_cmd(channel, controller, value))
+            }
+        }
+    }
+
+    pub fn is_smoothing(&self) -> bool {
+        self.ramps
+            .iter()
+            .flatten()
+            .flatten()
+            .any(|ramp| ramp.value != ramp.target)
+    }
+
+    /// Moves the ramps `seconds` forward, returns the controller changes to send
+    pub fn advance(&mut self, seconds: f32) -> Vec<u32> {
+        let mut cmds = Vec::new();
+        for (channel, ramps) in self.ramps.iter_mut().enumerate() {
+            for (ramp, controller) in ramps.iter_mut().zip([VOLUME, EXPRESSION]) {
+                let Some(ramp) = ramp.as_mut() else {
+                    continue;
+                };
+                let step = ramp.rate * seconds;
+                if (ramp.target - ramp.value).abs() <= step {
+                    ramp.value = ramp.target;
+                } else {
+                    ramp.value += step * (ramp.target - ramp.value).signum();
+                }
+                let value = ramp.value.round() as u8;
+                if value != ramp.sent {
+                    ramp.sent = value;
+                    cmds.push(cc_cmd(channel as u8, controller, value));
+                }
+            }
+        }
+        cmds
+    }
+}
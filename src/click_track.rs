@@ -0,0 +1,92 @@
+//! Metronome for `--click-track`, clicking on every beat of the tempo map's bar/beat grid
+//! with an accented click on the first beat of each bar.
+
+use std::f32::consts::TAU;
+
+use crate::tempo_map::Beat;
+
+/// Length of one click in seconds
+const CLICK_SECS: f32 = 0.03;
+const ACCENT_FREQUENCY: f32 = 1500.0;
+const BEAT_FREQUENCY: f32 = 1000.0;
+
+struct Click {
+    /// Output frame the click starts at
+    frame: u64,
+    accent: bool,
+}
+
+pub struct ClickTrack {
+    clicks: Vec<Click>,
+    sample_rate: u32,
+    click_frames: u64,
+    gain: f32,
+    /// Output frames rendered so far
+    position: u64,
+    /// First click that may still be sounding
+    next: usize,
+}
+
+impl ClickTrack {
+    /// Clicks on `beats` at `sample_rate` with a linear `gain`
+    pub fn new(beats: &[Beat], sample_rate: u32, gain: f32) -> Self {
+        ClickTrack {
+            clicks: beats
+                .iter()
+                .map(|beat| Click {
+                    frame: (beat.time * sample_rate as f64).floor() as u64,
+                    accent: beat.beat == 1,
+                })
+                .collect(),
+            sample_rate,
+            click_frames: (CLICK_SECS * sample_rate as f32).ceil() as u64,
+            gain,
+            position: 0,
+            next: 0,
+        }
+    }
+
+    /// Renders the next `frame_count` mono frames
+    pub fn render(&mut self, frame_count: usize) -> Vec<f32> {
+        let mut output = vec![0.0; frame_count];
+        let end = self.position + frame_count as u64;
+        while self
+            .clicks
+            .get(self.next)
+            .is_some_and(|click| click.frame + self.click_frames <= self.position)
+        {
+            self.next += 1;
+        }
+
+        for click in self.clicks[self.next..]
+            .iter()
+            .take_while(|click| click.frame < end)
+        {
+            let frequency = if click.accent {
+                ACCENT_FREQUENCY
+            } else {
+                BEAT_FREQUENCY
+            };
+            for frame in click.frame.max(self.position)..(click.frame + self.click_frames).min(end)
+            {
+                let t = (frame - click.frame) as f32 / self.sample_rate as f32;
+                // Sine burst with an exponential decay, about -40 dB at the end of the click
+                let envelope = (-t * 4.6 / CLICK_SECS).exp();
+                output[(frame - self.position) as usize] +=
+                    (TAU * frequency * t).sin() * envelope * self.gain;
+            }
+        }
+        self.position = end;
+        output
+    }
+
+    /// Adds the next frames of the click to every channel of the interleaved `buffer`
+    pub fn mix(&mut self, buffer: &mut [f32], num_channel: u16) {
+        let click = self.render(buffer.len() / num_channel as usize);
+        for (frame, sample) in buffer.chunks_exact_mut(num_channel as usize).zip(click) {
+            for s in frame {
+                *s += sample;
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    effects::Effect,
+    units::{amplitude_to_db, db_to_amplitude},
+};
+
+/// Settings of the compressor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressorSettings {
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub makeup_db: f32,
+}
+
+/// Parses settings like `-18:3` or `-18:3:10:150:4`
+/// (`threshold_db:ratio[:attack_ms[:release_ms[:makeup_db]]]`, 10 ms attack and 100 ms release
+/// by default)
+pub fn parse_compressor(s: &str) -> Result<CompressorSettings, String> {
+    let values = s
+        .trim()
+        .split(':')
+        .map(|value| value.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid compressor settings `{}`", s))?;
+
+    let (threshold_db, ratio, rest) = match values.as_slice() {
+        [threshold_db, ratio, rest @ ..] if rest.len() <= 3 => (*threshold_db, *ratio, rest),
+        _ => return Err(format!("invalid compressor settings `{}`", s)),
+    };
+    let settings = CompressorSettings {
+        threshold_db,
+        ratio,
+        attack_ms: rest.first().copied().unwrap_or(10.0),
+        release_ms: rest.get(1).copied().unwrap_or(100.0),
+        makeup_db: rest.get(2).copied().unwrap_or(0.0),
+    };
+    if settings.threshold_db > 0.0
+        || settings.ratio < 1.0
+        || settings.attack_ms < 0.0
+        || settings.release_ms <= 0.0
+    {
+        return Err(format!("invalid compressor settings `{}`", s));
+    }
+    Ok(settings)
+}
+
+/// Feed-forward compressor with a stereo linked peak detector, so the image doesn't shift when
+/// one side is compressed harder than the other
+pub struct Compressor {
+    settings: CompressorSettings,
+    attack_coef: f32,
+    release_coef: f32,
+    num_channel: usize,
+    /// Smoothed gain reduction in dB
+    reduction_db: f32,
+}
+
+impl Compressor {
+    pub fn new(settings: CompressorSettings, sample_rate: u32, num_channel: u16) -> Self {
+        let coef = |ms: f32| {
+            if ms <= 0.0 {
+                0.0
+            } else {
+                (-1.0 / (ms / 1000.0 * sample_rate as f32)).exp()
+            }
+        };
+        Compressor {
+            settings,
+            attack_coef: coef(settings.attack_ms),
+            release_coef: coef(settings.release_ms),
+            num_channel: num_channel.max(1) as usize,
+            reduction_db: 0.0,
+        }
+    }
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, buffer: &mut [f32]) {
+        let slope = 1.0 - 1.0 / self.settings.ratio;
+        for frame in buffer.chunks_exact_mut(self.num_channel) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            let over_db = amplitude_to_db(peak) - self.settings.threshold_db;
+            let target_db = over_db.max(0.0) * slope;
+
+            let coef = if target_db > self.reduction_db {
+                self.attack_coef
+            } else {
+                self.release_coef
+            };
+            self.reduction_db = coef * self.reduction_db + (1.0 - coef) * target_db;
+
+            let gain = db_to_amplitude(self.settings.makeup_db - self.reduction_db);
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+}
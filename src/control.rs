@@ -0,0 +1,146 @@
+use std::{
+    fmt,
+    io::BufRead,
+    sync::mpsc::{self, Receiver},
+};
+
+use log::warn;
+
+/// Command accepted on the control channel, one per line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlCommand {
+    Pause,
+    Resume,
+    SetMaxSpeed(f64),
+    Cancel,
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlCommand::Pause => write!(f, "pause"),
+            ControlCommand::Resume => write!(f, "resume"),
+            ControlCommand::SetMaxSpeed(speed) => write!(f, "set-max-speed {}", speed),
+            ControlCommand::Cancel => write!(f, "cancel"),
+        }
+    }
+}
+
+pub fn parse_command(line: &str) -> Result<ControlCommand, String> {
+    let mut parts = line.split_whitespace();
+    let command = match (parts.next(), parts.next()) {
+        (Some("pause"), None) => ControlCommand::Pause,
+        (Some("resume"), None) => ControlCommand::Resume,
+        (Some("cancel"), None) => ControlCommand::Cancel,
+        (Some("set-max-speed"), Some(speed)) => match speed.parse::<f64>() {
+            Ok(speed) if speed >= 0.0 => ControlCommand::SetMaxSpeed(speed),
+            _ => return Err(format!("invalid speed `{}`", speed)),
+        },
+        _ => return Err(format!("unknown command `{}`", line.trim())),
+    };
+    if parts.next().is_some() {
+        return Err(format!("unknown command `{}`", line.trim()));
+    }
+    Ok(command)
+}
+
+/// Reads control commands on background threads, the render loop polls them between blocks
+pub struct ControlChannel {
+    receiver: Receiver<ControlCommand>,
+}
+
+impl ControlChannel {
+    /// Reads commands from stdin
+    pub fn stdin() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                let command = match parse_line(&line) {
+                    Some(Ok(command)) => command,
+                    Some(Err(e)) => {
+                        warn!(event = "control_error", error:% = e; "Control command: {}", e);
+                        continue;
+                    }
+                    None => continue,
+                };
+                if sender.send(command).is_err() {
+                    break;
+                }
+            }
+        });
+        ControlChannel { receiver }
+    }
+
+    /// Listens on a Unix socket, each connection can send any number of commands
+    #[cfg(unix)]
+    pub fn unix_socket(path: &str) -> std::io::Result<Self> {
+        use std::{io::Write, os::unix::net::UnixListener};
+
+        // A stale socket from a previous run would make bind fail
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path)?;
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    let Ok(mut reply) = stream.try_clone() else {
+                        return;
+                    };
+                    for line in std::io::BufReader::new(stream).lines() {
+                        let Ok(line) = line else {
+                            break;
+                        };
+                        match parse_line(&line) {
+                            Some(Ok(command)) => {
+                                let _ = writeln!(reply, "ok");
+                                if sender.send(command).is_err() {
+                                    break;
+                                }
+                            }
+                            Some(Err(e)) => {
+                                let _ = writeln!(reply, "error {}", e);
+                            }
+                            None => {}
+                        }
+                    }
+                });
+            }
+        });
+        Ok(ControlChannel { receiver })
+    }
+
+    #[cfg(not(unix))]
+    pub fn unix_socket(_path: &str) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Unix sockets are not supported on this platform",
+        ))
+    }
+
+    /// Returns a pending command without blocking
+    pub fn try_recv(&self) -> Option<ControlCommand> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits for the next command, `None` if no more commands can arrive
+    pub fn recv(&self) -> Option<ControlCommand> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Parses a line, `None` for blank lines
+fn parse_line(line: &str) -> Option<Result<ControlCommand, String>> {
+    if line.trim().is_empty() {
+        None
+    } else {
+        Some(parse_command(line))
+    }
+}
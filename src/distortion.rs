@@ -0,0 +1,37 @@
+use crate::{effects::Effect, units::db_to_amplitude};
+
+/// Soft clipping distortion, `drive_db` of gain into a tanh curve scaled so full scale stays
+/// at full scale
+pub struct Distortion {
+    drive: f32,
+    scale: f32,
+}
+
+/// Parses the drive in dB, 0 to 48
+pub fn parse_drive(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(drive_db) if (0.0..=48.0).contains(&drive_db) => Ok(drive_db),
+        _ => Err(format!(
+            "invalid distortion drive `{}`, expected 0 to 48 dB",
+            s
+        )),
+    }
+}
+
+impl Distortion {
+    pub fn new(drive_db: f32) -> Self {
+        let drive = db_to_amplitude(drive_db);
+        Distortion {
+            drive,
+            scale: 1.0 / drive.tanh(),
+        }
+    }
+}
+
+impl Effect for Distortion {
+    fn process(&mut self, buffer: &mut [f32]) {
+        for sample in buffer {
+            *sample = (*sample * self.drive).tanh() * self.scale;
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// Dither added before quantizing to 16-bit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dither {
+    None,
+    /// Triangular PDF dither of ±1 LSB
+    Tpdf,
+    /// TPDF dither with second order noise shaping, moving the noise up to where it's less audible
+    Shaped,
+}
+
+impl fmt::Display for Dither {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dither::None => write!(f, "none"),
+            Dither::Tpdf => write!(f, "tpdf"),
+            Dither::Shaped => write!(f, "shaped"),
+        }
+    }
+}
+
+pub fn parse_dither(s: &str) -> Result<Dither, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "none" => Ok(Dither::None),
+        "tpdf" => Ok(Dither::Tpdf),
+        "shaped" => Ok(Dither::Shaped),
+        _ => Err(format!(
+            "invalid dither `{}`, expected none, tpdf or shaped",
+            s
+        )),
+    }
+}
+
+pub fn parse_bit_depth(s: &str) -> Result<u16, String> {
+    match s.trim().trim_end_matches(['f', 'F']) {
+        "16" => Ok(16),
+        "32" => Ok(32),
+        _ => Err(format!(
+            "invalid bit depth `{}`, expected 16 or 32 (float)",
+            s
+        )),
+    }
+}
+
+/// Converts float frames to 16-bit integers with dither
+pub struct Quantizer {
+    dither: Dither,
+    // Fixed seed so renders stay reproducible
+    rng: StdRng,
+    /// Last two quantization errors of each channel, for noise shaping
+    errors: Vec<[f32; 2]>,
+}
+
+impl Quantizer {
+    pub fn new(dither: Dither, num_channel: u16) -> Self {
+        Quantizer {
+            dither,
+            rng: StdRng::seed_from_u64(0),
+            errors: vec![[0.0; 2]; num_channel as usize],
+        }
+    }
+
+    pub fn quantize(&mut self, frame: &[f32]) -> Vec<i16> {
+        frame
+            .iter()
+            .zip(self.errors.iter_mut())
+            .map(|(&sample, errors)| {
+                let scaled = sample * 32767.0;
+                let target = match self.dither {
+                    // (1 - z^-1)^2 error feedback
+                    Dither::Shaped => scaled - 2.0 * errors[0] + errors[1],
+                    _ => scaled,
+                };
+                let noise = match self.dither {
+                    Dither::None => 0.0,
+                    Dither::Tpdf | Dither::Shaped => {
+                        self.rng.random::<f32>() - self.rng.random::<f32>()
+                    }
+                };
+                let quantized = (target + noise).round().clamp(-32768.0, 32767.0);
+                *errors = [(quantized - target).clamp(-2.0, 2.0), errors[0]];
+                quantized as i16
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,40 @@
+//! `--drum-remap`: moves drum notes to other keys before they reach the drum kit, so MIDIs
+//! written for kits with another layout (e.g. XG SFX kits) hit sensible GM drum sounds.
+
+/// Parses an entry like `36:35` (`source key:destination key`)
+pub fn parse_drum_remap(s: &str) -> Result<(u8, u8), String> {
+    let invalid = || {
+        format!(
+            "invalid drum remap `{}`, expected <key 0-127>:<key 0-127>",
+            s
+        )
+    };
+    let (source, destination) = s.trim().split_once(':').ok_or_else(invalid)?;
+    let key = |key: &str| {
+        key.trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|&key| key < 128)
+            .ok_or_else(invalid)
+    };
+    Ok((key(source)?, key(destination)?))
+}
+
+/// Reads `--drum-remap-file`: one entry per line or separated by commas, `#` starts a comment
+pub fn load_drum_remap(path: &str) -> Result<Vec<(u8, u8)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    text.lines()
+        .flat_map(|line| line.split('#').next().unwrap_or_default().split(','))
+        .filter(|entry| !entry.trim().is_empty())
+        .map(parse_drum_remap)
+        .collect()
+}
+
+/// Destination of every key, later entries win
+pub fn drum_remap_table(entries: &[(u8, u8)]) -> [u8; 128] {
+    let mut table = std::array::from_fn(|key| key as u8);
+    for &(source, destination) in entries {
+        table[source as usize] = destination;
+    }
+    table
+}
@@ -0,0 +1,32 @@
+/// Audio effect applied to interleaved output buffers before the limiter
+pub trait Effect: Send {
+    fn process(&mut self, buffer: &mut [f32]);
+}
+
+/// Effects applied in order
+#[derive(Default)]
+pub struct EffectChain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl EffectChain {
+    pub fn push(&mut self, effect: impl Effect + 'static) {
+        self.effects.push(Box::new(effect));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn process(&mut self, buffer: &mut [f32]) {
+        for effect in &mut self.effects {
+            effect.process(buffer);
+        }
+    }
+}
+
+impl Effect for EffectChain {
+    fn process(&mut self, buffer: &mut [f32]) {
+        EffectChain::process(self, buffer);
+    }
+}
@@ -0,0 +1,123 @@
+//! `--effects-config`: effect chains from a YAML file, one for the whole mix and insert effects
+//! for single MIDI channels.
+//!
+//! ```yaml
+//! master:              # after --eq and --compressor
+//!   - compressor: -18:3:10:150:2
+//! channels:
+//!   1:                 # MIDI channel 1-16
+//!     - distortion: 18 # drive in dB
+//!     - eq: highpass:80,peak:2500:-3
+//! ```
+//!
+//! Effects take the values of their command line option: `eq` the bands of `--eq` separated by
+//! commas, `compressor` the settings of `--compressor` and `distortion` the drive in dB.
+
+use std::path::Path;
+
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::{
+    compressor::{Compressor, parse_compressor},
+    distortion::{Distortion, parse_drive},
+    effects::EffectChain,
+    eq::{Equalizer, parse_eq_band},
+};
+
+pub struct EffectsConfig {
+    pub master: EffectChain,
+    /// Insert effects by MIDI channel (0-15)
+    pub channels: Vec<(u8, EffectChain)>,
+}
+
+fn yaml_value(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(value) | Yaml::Real(value) => Some(value.clone()),
+        Yaml::Integer(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Builds a chain from a list of `kind: value` effects
+fn effect_chain(effects: &Yaml, sample_rate: u32, num_channel: u16) -> Result<EffectChain, String> {
+    let mut chain = EffectChain::default();
+    let effects = match effects {
+        Yaml::Array(effects) => effects.as_slice(),
+        Yaml::BadValue | Yaml::Null => return Ok(chain),
+        _ => return Err("effects must be a list".to_string()),
+    };
+    for effect in effects {
+        let Some((kind, value)) = effect
+            .as_hash()
+            .filter(|effect| effect.len() == 1)
+            .and_then(|effect| effect.front())
+        else {
+            return Err("every effect must be a single `kind: value` mapping".to_string());
+        };
+        let kind = kind.as_str().unwrap_or_default();
+        let value =
+            yaml_value(value).ok_or_else(|| format!("invalid value for effect `{}`", kind))?;
+        match kind {
+            "eq" => {
+                let bands = value
+                    .split(',')
+                    .map(parse_eq_band)
+                    .collect::<Result<Vec<_>, _>>()?;
+                chain.push(Equalizer::new(&bands, sample_rate, num_channel));
+            }
+            "compressor" => chain.push(Compressor::new(
+                parse_compressor(&value)?,
+                sample_rate,
+                num_channel,
+            )),
+            "distortion" => chain.push(Distortion::new(parse_drive(&value)?)),
+            kind => return Err(format!("unknown effect `{}`", kind)),
+        }
+    }
+    Ok(chain)
+}
+
+/// Reads an effects config. The master chain runs at the output `sample_rate`, the channel
+/// inserts inside the synth at `render_rate`.
+pub fn load_effects_config(
+    path: impl AsRef<Path>,
+    sample_rate: u32,
+    render_rate: u32,
+    num_channel: u16,
+) -> Result<EffectsConfig, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let documents = YamlLoader::load_from_str(&text).map_err(|e| e.to_string())?;
+    let Some(document) = documents.first() else {
+        return Ok(EffectsConfig {
+            master: EffectChain::default(),
+            channels: Vec::new(),
+        });
+    };
+
+    let master = effect_chain(&document["master"], sample_rate, num_channel)
+        .map_err(|e| format!("master: {}", e))?;
+    let mut channels = Vec::new();
+    match &document["channels"] {
+        Yaml::Hash(entries) => {
+            for (channel, effects) in entries {
+                let channel = channel
+                    .as_i64()
+                    .filter(|channel| (1..=16).contains(channel))
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid channel {:?}, expected a MIDI channel 1-16",
+                            channel
+                        )
+                    })?;
+                let chain = effect_chain(effects, render_rate, num_channel)
+                    .map_err(|e| format!("channel {}: {}", channel, e))?;
+                if !chain.is_empty() {
+                    channels.push((channel as u8 - 1, chain));
+                }
+            }
+        }
+        Yaml::BadValue | Yaml::Null => {}
+        _ => return Err("`channels` must map MIDI channels to effects".to_string()),
+    }
+    Ok(EffectsConfig { master, channels })
+}
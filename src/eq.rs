@@ -0,0 +1,176 @@
+use std::f32::consts::PI;
+
+use crate::effects::Effect;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EqBandKind {
+    LowShelf,
+    HighShelf,
+    Peak,
+    LowPass,
+    HighPass,
+}
+
+/// One band of the parametric EQ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    pub kind: EqBandKind,
+    pub frequency: f32,
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+/// Parses a band like `lowshelf:100:-3`, `peak:3000:2:1.0` or `lowpass:18000`
+/// (`kind:frequency[:gain_db][:q]`, pass filters have no gain)
+pub fn parse_eq_band(s: &str) -> Result<EqBand, String> {
+    let mut parts = s.trim().split(':');
+    let kind = match parts.next().unwrap_or_default().to_lowercase().as_str() {
+        "lowshelf" => EqBandKind::LowShelf,
+        "highshelf" => EqBandKind::HighShelf,
+        "peak" | "bell" => EqBandKind::Peak,
+        "lowpass" => EqBandKind::LowPass,
+        "highpass" => EqBandKind::HighPass,
+        kind => return Err(format!("unknown EQ band type `{}`", kind)),
+    };
+
+    let values = parts
+        .map(|value| value.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid EQ band `{}`", s))?;
+
+    let has_gain = !matches!(kind, EqBandKind::LowPass | EqBandKind::HighPass);
+    let (frequency, gain_db, q) = match (has_gain, values.as_slice()) {
+        (true, &[frequency, gain_db]) => (frequency, gain_db, std::f32::consts::FRAC_1_SQRT_2),
+        (true, &[frequency, gain_db, q]) => (frequency, gain_db, q),
+        (false, &[frequency]) => (frequency, 0.0, std::f32::consts::FRAC_1_SQRT_2),
+        (false, &[frequency, q]) => (frequency, 0.0, q),
+        _ => return Err(format!("invalid EQ band `{}`", s)),
+    };
+    if frequency <= 0.0 || q <= 0.0 {
+        return Err(format!("invalid EQ band `{}`", s));
+    }
+
+    Ok(EqBand {
+        kind,
+        frequency,
+        gain_db,
+        q,
+    })
+}
+
+/// Biquad filter using the coefficients from the RBJ Audio EQ Cookbook
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    // Transposed direct form II state per channel
+    state: Vec<[f32; 2]>,
+}
+
+impl Biquad {
+    fn new(band: &EqBand, sample_rate: f32, num_channel: usize) -> Self {
+        // Keep the frequency below Nyquist so the coefficients stay stable
+        let frequency = band.frequency.min(sample_rate * 0.49);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * band.q);
+        let a = 10f32.powf(band.gain_db / 40.0);
+
+        let (b0, b1, b2, a0, a1, a2) = match band.kind {
+            EqBandKind::Peak => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            EqBandKind::LowShelf => {
+                let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha),
+                    (a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha,
+                )
+            }
+            EqBandKind::HighShelf => {
+                let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha),
+                    (a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha,
+                )
+            }
+            EqBandKind::LowPass => (
+                (1.0 - cos) / 2.0,
+                1.0 - cos,
+                (1.0 - cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            EqBandKind::HighPass => (
+                (1.0 + cos) / 2.0,
+                -(1.0 + cos),
+                (1.0 + cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+        };
+
+        Biquad {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            state: vec![[0.0; 2]; num_channel],
+        }
+    }
+
+    fn process(&mut self, buffer: &mut [f32]) {
+        let num_channel = self.state.len();
+        for frame in buffer.chunks_exact_mut(num_channel) {
+            for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let input = *sample;
+                let output = self.b0 * input + state[0];
+                state[0] = self.b1 * input - self.a1 * output + state[1];
+                state[1] = self.b2 * input - self.a2 * output;
+                *sample = output;
+            }
+        }
+    }
+}
+
+/// Parametric EQ made of cascaded biquad bands
+pub struct Equalizer {
+    bands: Vec<Biquad>,
+}
+
+impl Equalizer {
+    pub fn new(bands: &[EqBand], sample_rate: u32, num_channel: u16) -> Self {
+        Equalizer {
+            bands: bands
+                .iter()
+                .map(|band| Biquad::new(band, sample_rate as f32, num_channel as usize))
+                .collect(),
+        }
+    }
+}
+
+impl Effect for Equalizer {
+    fn process(&mut self, buffer: &mut [f32]) {
+        for band in &mut self.bands {
+            band.process(buffer);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Process exit codes and error reporting. Every error the renderer exits on has a kind with its
+//! own exit code, so wrappers can tell a broken MIDI from a full disk without parsing messages.
+
+use std::fmt;
+
+use log::error;
+
+/// Exit codes listed in `--help`
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0    Success
+  1    Other errors, or a job of --job-list or a case of --self-test failed
+  2    Invalid command line options
+  3    The MIDI file is missing or can't be read
+  4    Samples are missing (--fail-on-missing-samples, or a sample folder without samples)
+  5    Reading or writing a file or device failed
+  6    Rendering was cancelled, the output rendered so far is kept
+  7    The output clipped (--fail-on-clipping), the output is kept
+  130  Interrupted with Ctrl+C";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    /// A job of `--job-list` or a case of `--self-test` failed
+    Failed,
+    Usage,
+    BadMidi,
+    MissingSamples,
+    Io,
+    Cancelled,
+    Clipping,
+    Interrupted,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Failed => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::BadMidi => 3,
+            ErrorKind::MissingSamples => 4,
+            ErrorKind::Io => 5,
+            ErrorKind::Cancelled => 6,
+            ErrorKind::Clipping => 7,
+            ErrorKind::Interrupted => 130,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Failed => write!(f, "failed"),
+            ErrorKind::Usage => write!(f, "usage"),
+            ErrorKind::BadMidi => write!(f, "bad_midi"),
+            ErrorKind::MissingSamples => write!(f, "missing_samples"),
+            ErrorKind::Io => write!(f, "io"),
+            ErrorKind::Cancelled => write!(f, "cancelled"),
+            ErrorKind::Clipping => write!(f, "clipping"),
+            ErrorKind::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// Logs an error with its kind and exit code, `error kind=... exit_code=... message=...` in
+/// key=value format
+pub fn report(kind: ErrorKind, message: impl fmt::Display) {
+    error!(
+        kind:% = kind,
+        exit_code = kind.exit_code(),
+        message:% = message;
+        "{}",
+        message
+    );
+}
+
+/// Logs an error and exits with the code of its kind
+pub fn fail(kind: ErrorKind, message: impl fmt::Display) -> ! {
+    report(kind, message);
+    std::process::exit(kind.exit_code());
+}
+
+/// Error that stops a render, returned up to `main` and reported there
+#[derive(Debug)]
+pub struct RenderError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl RenderError {
+    pub fn new(kind: ErrorKind, message: impl fmt::Display) -> Self {
+        RenderError {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    /// IO error with what was being done, e.g. `RenderError::io("Failed to write sample", e)`
+    pub fn io(context: &str, error: impl fmt::Display) -> Self {
+        RenderError::new(ErrorKind::Io, format!("{}: {}", context, error))
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RenderError {}
@@ -0,0 +1,79 @@
+use std::collections::VecDeque;
+
+/// Linear fade-in from the first output frame and fade-out over the last output frames.
+/// The end of the render isn't known in advance (e.g. with `--tail auto`), so the last
+/// `fade_out_frames` are held back until `finish()`.
+pub struct Fader {
+    num_channel: usize,
+    fade_in_frames: u64,
+    fade_out_frames: usize,
+    position: u64,
+    pending: VecDeque<f32>,
+}
+
+impl Fader {
+    pub fn new(num_channel: u16, fade_in_frames: u64, fade_out_frames: u64) -> Self {
+        let fade_out_frames = fade_out_frames as usize;
+        Fader {
+            num_channel: num_channel as usize,
+            fade_in_frames,
+            fade_out_frames,
+            position: 0,
+            pending: VecDeque::with_capacity(fade_out_frames * num_channel as usize),
+        }
+    }
+
+    /// Applies the fade-in and returns the samples that are ready to be written
+    pub fn process(&mut self, buffer: &[f32]) -> Vec<f32> {
+        for frame in buffer.chunks_exact(self.num_channel) {
+            let gain = if self.position < self.fade_in_frames {
+                self.position as f32 / self.fade_in_frames as f32
+            } else {
+                1.0
+            };
+            self.pending
+                .extend(frame.iter().map(|sample| sample * gain));
+            self.position += 1;
+        }
+
+        let held_samples = self.fade_out_frames * self.num_channel;
+        let ready = self.pending.len().saturating_sub(held_samples);
+        self.pending.drain(..ready).collect()
+    }
+
+    /// Applies the fade-out to the held back samples and returns them
+    pub fn finish(self) -> Vec<f32> {
+        let mut output: Vec<f32> = self.pending.into();
+        let frame_count = output.len() / self.num_channel;
+        for (i, frame) in output.chunks_exact_mut(self.num_channel).enumerate() {
+            let gain = (frame_count - i - 1) as f32 / self.fade_out_frames as f32;
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fades_in_and_out() {
+        // (channels, fade in frames, fade out frames, input, processed, finished)
+        type Case<'a> = (u16, u64, u64, &'a [f32], &'a [f32], &'a [f32]);
+        let cases: [Case; 4] = [
+            (1, 2, 2, &[1.0; 4], &[0.0, 0.5], &[0.5, 0.0]),
+            (1, 0, 0, &[1.0; 3], &[1.0; 3], &[]),
+            (2, 2, 1, &[1.0; 6], &[0.0, 0.0, 0.5, 0.5], &[0.0, 0.0]),
+            // A fade-out longer than the output starts below full level
+            (1, 0, 4, &[1.0; 2], &[], &[0.25, 0.0]),
+        ];
+        for (num_channel, fade_in, fade_out, input, processed, finished) in cases {
+            let mut fader = Fader::new(num_channel, fade_in, fade_out);
+            assert_eq!(fader.process(input), processed);
+            assert_eq!(fader.finish(), finished);
+        }
+    }
+}
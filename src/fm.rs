@@ -0,0 +1,266 @@
+//! Small FM synthesizer for the built-in samples, with presets for the GM programs additive
+//! synthesis can't do well: electric pianos, chromatic percussion and brass.
+
+use std::f32::consts::TAU;
+
+/// Operator of an FM preset. Modulators' `level` is their modulation index in radians,
+/// carriers' their output level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Operator {
+    /// Frequency relative to the note
+    pub ratio: f32,
+    pub level: f32,
+    /// Attack time in seconds
+    pub attack: f32,
+    /// Time constant of the decay towards `sustain`, in seconds
+    pub decay: f32,
+    pub sustain: f32,
+}
+
+impl Operator {
+    const OFF: Operator = op(1.0, 0.0, 0.0, 1.0, 0.0);
+
+    fn envelope(&self, t: f32) -> f32 {
+        let attack = if t < self.attack {
+            t / self.attack
+        } else {
+            1.0
+        };
+        let decay = self.sustain + (1.0 - self.sustain) * (-t / self.decay).exp();
+        attack * decay * self.level
+    }
+}
+
+const fn op(ratio: f32, level: f32, attack: f32, decay: f32, sustain: f32) -> Operator {
+    Operator {
+        ratio,
+        level,
+        attack,
+        decay,
+        sustain,
+    }
+}
+
+/// How the operators are connected, operator 0 is always a carrier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// 1 -> 0, operators 2 and 3 are unused
+    Pair,
+    /// 3 -> 2 -> 1 -> 0
+    Stack,
+    /// 1 -> 0 and 3 -> 2, both carriers mixed
+    TwoPairs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FmPreset {
+    pub algorithm: Algorithm,
+    pub operators: [Operator; 4],
+    /// Self modulation of the top modulator, brightens it towards a sawtooth
+    pub feedback: f32,
+}
+
+const fn pair(carrier: Operator, modulator: Operator, feedback: f32) -> FmPreset {
+    FmPreset {
+        algorithm: Algorithm::Pair,
+        operators: [carrier, modulator, Operator::OFF, Operator::OFF],
+        feedback,
+    }
+}
+
+const fn two_pairs(operators: [Operator; 4], feedback: f32) -> FmPreset {
+    FmPreset {
+        algorithm: Algorithm::TwoPairs,
+        operators,
+        feedback,
+    }
+}
+
+/// Preset of a GM program (0-127), `None` for the programs without one
+pub fn fm_preset(program: u8) -> Option<FmPreset> {
+    Some(match program {
+        // Electric Piano 1: the body and a short bright tine
+        4 => two_pairs(
+            [
+                op(1.0, 1.0, 0.002, 1.5, 0.0),
+                op(1.0, 1.2, 0.0, 0.8, 0.0),
+                op(1.0, 0.4, 0.001, 0.3, 0.0),
+                op(14.0, 2.0, 0.0, 0.05, 0.0),
+            ],
+            0.0,
+        ),
+        // Electric Piano 2
+        5 => two_pairs(
+            [
+                op(1.0, 1.0, 0.002, 2.0, 0.0),
+                op(1.0, 2.0, 0.0, 1.2, 0.0),
+                op(1.0, 0.5, 0.001, 0.4, 0.0),
+                op(14.0, 3.5, 0.0, 0.08, 0.0),
+            ],
+            0.0,
+        ),
+        // Celesta
+        8 => pair(
+            op(1.0, 1.0, 0.001, 1.2, 0.0),
+            op(3.0, 1.5, 0.0, 0.6, 0.0),
+            0.0,
+        ),
+        // Glockenspiel
+        9 => pair(
+            op(1.0, 1.0, 0.001, 3.0, 0.0),
+            op(3.5, 2.0, 0.0, 1.5, 0.0),
+            0.0,
+        ),
+        // Music Box
+        10 => pair(
+            op(1.0, 1.0, 0.001, 2.0, 0.0),
+            op(4.0, 1.0, 0.0, 1.0, 0.0),
+            0.0,
+        ),
+        // Vibraphone
+        11 => pair(
+            op(1.0, 1.0, 0.002, 4.0, 0.0),
+            op(4.0, 0.8, 0.0, 0.5, 0.0),
+            0.0,
+        ),
+        // Marimba
+        12 => pair(
+            op(1.0, 1.0, 0.001, 0.6, 0.0),
+            op(4.0, 1.5, 0.0, 0.05, 0.0),
+            0.0,
+        ),
+        // Xylophone
+        13 => pair(
+            op(1.0, 1.0, 0.001, 0.35, 0.0),
+            op(3.0, 2.0, 0.0, 0.03, 0.0),
+            0.0,
+        ),
+        // Tubular Bells
+        14 => pair(
+            op(1.0, 1.0, 0.001, 6.0, 0.0),
+            op(3.5, 3.0, 0.0, 3.0, 0.0),
+            0.0,
+        ),
+        // Dulcimer
+        15 => pair(
+            op(1.0, 1.0, 0.001, 1.5, 0.0),
+            op(2.0, 2.0, 0.0, 0.5, 0.0),
+            0.0,
+        ),
+        // Trumpet
+        56 => pair(
+            op(1.0, 1.0, 0.03, 1.0, 0.8),
+            op(1.0, 2.5, 0.05, 0.5, 0.7),
+            0.5,
+        ),
+        // Trombone
+        57 => pair(
+            op(1.0, 1.0, 0.04, 1.0, 0.8),
+            op(1.0, 2.0, 0.06, 0.5, 0.6),
+            0.4,
+        ),
+        // Tuba
+        58 => pair(
+            op(1.0, 1.0, 0.05, 1.0, 0.8),
+            op(1.0, 1.5, 0.07, 0.5, 0.5),
+            0.3,
+        ),
+        // Muted Trumpet
+        59 => pair(
+            op(1.0, 1.0, 0.02, 1.0, 0.7),
+            op(3.0, 1.2, 0.03, 0.4, 0.6),
+            0.6,
+        ),
+        // French Horn
+        60 => pair(
+            op(1.0, 1.0, 0.08, 1.5, 0.8),
+            op(1.0, 1.2, 0.1, 0.8, 0.6),
+            0.2,
+        ),
+        // Brass Section: two slightly detuned brass pairs
+        61 => two_pairs(
+            [
+                op(1.0, 1.0, 0.04, 1.0, 0.8),
+                op(1.0, 2.5, 0.06, 0.5, 0.7),
+                op(1.005, 1.0, 0.04, 1.0, 0.8),
+                op(1.005, 2.5, 0.06, 0.5, 0.7),
+            ],
+            0.5,
+        ),
+        // Synth Brass 1
+        62 => pair(
+            op(1.0, 1.0, 0.01, 2.0, 0.7),
+            op(1.0, 3.0, 0.02, 0.6, 0.5),
+            1.0,
+        ),
+        // Synth Brass 2
+        63 => two_pairs(
+            [
+                op(1.0, 1.0, 0.05, 2.0, 0.8),
+                op(1.0, 2.0, 0.1, 1.0, 0.6),
+                op(0.995, 1.0, 0.05, 2.0, 0.8),
+                op(0.995, 2.0, 0.1, 1.0, 0.6),
+            ],
+            0.8,
+        ),
+        _ => return None,
+    })
+}
+
+/// Renders `preset` playing at `freq`. Modulation is reduced on high notes so the sidebands
+/// don't alias.
+pub fn generate_fm_sample(
+    preset: &FmPreset,
+    sample_rate: u32,
+    freq: f32,
+    sample_count: usize,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let index_scale = (2000.0 / freq).min(1.0);
+    let mut phases = [0.0f32; 4];
+    let mut feedback_sample = 0.0;
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let levels = preset.operators.map(|operator| operator.envelope(t));
+        // Output of operator `n` with `modulation` added to its phase
+        let mut output = |n: usize, modulation: f32| {
+            let value = (phases[n] + modulation).sin() * levels[n];
+            phases[n] = (phases[n] + TAU * freq * preset.operators[n].ratio / sample_rate as f32)
+                .rem_euclid(TAU);
+            value
+        };
+
+        let sample = match preset.algorithm {
+            Algorithm::Pair => {
+                let modulator = output(1, feedback_sample * preset.feedback);
+                feedback_sample = modulator;
+                output(0, modulator * index_scale)
+            }
+            Algorithm::Stack => {
+                let top = output(3, feedback_sample * preset.feedback);
+                feedback_sample = top;
+                let middle = output(2, top * index_scale);
+                let modulator = output(1, middle * index_scale);
+                output(0, modulator * index_scale)
+            }
+            Algorithm::TwoPairs => {
+                let modulator = output(1, feedback_sample * preset.feedback);
+                feedback_sample = modulator;
+                let first = output(0, modulator * index_scale);
+                let modulator = output(3, 0.0);
+                first + output(2, modulator * index_scale)
+            }
+        };
+        float_samples.push(sample);
+    }
+
+    let peak = float_samples
+        .iter()
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    let scale = if peak > 0.0 { 0.6 / peak } else { 0.0 };
+    float_samples
+        .into_iter()
+        .map(|sample| (sample * scale * i16::MAX as f32) as i16)
+        .collect()
+}
@@ -0,0 +1,90 @@
+use std::{collections::BTreeMap, fs, io::Write, path::PathBuf};
+
+use serde_json::json;
+
+use crate::synth_event::SynthEvent;
+
+/// Colors per MIDI channel, in the order most piano-roll visualizers use
+const CHANNEL_COLORS: [&str; 16] = [
+    "#3366FF", "#FF7E33", "#33FF66", "#FF3381", "#33FFFF", "#E433FF", "#99FF33", "#4B33FF",
+    "#FFCC33", "#33B4FF", "#FF3333", "#33FFB1", "#FF33CC", "#4EFF33", "#9933FF", "#E7FF33",
+];
+
+/// Writes a JSON snapshot of the sounding notes for every video frame, so external
+/// piano-roll visualizers can sync to the rendered audio
+pub struct FrameDumper {
+    dir: PathBuf,
+    fps: f64,
+    next_frame: u64,
+    // (channel, key) -> velocity
+    active_notes: BTreeMap<(u8, u8), u16>,
+}
+
+impl FrameDumper {
+    pub fn new(dir: impl Into<PathBuf>, fps: f64) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FrameDumper {
+            dir,
+            fps,
+            next_frame: 0,
+            active_notes: BTreeMap::new(),
+        })
+    }
+
+    /// Writes all frames before `time` (in seconds) with the current note state
+    pub fn advance(&mut self, time: f64) -> std::io::Result<()> {
+        while (self.next_frame as f64) / self.fps < time {
+            self.write_frame()?;
+            self.next_frame += 1;
+        }
+        Ok(())
+    }
+
+    pub fn handle_event(&mut self, event: &SynthEvent) {
+        match *event {
+            SynthEvent::NoteOn {
+                channel,
+                key,
+                velocity,
+            } => {
+                self.active_notes.insert((channel, key), velocity);
+            }
+            SynthEvent::NoteOff { channel, key, .. } => {
+                self.active_notes.remove(&(channel, key));
+            }
+            _ => {}
+        }
+    }
+
+    /// Number of frames written so far
+    pub fn frame_count(&self) -> u64 {
+        self.next_frame
+    }
+
+    fn write_frame(&self) -> std::io::Result<()> {
+        let notes: Vec<_> = self
+            .active_notes
+            .iter()
+            .map(|(&(channel, key), &velocity)| {
+                json!({
+                    "channel": channel,
+                    "key": key,
+                    // Scaled back to the MIDI 1.0 range most visualizers expect
+                    "velocity": velocity >> 9,
+                    "color": CHANNEL_COLORS[channel as usize & 0x0F],
+                })
+            })
+            .collect();
+        let frame = json!({
+            "frame": self.next_frame,
+            "time": self.next_frame as f64 / self.fps,
+            "notes": notes,
+        });
+
+        let path = self.dir.join(format!("frame_{:06}.json", self.next_frame));
+        let mut file = std::io::BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer(&mut file, &frame)?;
+        file.flush()
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::mpsc::{self, Receiver};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    Pause,
+    Resume,
+    /// Stop rendering and finalize the output written so far
+    Quit,
+    /// Ctrl+C, which doesn't raise a signal while the terminal is in raw mode
+    Interrupt,
+}
+
+/// Reads single key presses from the terminal on a background thread while the progress bar is shown.
+/// The terminal is put into raw mode until this is dropped.
+pub struct HotkeyReader {
+    receiver: Receiver<Hotkey>,
+}
+
+impl HotkeyReader {
+    pub fn start() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // Raw mode also turns off output processing on Unix, which breaks the
+        // progress bar's line breaks, so turn that back on
+        #[cfg(unix)]
+        unsafe {
+            let mut termios = std::mem::zeroed::<libc::termios>();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+                termios.c_oflag |= libc::OPOST;
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(event) = event::read() {
+                let Event::Key(key) = event else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                let hotkey = match key.code {
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        Hotkey::Interrupt
+                    }
+                    KeyCode::Char('p' | 'P') => Hotkey::Pause,
+                    KeyCode::Char('r' | 'R') => Hotkey::Resume,
+                    KeyCode::Char('q' | 'Q') => Hotkey::Quit,
+                    _ => continue,
+                };
+                if sender.send(hotkey).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(HotkeyReader { receiver })
+    }
+
+    /// Returns a pending key press without blocking
+    pub fn try_recv(&self) -> Option<Hotkey> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits for the next key press
+    pub fn recv(&self) -> Option<Hotkey> {
+        self.receiver.recv().ok()
+    }
+
+    /// Leaves raw mode, for exiting without dropping the reader
+    pub fn restore_terminal(&self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Drop for HotkeyReader {
+    fn drop(&mut self) {
+        self.restore_terminal();
+    }
+}
@@ -0,0 +1,174 @@
+//! `--humanize-timing` and `--humanize-velocity`: random jitter on the note events of
+//! quantized MIDIs. Note offs move with their note on so note lengths are kept, and the
+//! jitter is seeded so every pass over the events (statistics, polyphony plan, render) sees
+//! the same performance.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::synth_event::SynthEvent;
+
+/// One MIDI 1.0 velocity step in the 16-bit velocities of `SynthEvent`
+const VELOCITY_STEP: i32 = 1 << 9;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Humanize {
+    /// Largest timing offset in seconds, either way
+    pub timing: f64,
+    /// Largest velocity change in MIDI 1.0 steps, either way
+    pub velocity: u8,
+    pub seed: u64,
+}
+
+impl Humanize {
+    /// Applies the jitter to `(delta seconds, event)` pairs
+    pub fn apply<I>(self, events: I) -> Humanized<I>
+    where
+        I: Iterator<Item = (f64, Option<SynthEvent>)>,
+    {
+        Humanized {
+            events,
+            settings: self,
+            rng: StdRng::seed_from_u64(self.seed),
+            input_time: 0.0,
+            output_time: 0.0,
+            note_offsets: HashMap::new(),
+            note_off_times: HashMap::new(),
+            pending: BinaryHeap::new(),
+            sequence: 0,
+            finished: false,
+        }
+    }
+}
+
+/// Event waiting for its shifted time, ordered by time and then by input order
+pub(crate) struct Pending {
+    pub time: f64,
+    pub sequence: u64,
+    pub event: Option<SynthEvent>,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time
+            .total_cmp(&other.time)
+            .then(self.sequence.cmp(&other.sequence))
+    }
+}
+
+pub struct Humanized<I> {
+    events: I,
+    settings: Humanize,
+    rng: StdRng,
+    input_time: f64,
+    output_time: f64,
+    /// Timing offset of the held notes by channel and key, reused for their note offs
+    note_offsets: HashMap<(u8, u8), f64>,
+    /// Shifted time of the last note off by channel and key, a retriggered note never starts
+    /// before it or the note off would end the new note
+    note_off_times: HashMap<(u8, u8), f64>,
+    pending: BinaryHeap<Reverse<Pending>>,
+    sequence: u64,
+    finished: bool,
+}
+
+impl<I> Humanized<I> {
+    /// Shifted time and velocity of an event at the current input time
+    fn humanize(&mut self, event: Option<SynthEvent>) -> (f64, Option<SynthEvent>) {
+        let time = self.input_time;
+        match event {
+            Some(SynthEvent::NoteOn {
+                channel,
+                key,
+                velocity,
+            }) => {
+                let offset = if self.settings.timing > 0.0 {
+                    self.rng
+                        .random_range(-self.settings.timing..=self.settings.timing)
+                } else {
+                    0.0
+                };
+                let shifted = (time + offset).max(0.0).max(
+                    self.note_off_times
+                        .get(&(channel, key))
+                        .copied()
+                        .unwrap_or(0.0),
+                );
+                self.note_offsets.insert((channel, key), shifted - time);
+                let amount = self.settings.velocity as i32;
+                let velocity =
+                    velocity as i32 + self.rng.random_range(-amount..=amount) * VELOCITY_STEP;
+                let event = SynthEvent::NoteOn {
+                    channel,
+                    key,
+                    velocity: velocity.clamp(VELOCITY_STEP, u16::MAX as i32) as u16,
+                };
+                (shifted, Some(event))
+            }
+            Some(SynthEvent::NoteOff { channel, key, .. }) => {
+                let offset = self.note_offsets.remove(&(channel, key)).unwrap_or(0.0);
+                let shifted = (time + offset).max(0.0);
+                self.note_off_times.insert((channel, key), shifted);
+                (shifted, event)
+            }
+            event => (time, event),
+        }
+    }
+}
+
+impl<I> Iterator for Humanized<I>
+where
+    I: Iterator<Item = (f64, Option<SynthEvent>)>,
+{
+    type Item = (f64, Option<SynthEvent>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // Later events can be moved up to `timing` earlier, so the first pending event is
+            // only final once the input is that far past it
+            if let Some(Reverse(first)) = self.pending.peek()
+                && (self.finished || first.time <= self.input_time - self.settings.timing)
+            {
+                let Reverse(first) = self.pending.pop()?;
+                let delta = (first.time - self.output_time).max(0.0);
+                self.output_time = self.output_time.max(first.time);
+                return Some((delta, first.event));
+            }
+            if self.finished {
+                return None;
+            }
+
+            match self.events.next() {
+                Some((delta, event)) => {
+                    self.input_time += delta;
+                    let (time, event) = self.humanize(event);
+                    self.pending.push(Reverse(Pending {
+                        time,
+                        sequence: self.sequence,
+                        event,
+                    }));
+                    self.sequence += 1;
+                }
+                None => self.finished = true,
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Translated messages for the human readable output, selected with `--lang` or the system
+//! locale. The catalogs are the Fluent files in `locales/`, messages missing from a catalog
+//! fall back to English. Headless key=value and JSON output is never translated.
+
+use std::{fmt, sync::OnceLock};
+
+use fluent_bundle::{FluentArgs, FluentResource, concurrent::FluentBundle};
+use unic_langid::LanguageIdentifier;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    En,
+    Ja,
+}
+
+impl Lang {
+    /// Language of the system locale, English unless it's Japanese
+    pub fn detect() -> Self {
+        match sys_locale::get_locale() {
+            Some(locale) if locale.to_ascii_lowercase().starts_with("ja") => Lang::Ja,
+            _ => Lang::En,
+        }
+    }
+
+    fn catalog(self) -> &'static str {
+        match self {
+            Lang::En => include_str!("../locales/en.ftl"),
+            Lang::Ja => include_str!("../locales/ja.ftl"),
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lang::En => write!(f, "en"),
+            Lang::Ja => write!(f, "ja"),
+        }
+    }
+}
+
+pub fn parse_lang(s: &str) -> Result<Lang, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "en" | "english" => Ok(Lang::En),
+        "ja" | "japanese" => Ok(Lang::Ja),
+        _ => Err(format!("invalid language `{}`, expected en or ja", s)),
+    }
+}
+
+struct Catalogs {
+    selected: FluentBundle<FluentResource>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+static CATALOGS: OnceLock<Catalogs> = OnceLock::new();
+
+fn bundle(lang: Lang) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = lang
+        .to_string()
+        .parse()
+        .expect("invalid language identifier");
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Unicode isolation marks around arguments would end up in terminals and log files
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(lang.catalog().to_string())
+        .unwrap_or_else(|(_, errors)| panic!("invalid {} message catalog: {:?}", lang, errors));
+    bundle
+        .add_resource(resource)
+        .unwrap_or_else(|errors| panic!("invalid {} message catalog: {:?}", lang, errors));
+    bundle
+}
+
+/// Selects the language of `tr`, English is used until this is called
+pub fn init(lang: Lang) {
+    let _ = CATALOGS.set(Catalogs {
+        selected: bundle(lang),
+        fallback: bundle(Lang::En),
+    });
+}
+
+/// Formats message `id` with `args`, see the `t!` macro
+pub fn tr(id: &str, args: &[(&str, String)]) -> String {
+    let catalogs = CATALOGS.get_or_init(|| Catalogs {
+        selected: bundle(Lang::En),
+        fallback: bundle(Lang::En),
+    });
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.as_str());
+    }
+    for bundle in [&catalogs.selected, &catalogs.fallback] {
+        if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+            let mut errors = Vec::new();
+            return bundle
+                .format_pattern(pattern, Some(&fluent_args), &mut errors)
+                .into_owned();
+        }
+    }
+    id.to_string()
+}
+
+/// Translated message: `t!("loading-midi", name = midi_file_name)`.
+/// Arguments are formatted with `Display` before they are passed to the catalog.
+#[macro_export]
+macro_rules! t {
+    ($id:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::tr($id, &[$((stringify!($name), $value.to_string())),*])
+    };
+}
+
+pub use t;
@@ -0,0 +1,132 @@
+//! `init`: asks for the sample folder, sample rate and output settings on the terminal and
+//! writes them as the defaults of a job list, with the MIDI files given as its jobs, to render
+//! with `--job-list`.
+
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{dither::parse_bit_depth, i18n::t, midi_input::midi_file_stem};
+
+/// Asks `question` until `parse` accepts the answer, an empty answer is `default`
+fn ask<T>(
+    question: &str,
+    default: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<T, String> {
+    loop {
+        if default.is_empty() {
+            print!("{}: ", question);
+        } else {
+            print!("{} [{}]: ", question, default);
+        }
+        std::io::stdout().flush().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        let read = std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Err("the input ended before every question was answered".to_string());
+        }
+        let answer = match line.trim() {
+            "" => default,
+            answer => answer,
+        };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Absolute path of an answer, the job list may be rendered from another folder
+fn absolute(path: &str) -> Result<PathBuf, String> {
+    std::path::absolute(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Single quoted YAML string
+fn yaml_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn parse_sample_folder(s: &str) -> Result<Option<String>, String> {
+    if s.is_empty() || s.starts_with("http://") || s.starts_with("https://") {
+        return Ok((!s.is_empty()).then(|| s.to_string()));
+    }
+    if !Path::new(s).exists() {
+        return Err(t!("init-not-found", path = s));
+    }
+    Ok(Some(absolute(s)?.display().to_string()))
+}
+
+fn parse_sample_rate(s: &str) -> Result<u32, String> {
+    s.parse()
+        .ok()
+        .filter(|&rate| rate > 0)
+        .ok_or_else(|| format!("invalid sample rate `{}`", s))
+}
+
+fn parse_midi_files(s: &str) -> Result<Vec<PathBuf>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(|path| {
+            if Path::new(path).is_file() {
+                absolute(path)
+            } else {
+                Err(t!("init-not-found", path = path))
+            }
+        })
+        .collect()
+}
+
+/// Runs the wizard and writes the job list to `path`
+pub fn run(path: &str) -> Result<(), String> {
+    if Path::new(path).exists()
+        && !ask(&t!("init-overwrite", path = path), "n", |answer| {
+            Ok(answer.eq_ignore_ascii_case("y"))
+        })?
+    {
+        return Ok(());
+    }
+
+    let sample_folder = ask(&t!("init-sample-folder"), "", parse_sample_folder)?;
+    let sample_rate = ask(&t!("init-sample-rate"), "48000", parse_sample_rate)?;
+    let bit_depth = ask(&t!("init-bit-depth"), "32", parse_bit_depth)?;
+    let midi_files = ask(&t!("init-midi-files"), "", parse_midi_files)?;
+    let output_folder = if midi_files.is_empty() {
+        None
+    } else {
+        Some(ask(&t!("init-output-folder"), "renders", absolute)?)
+    };
+
+    let mut yaml = String::from("defaults:\n");
+    if let Some(sample_folder) = sample_folder {
+        yaml += &format!("  sample-folder-path: {}\n", yaml_string(&sample_folder));
+    }
+    yaml += &format!(
+        "  sample-rate: {}\n  bit-depth: {}\n",
+        sample_rate, bit_depth
+    );
+    match output_folder {
+        Some(output_folder) => {
+            std::fs::create_dir_all(&output_folder)
+                .map_err(|e| format!("{}: {}", output_folder.display(), e))?;
+            yaml += "jobs:\n";
+            for midi_file in &midi_files {
+                let output = output_folder.join(format!("{}.wav", midi_file_stem(midi_file)));
+                yaml += &format!(
+                    "  - input: {}\n    output: {}\n",
+                    yaml_string(&midi_file.display().to_string()),
+                    yaml_string(&output.display().to_string())
+                );
+            }
+        }
+        None => yaml += "jobs: []\n# - input: song.mid\n#   output: renders/song.wav\n",
+    }
+    std::fs::write(path, yaml).map_err(|e| format!("{}: {}", path, e))?;
+    println!("{}", t!("init-written", path = path));
+    Ok(())
+}
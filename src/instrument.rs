@@ -0,0 +1,143 @@
+//! Bank select (CC 0/32) and program change of each MIDI channel. There are two sample banks,
+//! the melodic samples and the drum kit, and the instrument of a channel picks one:
+//!
+//! - channel 10 plays drums, unless a GM2 melodic bank (MSB 121) is selected on it
+//! - other channels play drums when a GM2 (MSB 120) or XG (MSB 127) drum bank is selected
+//!
+//! GS selects drum parts with SysEx rather than banks, so its bank numbers are variations of the
+//! melodic sounds.
+//!
+//! `--program-remap` overrides the choice by program number: 0-127 are the melodic programs and
+//! 128-255 the drum kits (128 + program), e.g. `128*:0` plays every drum kit on the melodic
+//! samples and `48:-` mutes the strings.
+
+const BANK_SELECT_MSB: u8 = 0;
+const BANK_SELECT_LSB: u8 = 32;
+const GM2_DRUM_BANK: u8 = 120;
+const GM2_MELODIC_BANK: u8 = 121;
+const XG_DRUM_BANK: u8 = 127;
+const DRUM_CHANNEL: u8 = 9;
+
+/// Bank and program selected on a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Instrument {
+    pub bank_msb: u8,
+    pub bank_lsb: u8,
+    pub program: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleBank {
+    Melodic,
+    Drums,
+}
+
+/// `from:to` entry of `--program-remap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemapRule {
+    from: u8,
+    /// `from*`, every program from `from` up
+    and_above: bool,
+    /// `None` mutes the program
+    to: Option<u8>,
+}
+
+impl RemapRule {
+    fn matches(&self, program: u8) -> bool {
+        program == self.from || (self.and_above && program > self.from)
+    }
+}
+
+/// Parses an entry like `48:0`, `128*:0` or `48:-` (`from[*]:to`, `-` mutes)
+pub fn parse_remap_rule(s: &str) -> Result<RemapRule, String> {
+    let invalid = || {
+        format!(
+            "invalid program remap `{}`, expected <from 0-255>[*]:<to 0-255 or ->",
+            s
+        )
+    };
+    let (from, to) = s.trim().split_once(':').ok_or_else(invalid)?;
+    let from = from.trim();
+    let (from, and_above) = match from.strip_suffix('*') {
+        Some(from) => (from, true),
+        None => (from, false),
+    };
+    let to = match to.trim() {
+        "-" => None,
+        to => Some(to.parse().map_err(|_| invalid())?),
+    };
+    Ok(RemapRule {
+        from: from.parse().map_err(|_| invalid())?,
+        and_above,
+        to,
+    })
+}
+
+/// Reads `--program-remap-file`: one entry per line or separated by commas, `#` starts a comment
+pub fn load_program_remap(path: &str) -> Result<Vec<RemapRule>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    text.lines()
+        .flat_map(|line| line.split('#').next().unwrap_or_default().split(','))
+        .filter(|entry| !entry.trim().is_empty())
+        .map(parse_remap_rule)
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Instruments {
+    /// Bank select MSB and LSB waiting for the next program change
+    pending_banks: [(u8, u8); 16],
+    instruments: [Instrument; 16],
+    /// Checked in order, the last matching rule wins
+    remap: Vec<RemapRule>,
+}
+
+impl Instruments {
+    pub fn control_change(&mut self, channel: u8, controller: u8, value: u8) {
+        let pending = &mut self.pending_banks[channel as usize & 0xF];
+        match controller {
+            BANK_SELECT_MSB => pending.0 = value,
+            BANK_SELECT_LSB => pending.1 = value,
+            _ => {}
+        }
+    }
+
+    /// Selects `program` in the pending bank, bank selects only take effect here
+    pub fn program_change(&mut self, channel: u8, program: u8) {
+        let index = channel as usize & 0xF;
+        let (bank_msb, bank_lsb) = self.pending_banks[index];
+        self.instruments[index] = Instrument {
+            bank_msb,
+            bank_lsb,
+            program,
+        };
+    }
+
+    pub fn get(&self, channel: u8) -> Instrument {
+        self.instruments[channel as usize & 0xF]
+    }
+
+    pub fn set_remap(&mut self, remap: Vec<RemapRule>) {
+        self.remap = remap;
+    }
+
+    /// Bank the notes of `channel` play on, `None` if its program is muted
+    pub fn sample_bank(&self, channel: u8) -> Option<SampleBank> {
+        let instrument = self.get(channel);
+        let drums = if channel & 0xF == DRUM_CHANNEL {
+            instrument.bank_msb != GM2_MELODIC_BANK
+        } else {
+            matches!(instrument.bank_msb, GM2_DRUM_BANK | XG_DRUM_BANK)
+        };
+        let program = instrument.program | if drums { 0x80 } else { 0 };
+        let program = match self.remap.iter().rev().find(|rule| rule.matches(program)) {
+            Some(rule) => rule.to?,
+            None => program,
+        };
+        Some(if program >= 0x80 {
+            SampleBank::Drums
+        } else {
+            SampleBank::Melodic
+        })
+    }
+}
@@ -0,0 +1,289 @@
+//! `--job-list`: renders the jobs of a YAML file one after another, or several at once with
+//! `--parallel-jobs`. Every job runs in its own renderer process so a failing job doesn't take
+//! the others down.
+//!
+//! ```yaml
+//! defaults:            # options for every job
+//!   sample-rate: 44100
+//! jobs:
+//!   - input: song.mid
+//!     output: renders/song.wav
+//!     max-polyphony: 2000
+//!     limiter: true      # flags are true or false
+//!     tag: [title=Song, artist=Me]
+//! ```
+//!
+//! Options are the command line options without the leading `--`. Relative paths are relative
+//! to the job list file.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use log::{error, info};
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::{
+    error::{ErrorKind, RenderError},
+    i18n::t,
+};
+
+/// Command line options handled by the job list process itself and not passed to the jobs.
+/// The jobs log in text format to stderr, where their last line is read as the error.
+const JOB_LIST_OPTIONS: [&str; 4] = [
+    "--job-list",
+    "--parallel-jobs",
+    "--log-format",
+    "--log-file",
+];
+/// Flags of the job list process, the jobs' output is never headless
+const JOB_LIST_FLAGS: [&str; 2] = ["--headless", "-H"];
+
+pub struct Job {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    /// Command line arguments of the job's options
+    pub args: Vec<String>,
+}
+
+/// Converts an option mapping to command line arguments
+pub fn option_args(options: &Yaml, skip: &[&str]) -> Result<Vec<String>, String> {
+    let Some(options) = options.as_hash() else {
+        return match options {
+            Yaml::BadValue | Yaml::Null => Ok(Vec::new()),
+            _ => Err("options must be a mapping".to_string()),
+        };
+    };
+    let mut args = Vec::new();
+    for (key, value) in options {
+        let key = key
+            .as_str()
+            .ok_or_else(|| format!("invalid option name {:?}", key))?;
+        if skip.contains(&key) {
+            continue;
+        }
+        let values = match value {
+            Yaml::Array(values) => values.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+        for value in values {
+            let value = match value {
+                Yaml::Boolean(true) => None,
+                Yaml::Boolean(false) => continue,
+                Yaml::String(value) | Yaml::Real(value) => Some(value.clone()),
+                Yaml::Integer(value) => Some(value.to_string()),
+                _ => return Err(format!("invalid value for option `{}`", key)),
+            };
+            args.push(format!("--{}", key));
+            args.extend(value);
+        }
+    }
+    Ok(args)
+}
+
+/// Reads the jobs of a job list file, with the defaults applied before each job's options
+pub fn load_job_list(path: impl AsRef<Path>) -> Result<Vec<Job>, String> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let documents = YamlLoader::load_from_str(&text).map_err(|e| e.to_string())?;
+    let document = documents.first().ok_or("the job list is empty")?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let defaults =
+        option_args(&document["defaults"], &[]).map_err(|e| format!("defaults: {}", e))?;
+    let jobs = document["jobs"]
+        .as_vec()
+        .ok_or("`jobs` must be a list of jobs")?;
+    jobs.iter()
+        .enumerate()
+        .map(|(i, job)| {
+            let input = job["input"]
+                .as_str()
+                .ok_or_else(|| format!("job {}: missing `input`", i + 1))?;
+            let mut args = defaults.clone();
+            args.extend(
+                option_args(job, &["input", "output"])
+                    .map_err(|e| format!("job {}: {}", i + 1, e))?,
+            );
+            Ok(Job {
+                input: base_dir.join(input),
+                output: job["output"].as_str().map(|output| base_dir.join(output)),
+                args,
+            })
+        })
+        .collect()
+}
+
+/// Arguments of this process to pass on to every job, without the job list options
+pub fn forwarded_args() -> Vec<String> {
+    args_without(&JOB_LIST_OPTIONS, &JOB_LIST_FLAGS)
+}
+
+/// Arguments of this process without the given options (and their values) and flags
+pub fn args_without(options: &[&str], flags: &[&str]) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        if flags.contains(&arg.as_str()) {
+            continue;
+        }
+        let name = arg.split('=').next().unwrap_or_default();
+        if options.contains(&name) {
+            // The value is the next argument unless given as `--option=value`
+            if !arg.contains('=') {
+                iter.next();
+            }
+            continue;
+        }
+        args.push(arg);
+    }
+    args
+}
+
+/// Runs the jobs with up to `parallel_jobs` at once and reports their status.
+/// Fails if any job failed.
+pub fn run(jobs: &[Job], base_args: &[String], parallel_jobs: usize) -> Result<(), RenderError> {
+    let exe = std::env::current_exe()
+        .map_err(|e| RenderError::io("Failed to find the renderer executable", e))?;
+    let start_time = Instant::now();
+    let next_job = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| {
+        for _ in 0..parallel_jobs.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next_job.fetch_add(1, Ordering::Relaxed);
+                    let Some(job) = jobs.get(index) else {
+                        break;
+                    };
+                    if !run_job(&exe, job, index, jobs.len(), base_args) {
+                        failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    let failed = failed.into_inner();
+    let elapsed = start_time.elapsed().as_secs_f64();
+    info!(
+        event = "jobs_finished",
+        succeeded = jobs.len() - failed,
+        failed,
+        elapsed_sec:% = format!("{:.2}", elapsed);
+        "{}",
+        t!(
+            "jobs-finished",
+            count = jobs.len(),
+            seconds = format!("{:.2}", elapsed),
+            failed = failed
+        )
+    );
+    if failed > 0 {
+        return Err(RenderError::new(
+            ErrorKind::Failed,
+            format!("{} of {} jobs failed", failed, jobs.len()),
+        ));
+    }
+    Ok(())
+}
+
+/// Error message of a failed renderer process, the last line it logged to stderr
+pub fn last_error_line(stderr: &[u8]) -> String {
+    // The renderer logs in the same language, so its error prefix is the same as ours
+    let error_prefix = format!("{} ", t!("error-prefix"));
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("no error output")
+        .trim_start_matches(&error_prefix)
+        .to_string()
+}
+
+/// Renders one job and reports its status with the last error line of the renderer if it fails,
+/// returns whether it succeeded
+fn run_job(exe: &Path, job: &Job, index: usize, job_count: usize, base_args: &[String]) -> bool {
+    let input = job.input.display();
+    info!(
+        event = "job",
+        index = index + 1,
+        input:%,
+        status = "started";
+        "{}",
+        t!(
+            "job-started",
+            index = index + 1,
+            count = job_count,
+            input = input
+        )
+    );
+
+    let job_start_time = Instant::now();
+    let mut command = Command::new(exe);
+    command
+        .args(base_args)
+        .args(&job.args)
+        .arg("--midi-file-path")
+        .arg(&job.input);
+    if let Some(output) = &job.output {
+        command.arg("--output").arg(output);
+    }
+    let result = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output();
+
+    let elapsed = job_start_time.elapsed().as_secs_f64();
+    let error = match result {
+        Ok(output) if output.status.success() => None,
+        Ok(output) => Some((
+            output.status.code().unwrap_or(1),
+            last_error_line(&output.stderr),
+        )),
+        Err(e) => Some((1, e.to_string())),
+    };
+
+    let elapsed_sec = format!("{:.2}", elapsed);
+    match &error {
+        None => info!(
+            event = "job",
+            index = index + 1,
+            input:%,
+            status = "finished",
+            elapsed_sec:%;
+            "{}",
+            t!(
+                "job-finished",
+                index = index + 1,
+                count = job_count,
+                seconds = elapsed_sec,
+                input = input
+            )
+        ),
+        Some((code, message)) => error!(
+            event = "job",
+            index = index + 1,
+            input:%,
+            status = "failed",
+            exit_code = code,
+            elapsed_sec:%,
+            error:% = message;
+            "{}",
+            t!(
+                "job-failed",
+                index = index + 1,
+                count = job_count,
+                code = code,
+                input = input,
+                message = message
+            )
+        ),
+    }
+    error.is_none()
+}
@@ -0,0 +1,182 @@
+//! KDMAPI (Keppy's Direct MIDI API) exports, the API OmniMIDI exposes to MIDI players.
+//! Build with `--features kdmapi` and install the library as `OmniMIDI.dll` so players
+//! that speak KDMAPI use this renderer as their synth.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Sender},
+    },
+    thread::JoinHandle,
+};
+
+use ksynth_core::Channel;
+
+use crate::{
+    builtin_samples::{BuiltinOptions, builtin_samples, load_builtin_drum_kits},
+    multi_synth::MultiSynth,
+    predefined_drum_samples::DrumKitStyle,
+    realtime_output::RealtimeOutput,
+};
+
+type Bool = i32;
+const TRUE: Bool = 1;
+const FALSE: Bool = 0;
+
+const SAMPLE_RATE: u32 = 48000;
+const NUM_CHANNEL: u16 = 2;
+const MAX_POLYPHONY: u32 = 1024;
+
+/// Running KDMAPI stream. The audio stream isn't `Send` on every platform, so it lives
+/// on its own thread until `stop` is sent.
+struct KdmapiStream {
+    synth: Arc<Mutex<MultiSynth>>,
+    stop: Sender<()>,
+    output_thread: JoinHandle<()>,
+}
+
+static STREAM: Mutex<Option<KdmapiStream>> = Mutex::new(None);
+
+fn start_stream() -> Result<KdmapiStream, String> {
+    let options = BuiltinOptions::new(NUM_CHANNEL);
+    let (samples_map, drum_kit) = builtin_samples(&options, SAMPLE_RATE);
+    let num_channel: Channel = NUM_CHANNEL.try_into()?;
+    let mut multi_synth = MultiSynth::new(
+        SAMPLE_RATE,
+        num_channel,
+        MAX_POLYPHONY,
+        (SAMPLE_RATE as f64 * 0.1) as u64,
+        Arc::new(std::sync::RwLock::new(samples_map)),
+        Some(drum_kit),
+        num_cpus::get(),
+    );
+    load_builtin_drum_kits(&mut multi_synth, &options, SAMPLE_RATE, &DrumKitStyle::ALL);
+    let synth = Arc::new(Mutex::new(multi_synth));
+
+    let (stop, stop_receiver) = mpsc::channel();
+    let (started, started_receiver) = mpsc::channel();
+    let render_synth = synth.clone();
+    let output_thread = std::thread::spawn(move || {
+        let output = RealtimeOutput::start(SAMPLE_RATE, NUM_CHANNEL, move |buffer| {
+            render_synth.lock().unwrap().fill_buffer(buffer);
+        });
+        let failed = output.is_err();
+        let _ = started.send(output.map(|_| ()).err());
+        if !failed {
+            let _ = stop_receiver.recv();
+        }
+    });
+
+    match started_receiver.recv() {
+        Ok(None) => Ok(KdmapiStream {
+            synth,
+            stop,
+            output_thread,
+        }),
+        Ok(Some(e)) => Err(e),
+        Err(_) => Err("Audio output thread exited".to_string()),
+    }
+}
+
+/// # Safety
+/// All pointers must be valid for writes
+#[unsafe(no_mangle)]
+pub unsafe extern "system" fn ReturnKDMAPIVer(
+    major: *mut u32,
+    minor: *mut u32,
+    build: *mut u32,
+    revision: *mut u32,
+) -> Bool {
+    if major.is_null() || minor.is_null() || build.is_null() || revision.is_null() {
+        return FALSE;
+    }
+    unsafe {
+        *major = env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0);
+        *minor = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0);
+        *build = env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0);
+        *revision = 0;
+    }
+    TRUE
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn IsKDMAPIAvailable() -> Bool {
+    TRUE
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn InitializeKDMAPIStream() -> Bool {
+    let mut stream = STREAM.lock().unwrap();
+    if stream.is_some() {
+        return TRUE;
+    }
+    match start_stream() {
+        Ok(new_stream) => {
+            *stream = Some(new_stream);
+            TRUE
+        }
+        Err(e) => {
+            eprintln!("KDMAPI: {}", e);
+            FALSE
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn TerminateKDMAPIStream() -> Bool {
+    match STREAM.lock().unwrap().take() {
+        Some(stream) => {
+            let _ = stream.stop.send(());
+            let _ = stream.output_thread.join();
+            TRUE
+        }
+        None => FALSE,
+    }
+}
+
+/// Releases every held note
+#[unsafe(no_mangle)]
+pub extern "system" fn ResetKDMAPIStream() {
+    if let Some(stream) = STREAM.lock().unwrap().as_ref() {
+        let mut synth = stream.synth.lock().unwrap();
+        for channel in 0..16u32 {
+            for key in 0..128u32 {
+                synth.queue_midi_cmd(0x80 | channel | (key << 8));
+            }
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn SendDirectData(msg: u32) {
+    if let Some(stream) = STREAM.lock().unwrap().as_ref() {
+        stream.synth.lock().unwrap().queue_midi_cmd(msg);
+    }
+}
+
+/// KSynth has no event buffer to bypass, so this is the same as `SendDirectData`
+#[unsafe(no_mangle)]
+pub extern "system" fn SendDirectDataNoBuf(msg: u32) {
+    SendDirectData(msg);
+}
+
+/// SysEx isn't supported by KSynth, long messages are accepted and ignored
+#[unsafe(no_mangle)]
+pub extern "system" fn SendDirectLongData(_header: *mut std::ffi::c_void, _size: u32) -> u32 {
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn SendDirectLongDataNoBuf(_header: *mut std::ffi::c_void, _size: u32) -> u32 {
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn PrepareLongData(_header: *mut std::ffi::c_void, _size: u32) -> u32 {
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn UnprepareLongData(_header: *mut std::ffi::c_void, _size: u32) -> u32 {
+    0
+}
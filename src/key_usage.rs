@@ -0,0 +1,210 @@
+use std::{collections::HashSet, io::Write, path::Path};
+
+use crate::{
+    instrument::{Instruments, RemapRule, SampleBank},
+    predefined_drum_samples::DrumKitStyle,
+};
+
+/// Size of a key cell of the heatmap image in pixels
+const CELL_WIDTH: usize = 8;
+const CELL_HEIGHT: usize = 16;
+/// Room for the channel labels on the left and the octave labels on top
+const LABEL_WIDTH: usize = 48;
+const LABEL_HEIGHT: usize = 20;
+
+/// Note-on counts per MIDI channel and key, collected from a pass over the MIDI events, and the
+/// built-in drum kits the program changes select
+pub struct KeyUsage {
+    counts: Box<[[u64; 128]; 16]>,
+    /// Followed like `MultiSynth` does, to tell which program changes select a drum kit
+    instruments: Instruments,
+    drum_kit_styles: HashSet<DrumKitStyle>,
+}
+
+/// A key that is played in the MIDI but has no sample loaded
+pub struct MissingKey {
+    pub channel: u8,
+    pub drum: bool,
+    pub key: u8,
+    pub count: u64,
+}
+
+impl KeyUsage {
+    pub fn new() -> Self {
+        KeyUsage {
+            counts: Box::new([[0; 128]; 16]),
+            instruments: Instruments::default(),
+            drum_kit_styles: HashSet::new(),
+        }
+    }
+
+    /// Overrides the sample bank of programs like `MultiSynth::set_program_remap`
+    pub fn set_program_remap(&mut self, remap: Vec<RemapRule>) {
+        self.instruments.set_remap(remap);
+    }
+
+    pub fn record(&mut self, cmd: u32) {
+        let status = (cmd & 0xFF) as u8;
+        let channel = status & 0x0F;
+        let data1 = ((cmd >> 8) & 0x7F) as u8;
+        let data2 = ((cmd >> 16) & 0xFF) as u8;
+
+        match status & 0xF0 {
+            0x90 if data2 > 0 => self.counts[channel as usize][data1 as usize] += 1,
+            0xB0 => self.instruments.control_change(channel, data1, data2),
+            0xC0 => {
+                self.instruments.program_change(channel, data1);
+                if self.instruments.sample_bank(channel) == Some(SampleBank::Drums) {
+                    self.drum_kit_styles
+                        .insert(DrumKitStyle::from_program(data1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Built-in drum kits selected by program changes on channels that play drums. The standard
+    /// kit is only included if a program change selects it.
+    pub fn drum_kit_styles(&self) -> Vec<DrumKitStyle> {
+        self.drum_kit_styles.iter().copied().collect()
+    }
+
+    /// Returns the keys that are played but have no sample, sorted by channel and key.
+    /// Channel 10 is checked against `drum_keys` when a drum kit is loaded,
+    /// matching how `MultiSynth` routes drum notes.
+    pub fn missing_keys(
+        &self,
+        sample_keys: &HashSet<u8>,
+        drum_keys: Option<&HashSet<u8>>,
+    ) -> Vec<MissingKey> {
+        let mut missing = Vec::new();
+
+        for (channel, keys) in self.counts.iter().enumerate() {
+            let drum_keys = if channel == 9 { drum_keys } else { None };
+            for (key, &count) in keys.iter().enumerate() {
+                let key = key as u8;
+                let loaded = match drum_keys {
+                    Some(drum_keys) => drum_keys.contains(&key),
+                    None => sample_keys.contains(&key),
+                };
+                if count > 0 && !loaded {
+                    missing.push(MissingKey {
+                        channel: channel as u8,
+                        drum: drum_keys.is_some(),
+                        key,
+                        count,
+                    });
+                }
+            }
+        }
+
+        missing
+    }
+
+    /// Note-on counts of every key over all channels
+    fn totals(&self) -> [u64; 128] {
+        let mut totals = [0; 128];
+        for keys in self.counts.iter() {
+            for (total, count) in totals.iter_mut().zip(keys) {
+                *total += count;
+            }
+        }
+        totals
+    }
+
+    /// Writes the counts as CSV, one row per channel and a row of the totals, one column per key
+    pub fn write_heatmap_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let keys: Vec<String> = (0..128).map(|key| key.to_string()).collect();
+        writeln!(file, "channel,{}", keys.join(","))?;
+        let row = |counts: &[u64; 128]| {
+            counts
+                .iter()
+                .map(|count| count.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        for (channel, keys) in self.counts.iter().enumerate() {
+            writeln!(file, "{},{}", channel + 1, row(keys))?;
+        }
+        writeln!(file, "all,{}", row(&self.totals()))?;
+        file.flush()
+    }
+
+    /// Writes the counts as an SVG heatmap of channels by keys with the totals below, the color
+    /// goes from blue to red on a logarithmic scale and every cell has its count as a tooltip
+    pub fn write_heatmap_svg(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let totals = self.totals();
+        let max = totals.iter().copied().max().unwrap_or(0);
+        let color = |count: u64| {
+            if count == 0 {
+                return "#1a1a1a".to_string();
+            }
+            let level = (count as f64).ln_1p() / (max as f64).ln_1p();
+            format!("hsl({:.0},100%,50%)", 240.0 * (1.0 - level))
+        };
+        let width = LABEL_WIDTH + 128 * CELL_WIDTH;
+        // The totals row is set apart by half a row
+        let totals_y = LABEL_HEIGHT + 16 * CELL_HEIGHT + CELL_HEIGHT / 2;
+        let height = totals_y + CELL_HEIGHT;
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(
+            file,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="11">"#,
+            w = width,
+            h = height
+        )?;
+        writeln!(
+            file,
+            r##"<rect width="{}" height="{}" fill="#000"/>"##,
+            width, height
+        )?;
+        // Key 0 is C-1, so middle C (60) is C4
+        for octave in 0..11 {
+            writeln!(
+                file,
+                r##"<text x="{}" y="{}" fill="#ccc">C{}</text>"##,
+                LABEL_WIDTH + octave * 12 * CELL_WIDTH,
+                LABEL_HEIGHT - 6,
+                octave as i32 - 1
+            )?;
+        }
+        let mut write_row = |y: usize, label: &str, counts: &[u64; 128]| {
+            writeln!(
+                file,
+                r##"<text x="4" y="{}" fill="#ccc">{}</text>"##,
+                y + CELL_HEIGHT - 4,
+                label
+            )?;
+            for (key, &count) in counts.iter().enumerate() {
+                writeln!(
+                    file,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"><title>{}, key {}: {}</title></rect>"#,
+                    LABEL_WIDTH + key * CELL_WIDTH,
+                    y,
+                    CELL_WIDTH,
+                    CELL_HEIGHT,
+                    color(count),
+                    label,
+                    key,
+                    count
+                )?;
+            }
+            Ok::<_, std::io::Error>(())
+        };
+        for (channel, keys) in self.counts.iter().enumerate() {
+            let y = LABEL_HEIGHT + channel * CELL_HEIGHT;
+            write_row(y, &format!("Ch {}", channel + 1), keys)?;
+        }
+        write_row(totals_y, "All", &totals)?;
+        writeln!(file, "</svg>")?;
+        file.flush()
+    }
+}
+
+impl Default for KeyUsage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
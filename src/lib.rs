@@ -0,0 +1,134 @@
+//! Library target for embedding the KSynth engine in other hosts, and the modules of the
+//! renderer binary
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod affinity;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod auto_tune;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bookends;
+pub mod builtin_samples;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cc_smoothing;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod click_track;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compressor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod control;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod distortion;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dither;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod drum_remap;
+pub mod effects;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod effects_config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod eq;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fade;
+pub mod fm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod frame_dump;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hotkeys;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod humanize;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod i18n;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod init_wizard;
+pub mod instrument;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod job_list;
+#[cfg(feature = "kdmapi")]
+pub mod kdmapi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod key_usage;
+pub mod limiter;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod live_input;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod logging;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod markers;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod midi2_clip;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod midi_analysis;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod midi_clock;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod midi_fix;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod midi_input;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod midi_lint;
+pub mod multi_synth;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod network_stream;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod note_log;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod output;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod oversample;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod polyphony_log;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod polyphony_plan;
+pub mod portamento;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod power;
+pub mod predefined_drum_samples;
+pub mod predefined_sample;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod processed_midi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod profiler;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod realtime_output;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod render_farm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod replaygain;
+pub mod sample_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sample_export;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sample_pack;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod self_test;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod silence;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod smf_writer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod strum;
+pub mod surround;
+pub mod synth_event;
+pub mod synth_snapshot;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod telemetry;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tempo_map;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod time_range;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tui;
+pub mod tuning;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod units;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod user_config;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod wav_chunks;
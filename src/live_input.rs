@@ -0,0 +1,89 @@
+use midir::{MidiIO, MidiInput, MidiInputConnection};
+
+/// Connection to a MIDI input port that forwards channel messages as packed u32 commands
+pub struct LiveInput {
+    _connection: MidiInputConnection<()>,
+    port_name: String,
+}
+
+impl LiveInput {
+    /// Connects to the port matching `port` (an index or part of the port name),
+    /// or the first available port if `port` is `None`.
+    pub fn connect<F>(port: Option<&str>, mut on_midi_cmd: F) -> Result<Self, String>
+    where
+        F: FnMut(u32) + Send + 'static,
+    {
+        let midi_in = MidiInput::new("ksynth-midi-renderer")
+            .map_err(|e| format!("Failed to create MIDI input: {}", e))?;
+        let (selected_port, port_name) = select_port(&midi_in, port, "input")?;
+
+        let connection = midi_in
+            .connect(
+                &selected_port,
+                "ksynth-live-input",
+                move |_, message, _| {
+                    if let Some(cmd) = pack_midi_message(message) {
+                        on_midi_cmd(cmd);
+                    }
+                },
+                (),
+            )
+            .map_err(|e| format!("Failed to connect to MIDI input port: {}", e))?;
+
+        Ok(LiveInput {
+            _connection: connection,
+            port_name,
+        })
+    }
+
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+}
+
+/// Port matching `port` (an index or part of the port name) or the first one, with its name.
+/// `direction` is `input` or `output` for the error messages.
+pub fn select_port<T: MidiIO>(
+    midi_io: &T,
+    port: Option<&str>,
+    direction: &str,
+) -> Result<(T::Port, String), String> {
+    let ports = midi_io.ports();
+    if ports.is_empty() {
+        return Err(format!("No MIDI {} ports available", direction));
+    }
+
+    let selected_port = match port {
+        None => &ports[0],
+        Some(port) => match port.parse::<usize>() {
+            Ok(index) => ports
+                .get(index)
+                .ok_or_else(|| format!("MIDI {} port index out of range: {}", direction, index))?,
+            Err(_) => ports
+                .iter()
+                .find(|p| {
+                    midi_io
+                        .port_name(p)
+                        .map(|name| name.contains(port))
+                        .unwrap_or(false)
+                })
+                .ok_or_else(|| format!("MIDI {} port not found: {}", direction, port))?,
+        },
+    };
+    let port_name = midi_io
+        .port_name(selected_port)
+        .unwrap_or_else(|_| "Unknown".to_string());
+    Ok((selected_port.clone(), port_name))
+}
+
+/// Packs a channel message into the `status | data1 << 8 | data2 << 16` layout used by KSynth
+fn pack_midi_message(message: &[u8]) -> Option<u32> {
+    let (&status, data) = message.split_first()?;
+    if !(0x80..0xF0).contains(&status) {
+        return None;
+    }
+
+    let data1 = data.first().copied().unwrap_or(0) as u32;
+    let data2 = data.get(1).copied().unwrap_or(0) as u32;
+    Some(status as u32 | (data1 << 8) | (data2 << 16))
+}
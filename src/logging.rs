@@ -0,0 +1,257 @@
+//! Logger behind the `log` macros. Every record has a human readable message and the
+//! `event` and fields of the headless key=value line, and the format picks which one is written:
+//!
+//! - `text`: the message, as interactive mode prints it (`Warning: ...`, `Error: ...`)
+//! - `key-value`: `[warning |error ]<event> key=value ...`, the headless output. Values with
+//!   whitespace, `=` or `"` are quoted, with `"` and `\` escaped.
+//! - `json`: one object per record with the level, event, message and fields
+//!
+//! Records with an empty message are only written by the machine readable formats, records
+//! without an event and fields only by the text format.
+
+use std::{
+    borrow::Cow,
+    fmt,
+    fs::File,
+    io::{LineWriter, Write},
+    sync::Mutex,
+};
+
+use log::{
+    Level, LevelFilter, Log, Metadata, Record,
+    kv::{Key, Value, VisitSource},
+};
+use serde_json::{Map, json};
+
+use crate::i18n::t;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    KeyValue,
+    Json,
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::KeyValue => write!(f, "key-value"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+pub fn parse_log_format(s: &str) -> Result<LogFormat, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "text" => Ok(LogFormat::Text),
+        "key-value" | "kv" => Ok(LogFormat::KeyValue),
+        "json" => Ok(LogFormat::Json),
+        _ => Err(format!(
+            "invalid log format `{}`, expected text, key-value or json",
+            s
+        )),
+    }
+}
+
+pub fn parse_log_level(s: &str) -> Result<LevelFilter, String> {
+    s.trim().parse().map_err(|_| {
+        format!(
+            "invalid log level `{}`, expected off, error, warn, info, debug or trace",
+            s
+        )
+    })
+}
+
+/// Event and fields of a record
+#[derive(Default)]
+struct Fields {
+    event: Option<String>,
+    /// Key, value as written and value for JSON
+    pairs: Vec<(String, String, serde_json::Value)>,
+}
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        if key.as_str() == "event" {
+            self.event = Some(value.to_string());
+            return Ok(());
+        }
+        let json = if let Some(value) = value.to_bool() {
+            json!(value)
+        } else if let Some(value) = value.to_u64() {
+            json!(value)
+        } else if let Some(value) = value.to_i64() {
+            json!(value)
+        } else if let Some(value) = value.to_f64() {
+            json!(value)
+        } else {
+            json!(value.to_string())
+        };
+        self.pairs
+            .push((key.as_str().to_string(), value.to_string(), json));
+        Ok(())
+    }
+}
+
+/// Quotes a key=value value that wouldn't parse back as written, such as a path with spaces
+fn quote_value(value: &str) -> Cow<'_, str> {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '=' || c == '"');
+    if !needs_quotes {
+        return Cow::Borrowed(value);
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+    /// Headless mode writes the audio to stdout, so everything is logged to stderr
+    headless: bool,
+    /// Line buffered, so lines aren't lost when exiting with `process::exit`
+    file: Option<Mutex<LineWriter<File>>>,
+}
+
+impl Logger {
+    /// Formats a record, `None` if the format doesn't write it.
+    /// Returns whether the line goes to stdout.
+    fn format(&self, record: &Record) -> Option<(String, bool)> {
+        let message = record.args().to_string();
+        let mut fields = Fields::default();
+        let _ = record.key_values().visit(&mut fields);
+
+        match self.format {
+            LogFormat::Text => {
+                if message.is_empty() {
+                    return None;
+                }
+                Some(match record.level() {
+                    Level::Error => (format!("{} {}", t!("error-prefix"), message), false),
+                    Level::Warn => (
+                        format!("{} {}", t!("warning-prefix"), message),
+                        !self.headless,
+                    ),
+                    Level::Info => (message, !self.headless),
+                    level => (
+                        format!("[{}] {}", level.as_str().to_ascii_lowercase(), message),
+                        false,
+                    ),
+                })
+            }
+            LogFormat::KeyValue => {
+                if fields.event.is_none() && fields.pairs.is_empty() {
+                    return None;
+                }
+                let mut parts = Vec::new();
+                match record.level() {
+                    Level::Error => parts.push("error".to_string()),
+                    Level::Warn => parts.push("warning".to_string()),
+                    Level::Debug => parts.push("debug".to_string()),
+                    Level::Trace => parts.push("trace".to_string()),
+                    Level::Info => {}
+                }
+                parts.extend(fields.event);
+                parts.extend(
+                    fields
+                        .pairs
+                        .iter()
+                        .map(|(key, value, _)| format!("{}={}", key, quote_value(value))),
+                );
+                Some((parts.join(" "), false))
+            }
+            LogFormat::Json => {
+                let mut object = Map::new();
+                object.insert(
+                    "level".to_string(),
+                    json!(record.level().as_str().to_ascii_lowercase()),
+                );
+                if let Some(event) = fields.event {
+                    object.insert("event".to_string(), json!(event));
+                }
+                if !message.is_empty() {
+                    object.insert("message".to_string(), json!(message));
+                }
+                for (key, _, value) in fields.pairs {
+                    object.insert(key, value);
+                }
+                Some((serde_json::Value::Object(object).to_string(), false))
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let Some((line, stdout)) = self.format(record) else {
+            return;
+        };
+        if stdout {
+            println!("{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Installs the logger. `format` defaults to key=value in headless mode and text otherwise.
+/// `file` gets a copy of every line written.
+pub fn init(
+    level: LevelFilter,
+    format: Option<LogFormat>,
+    headless: bool,
+    file: Option<&str>,
+) -> std::io::Result<()> {
+    let file = file
+        .map(|path| File::create(path).map(|file| Mutex::new(LineWriter::new(file))))
+        .transpose()?;
+    let format = format.unwrap_or(if headless {
+        LogFormat::KeyValue
+    } else {
+        LogFormat::Text
+    });
+    log::set_boxed_logger(Box::new(Logger {
+        level,
+        format,
+        headless,
+        file,
+    }))
+    .map_err(std::io::Error::other)?;
+    log::set_max_level(level);
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::{io::Write, path::Path};
+
+use midi_toolkit::events::{Event, TextEventKind};
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Marker,
+    CuePoint,
+    Lyric,
+    Text,
+}
+
+impl MarkerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarkerKind::Marker => "marker",
+            MarkerKind::CuePoint => "cue_point",
+            MarkerKind::Lyric => "lyric",
+            MarkerKind::Text => "text",
+        }
+    }
+}
+
+/// Text meta event with its time in seconds from the start of the MIDI, or of the time range
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub time: f64,
+    pub kind: MarkerKind,
+    pub text: String,
+}
+
+impl Marker {
+    pub fn from_event(time: f64, event: &Event) -> Option<Self> {
+        let Event::Text(text_event) = event else {
+            return None;
+        };
+
+        let kind = match text_event.kind {
+            TextEventKind::Marker => MarkerKind::Marker,
+            TextEventKind::CuePoint => MarkerKind::CuePoint,
+            TextEventKind::Lyric => MarkerKind::Lyric,
+            TextEventKind::TextEvent => MarkerKind::Text,
+            _ => return None,
+        };
+
+        Some(Marker {
+            time,
+            kind,
+            text: String::from_utf8_lossy(&text_event.bytes).into_owned(),
+        })
+    }
+
+    /// Markers and cue points are used as chapters, lyrics and plain text are not
+    pub fn is_chapter(&self) -> bool {
+        matches!(self.kind, MarkerKind::Marker | MarkerKind::CuePoint)
+    }
+}
+
+/// Returns the text of a track name meta event
+pub fn track_name(event: &Event) -> Option<String> {
+    match event {
+        Event::Text(text_event) if matches!(text_event.kind, TextEventKind::TrackName) => Some(
+            String::from_utf8_lossy(&text_event.bytes)
+                .trim()
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+pub fn write_markers_json(path: impl AsRef<Path>, markers: &[Marker]) -> std::io::Result<()> {
+    let markers: Vec<_> = markers
+        .iter()
+        .map(|marker| {
+            json!({
+                "time": marker.time,
+                "kind": marker.kind.as_str(),
+                "text": marker.text,
+            })
+        })
+        .collect();
+
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    serde_json::to_writer_pretty(file, &markers)?;
+    Ok(())
+}
+
+/// Writes one `HH:MM:SS.mmm title` line per chapter marker
+pub fn write_chapters_file(path: impl AsRef<Path>, markers: &[Marker]) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for marker in markers.iter().filter(|m| m.is_chapter()) {
+        let total_ms = (marker.time * 1000.0).round() as u64;
+        writeln!(
+            file,
+            "{:02}:{:02}:{:02}.{:03} {}",
+            total_ms / 3_600_000,
+            (total_ms / 60_000) % 60,
+            (total_ms / 1000) % 60,
+            total_ms % 1000,
+            marker.text.trim()
+        )?;
+    }
+    file.flush()
+}
@@ -0,0 +1,192 @@
+use std::{io::Read, path::Path, time::Duration};
+
+use crate::synth_event::SynthEvent;
+
+const CLIP_HEADER: &[u8; 8] = b"SMF2CLIP";
+
+// Default tempo (120 BPM) in 10 ns units per quarter note
+const DEFAULT_TEMPO: u32 = 50_000_000;
+const DEFAULT_TICKS_PER_QUARTER: u32 = 480;
+
+/// MIDI 2.0 clip file (SMF2CLIP) decoded into delta-timed synth events.
+/// Groups are folded onto the 16 channels since KSynth has no notion of groups.
+pub struct Midi2Clip {
+    events: Vec<(f64, SynthEvent)>,
+}
+
+fn ump_word_count(message_type: u32) -> usize {
+    match message_type {
+        0x0 | 0x1 | 0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8 | 0x9 | 0xA => 2,
+        0xB | 0xC => 3,
+        _ => 4,
+    }
+}
+
+impl Midi2Clip {
+    pub fn is_clip_file(path: impl AsRef<Path>) -> bool {
+        let mut header = [0u8; 8];
+        std::fs::File::open(path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .map(|_| &header == CLIP_HEADER)
+            .unwrap_or(false)
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read clip file: {}", e))?;
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < CLIP_HEADER.len() || &bytes[..CLIP_HEADER.len()] != CLIP_HEADER {
+            return Err("Not a MIDI 2.0 clip file".to_string());
+        }
+
+        let words: Vec<u32> = bytes[CLIP_HEADER.len()..]
+            .chunks_exact(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        let mut events = Vec::new();
+        let mut ticks_per_quarter = DEFAULT_TICKS_PER_QUARTER;
+        let mut tempo = DEFAULT_TEMPO;
+        let mut pending_delta = 0.0f64;
+
+        let mut i = 0;
+        while i < words.len() {
+            let word0 = words[i];
+            let message_type = word0 >> 28;
+            let word_count = ump_word_count(message_type);
+            if i + word_count > words.len() {
+                return Err("Truncated UMP message at end of clip".to_string());
+            }
+            let message = &words[i..i + word_count];
+            i += word_count;
+
+            match message_type {
+                // Utility messages: delta clockstamp ticks per quarter note / delta clockstamp
+                0x0 => match (word0 >> 20) & 0xF {
+                    0x3 => ticks_per_quarter = (word0 & 0xFFFF).max(1),
+                    0x4 => {
+                        let ticks = (word0 & 0xFFFFF) as f64;
+                        pending_delta += ticks * tempo as f64 * 1e-8 / ticks_per_quarter as f64;
+                    }
+                    _ => {}
+                },
+                // MIDI 1.0 channel voice messages
+                0x2 => {
+                    let cmd = ((word0 >> 16) & 0xFF)
+                        | (((word0 >> 8) & 0x7F) << 8)
+                        | ((word0 & 0x7F) << 16);
+                    if let Some(event) = SynthEvent::from_midi1(cmd) {
+                        events.push((pending_delta, event));
+                        pending_delta = 0.0;
+                    }
+                }
+                // MIDI 2.0 channel voice messages
+                0x4 => {
+                    let word1 = message[1];
+                    let channel = ((word0 >> 16) & 0xF) as u8;
+                    let index1 = ((word0 >> 8) & 0x7F) as u8;
+                    let index2 = (word0 & 0xFF) as u8;
+
+                    let mut new_events = Vec::with_capacity(1);
+                    match (word0 >> 20) & 0xF {
+                        0x0 | 0x1 => new_events.push(SynthEvent::PerNoteController {
+                            channel,
+                            key: index1,
+                            controller: index2,
+                            value: word1,
+                        }),
+                        0x6 => new_events.push(SynthEvent::PerNotePitchBend {
+                            channel,
+                            key: index1,
+                            value: word1,
+                        }),
+                        0x8 => new_events.push(SynthEvent::NoteOff {
+                            channel,
+                            key: index1,
+                            velocity: (word1 >> 16) as u16,
+                        }),
+                        0x9 => new_events.push(SynthEvent::NoteOn {
+                            channel,
+                            key: index1,
+                            velocity: (word1 >> 16) as u16,
+                        }),
+                        0xA => new_events.push(SynthEvent::PolyPressure {
+                            channel,
+                            key: index1,
+                            value: word1,
+                        }),
+                        0xB => new_events.push(SynthEvent::ControlChange {
+                            channel,
+                            controller: index1,
+                            value: word1,
+                        }),
+                        0xC => {
+                            // Bank valid flag: send bank select MSB/LSB before the program change
+                            if index2 & 0x01 != 0 {
+                                new_events.push(SynthEvent::ControlChange {
+                                    channel,
+                                    controller: 0,
+                                    value: ((word1 >> 8) & 0x7F) << 25,
+                                });
+                                new_events.push(SynthEvent::ControlChange {
+                                    channel,
+                                    controller: 32,
+                                    value: (word1 & 0x7F) << 25,
+                                });
+                            }
+                            new_events.push(SynthEvent::ProgramChange {
+                                channel,
+                                program: ((word1 >> 24) & 0x7F) as u8,
+                            });
+                        }
+                        0xD => new_events.push(SynthEvent::ChannelPressure {
+                            channel,
+                            value: word1,
+                        }),
+                        0xE => new_events.push(SynthEvent::PitchBend {
+                            channel,
+                            value: word1,
+                        }),
+                        _ => {}
+                    }
+
+                    for event in new_events {
+                        events.push((pending_delta, event));
+                        pending_delta = 0.0;
+                    }
+                }
+                // Flex data: set tempo
+                0xD => {
+                    let status_bank = (word0 >> 8) & 0xFF;
+                    let status = word0 & 0xFF;
+                    if status_bank == 0x00 && status == 0x00 && message[1] > 0 {
+                        tempo = message[1];
+                    }
+                }
+                // UMP stream: end of clip
+                0xF if (word0 >> 16) & 0x3FF == 0x21 => break,
+                _ => {}
+            }
+        }
+
+        Ok(Midi2Clip { events })
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = (f64, SynthEvent)> + '_ {
+        self.events.iter().copied()
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.events.iter().map(|(delta, _)| delta).sum())
+    }
+
+    pub fn note_count(&self) -> u64 {
+        self.events
+            .iter()
+            .filter(|(_, event)| matches!(event, SynthEvent::NoteOn { .. }))
+            .count() as u64
+    }
+}
@@ -0,0 +1,217 @@
+//! The `analyze` subcommand: per event type statistics of the MIDI, written as JSON or CSV
+//! without loading samples or rendering any audio.
+
+use std::{io::Write, path::Path};
+
+use serde_json::json;
+
+/// Length of the windows the most simultaneous notes are reported for
+const WINDOW_SECS: f64 = 1.0;
+
+pub struct MidiAnalysis {
+    note_ons: Box<[[u64; 128]; 16]>,
+    controllers: Box<[[u64; 128]; 16]>,
+    program_changes: [u64; 16],
+    pitch_bends: [u64; 16],
+    /// `(tick, seconds, BPM)` of the tempo changes, empty for MIDI 2.0 clips
+    tempos: Vec<(u64, f64, f64)>,
+    held: Box<[[bool; 128]; 16]>,
+    held_count: u32,
+    /// Most notes held at once in each window
+    simultaneous: Vec<u32>,
+    peak_notes: u32,
+    peak_time: f64,
+}
+
+impl MidiAnalysis {
+    pub fn new() -> Self {
+        MidiAnalysis {
+            note_ons: Box::new([[0; 128]; 16]),
+            controllers: Box::new([[0; 128]; 16]),
+            program_changes: [0; 16],
+            pitch_bends: [0; 16],
+            tempos: Vec::new(),
+            held: Box::new([[false; 128]; 16]),
+            held_count: 0,
+            simultaneous: Vec::new(),
+            peak_notes: 0,
+            peak_time: 0.0,
+        }
+    }
+
+    /// Counts a packed MIDI 1.0 command at `time` seconds, commands come in time order
+    pub fn record(&mut self, time: f64, cmd: u32) {
+        let status = (cmd & 0xFF) as u8;
+        let data1 = ((cmd >> 8) & 0x7F) as usize;
+        let velocity = ((cmd >> 16) & 0x7F) as u8;
+        let channel = (status & 0x0F) as usize;
+
+        // A window without events keeps the notes held from the one before
+        let window = (time / WINDOW_SECS) as usize;
+        while self.simultaneous.len() <= window {
+            self.simultaneous.push(self.held_count);
+        }
+
+        match status & 0xF0 {
+            0x90 if velocity > 0 => {
+                self.note_ons[channel][data1] += 1;
+                // A retriggered note is still one note
+                if !std::mem::replace(&mut self.held[channel][data1], true) {
+                    self.held_count += 1;
+                }
+            }
+            0x80 | 0x90 => {
+                let was_held = std::mem::take(&mut self.held[channel][data1]);
+                self.held_count -= was_held as u32;
+            }
+            0xB0 => self.controllers[channel][data1] += 1,
+            0xC0 => self.program_changes[channel] += 1,
+            0xE0 => self.pitch_bends[channel] += 1,
+            _ => {}
+        }
+
+        let most = &mut self.simultaneous[window];
+        *most = (*most).max(self.held_count);
+        if self.held_count > self.peak_notes {
+            self.peak_notes = self.held_count;
+            self.peak_time = time;
+        }
+    }
+
+    pub fn set_tempos(&mut self, tempos: Vec<(u64, f64, f64)>) {
+        self.tempos = tempos;
+    }
+
+    pub fn note_on_count(&self) -> u64 {
+        self.note_ons.iter().flatten().sum()
+    }
+
+    /// Channels with at least one note on
+    pub fn channels_used(&self) -> usize {
+        self.note_ons
+            .iter()
+            .filter(|keys| keys.iter().any(|&count| count > 0))
+            .count()
+    }
+
+    pub fn controller_count(&self) -> u64 {
+        self.controllers.iter().flatten().sum()
+    }
+
+    pub fn tempo_change_count(&self) -> usize {
+        self.tempos.len()
+    }
+
+    /// Most notes held at once and the time in seconds it was first reached
+    pub fn peak_notes(&self) -> (u32, f64) {
+        (self.peak_notes, self.peak_time)
+    }
+
+    /// Writes the histograms, tempo changes and simultaneous notes as JSON
+    pub fn write_json(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let channels: Vec<_> = (0..16)
+            .map(|channel| {
+                let keys: serde_json::Map<_, _> = nonzero(&self.note_ons[channel])
+                    .map(|(key, count)| (key.to_string(), json!(count)))
+                    .collect();
+                let controllers: serde_json::Map<_, _> = nonzero(&self.controllers[channel])
+                    .map(|(controller, count)| (controller.to_string(), json!(count)))
+                    .collect();
+                json!({
+                    "channel": channel + 1,
+                    "note_ons": self.note_ons[channel].iter().sum::<u64>(),
+                    "keys": keys,
+                    "controllers": controllers,
+                    "program_changes": self.program_changes[channel],
+                    "pitch_bends": self.pitch_bends[channel],
+                })
+            })
+            .collect();
+        let tempos: Vec<_> = self
+            .tempos
+            .iter()
+            .map(|&(tick, time, bpm)| json!({ "tick": tick, "time": time, "bpm": bpm }))
+            .collect();
+        let simultaneous: Vec<_> = self
+            .simultaneous
+            .iter()
+            .enumerate()
+            .map(|(window, &notes)| json!({ "time": window as f64 * WINDOW_SECS, "notes": notes }))
+            .collect();
+
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(
+            file,
+            &json!({
+                "note_ons": self.note_on_count(),
+                "peak_notes": self.peak_notes,
+                "peak_time": self.peak_time,
+                "window": WINDOW_SECS,
+                "channels": channels,
+                "tempos": tempos,
+                "simultaneous_notes": simultaneous,
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Writes every statistic as one CSV table of `kind,channel,number,time,value` rows:
+    /// `note_on` per channel and key, `controller` per channel and controller number,
+    /// `program_change` and `pitch_bend` per channel, `tempo` with the BPM and
+    /// `simultaneous_notes` with the most notes held in each window
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "kind,channel,number,time,value")?;
+        for channel in 0..16 {
+            for (key, count) in nonzero(&self.note_ons[channel]) {
+                writeln!(file, "note_on,{},{},,{}", channel + 1, key, count)?;
+            }
+            for (controller, count) in nonzero(&self.controllers[channel]) {
+                writeln!(file, "controller,{},{},,{}", channel + 1, controller, count)?;
+            }
+            if self.program_changes[channel] > 0 {
+                writeln!(
+                    file,
+                    "program_change,{},,,{}",
+                    channel + 1,
+                    self.program_changes[channel]
+                )?;
+            }
+            if self.pitch_bends[channel] > 0 {
+                writeln!(
+                    file,
+                    "pitch_bend,{},,,{}",
+                    channel + 1,
+                    self.pitch_bends[channel]
+                )?;
+            }
+        }
+        for &(tick, time, bpm) in &self.tempos {
+            writeln!(file, "tempo,,{},{:.6},{:.3}", tick, time, bpm)?;
+        }
+        for (window, notes) in self.simultaneous.iter().enumerate() {
+            writeln!(
+                file,
+                "simultaneous_notes,,,{:.6},{}",
+                window as f64 * WINDOW_SECS,
+                notes
+            )?;
+        }
+        file.flush()
+    }
+}
+
+impl Default for MidiAnalysis {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `(index, count)` of the counts that aren't zero
+fn nonzero(counts: &[u64; 128]) -> impl Iterator<Item = (usize, u64)> + '_ {
+    counts
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count > 0)
+        .map(|(index, &count)| (index, count))
+}
@@ -0,0 +1,89 @@
+//! `--midi-clock-port`: MIDI clock (24 pulses per quarter note at the MIDI's tempo) and song
+//! position pointers on a MIDI output port, so external gear and visualizers can follow the
+//! render. Pulses are sent as the audio of their time is rendered, which is only in time with
+//! the listener at realtime speed (`--max-render-speed 1` or `--stream`).
+
+use midir::{MidiOutput, MidiOutputConnection};
+
+use crate::live_input::select_port;
+
+const TIMING_CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+const SONG_POSITION_POINTER: u8 = 0xF2;
+const PULSES_PER_QUARTER: f64 = 24.0;
+/// A song position counts MIDI beats (sixteenth notes) of 6 pulses
+const PULSES_PER_MIDI_BEAT: u64 = 6;
+
+pub struct MidiClock {
+    connection: MidiOutputConnection,
+    port_name: String,
+    /// Pulses from the start of the song up to the last one sent
+    pulses: u64,
+    running: bool,
+}
+
+impl MidiClock {
+    /// Connects to the output port matching `port` (an index or part of the port name), or the
+    /// first available port if `port` is `None`
+    pub fn connect(port: Option<&str>) -> Result<Self, String> {
+        let midi_out = MidiOutput::new("ksynth-midi-renderer")
+            .map_err(|e| format!("Failed to create MIDI output: {}", e))?;
+        let (selected_port, port_name) = select_port(&midi_out, port, "output")?;
+        let connection = midi_out
+            .connect(&selected_port, "ksynth-midi-clock")
+            .map_err(|e| format!("Failed to connect to MIDI output port: {}", e))?;
+        Ok(MidiClock {
+            connection,
+            port_name,
+            pulses: 0,
+            running: false,
+        })
+    }
+
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), String> {
+        self.connection.send(message).map_err(|e| e.to_string())
+    }
+
+    /// Starts the clock at `quarters` quarter notes into the song, from the MIDI beat before it
+    pub fn start(&mut self, quarters: f64) -> Result<(), String> {
+        let midi_beats =
+            ((quarters * PULSES_PER_QUARTER) as u64 / PULSES_PER_MIDI_BEAT).min(0x3FFF);
+        self.pulses = midi_beats * PULSES_PER_MIDI_BEAT;
+        self.running = true;
+        if midi_beats == 0 {
+            return self.send(&[START]);
+        }
+        self.send(&[
+            SONG_POSITION_POINTER,
+            (midi_beats & 0x7F) as u8,
+            (midi_beats >> 7) as u8,
+        ])?;
+        self.send(&[CONTINUE])
+    }
+
+    /// Sends the pulses up to `quarters` quarter notes into the song
+    pub fn advance(&mut self, quarters: f64) -> Result<(), String> {
+        if !self.running {
+            return Ok(());
+        }
+        let due = (quarters * PULSES_PER_QUARTER) as u64;
+        while self.pulses < due {
+            self.send(&[TIMING_CLOCK])?;
+            self.pulses += 1;
+        }
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Result<(), String> {
+        if !std::mem::replace(&mut self.running, false) {
+            return Ok(());
+        }
+        self.send(&[STOP])
+    }
+}
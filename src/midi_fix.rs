@@ -0,0 +1,171 @@
+//! `--fix-midi`: repairs applied to the merged MIDI events in ticks, before the tempo is
+//! applied, so every pass over the MIDI and `--save-fixed` see the same fixed events.
+//!
+//! - `dedupe-notes` drops note ons of a key the channel already holds with the note off that
+//!   balances them, so the key sounds once until the last of the overlapping notes ends, and
+//!   drops note offs of keys that aren't held
+//! - `clamp-velocity` limits note on velocities to 127, broken files can have 8-bit ones
+//! - `strip-sysex` drops SysEx messages
+//! - `quantize:N` moves every event to the nearest 1/N note, notes shorter than the grid can
+//!   end up starting and ending on the same tick
+
+use std::path::Path;
+
+use midi_toolkit::{
+    events::{Delta, Event, MIDIEvent},
+    io::MIDIFile,
+    pipe,
+    sequence::{event::merge_events_array, to_vec, unwrap_items},
+};
+
+use crate::smf_writer::SmfWriter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiFix {
+    DedupeNotes,
+    ClampVelocity,
+    StripSysex,
+    /// Grid in notes per whole note, 16 for sixteenth notes
+    Quantize(u32),
+}
+
+/// Parses `dedupe-notes`, `clamp-velocity`, `strip-sysex` or `quantize:N`
+pub fn parse_midi_fix(s: &str) -> Result<MidiFix, String> {
+    match s.trim() {
+        "dedupe-notes" => Ok(MidiFix::DedupeNotes),
+        "clamp-velocity" => Ok(MidiFix::ClampVelocity),
+        "strip-sysex" => Ok(MidiFix::StripSysex),
+        fix => {
+            let grid = fix
+                .strip_prefix("quantize:")
+                .ok_or_else(|| format!("unknown MIDI fix `{}`", fix))?;
+            match grid.parse::<u32>() {
+                Ok(grid) if grid > 0 => Ok(MidiFix::Quantize(grid)),
+                _ => Err(format!(
+                    "invalid quantize grid `{}`, expected e.g. 16",
+                    grid
+                )),
+            }
+        }
+    }
+}
+
+pub struct FixedEvents<I> {
+    events: I,
+    dedupe: bool,
+    clamp_velocity: bool,
+    strip_sysex: bool,
+    /// Grid in ticks, 1 without quantizing
+    grid: u64,
+    /// Input ticks of the last event read
+    tick: u64,
+    /// Output ticks of the last event returned
+    output_tick: u64,
+    held: Box<[[bool; 128]; 16]>,
+    /// Note ons dropped by `dedupe-notes` whose note off is still to be dropped
+    dropped: Box<[[u32; 128]; 16]>,
+}
+
+/// Applies `fixes` to merged `events` with tick deltas, the MIDI has `ppq` ticks per quarter
+pub fn fix_events<I, E>(events: I, fixes: &[MidiFix], ppq: u16) -> FixedEvents<I>
+where
+    I: Iterator<Item = Result<Delta<u64, Event>, E>>,
+{
+    let grid = fixes.iter().find_map(|fix| match fix {
+        MidiFix::Quantize(grid) => Some((ppq as u64 * 4 / *grid as u64).max(1)),
+        _ => None,
+    });
+    FixedEvents {
+        events,
+        dedupe: fixes.contains(&MidiFix::DedupeNotes),
+        clamp_velocity: fixes.contains(&MidiFix::ClampVelocity),
+        strip_sysex: fixes.contains(&MidiFix::StripSysex),
+        grid: grid.unwrap_or(1),
+        tick: 0,
+        output_tick: 0,
+        held: Box::new([[false; 128]; 16]),
+        dropped: Box::new([[0; 128]; 16]),
+    }
+}
+
+impl<I> FixedEvents<I> {
+    /// Whether `dedupe-notes` drops the event, tracking the held keys
+    fn drop_note(&mut self, event: &Event) -> bool {
+        let Some(cmd) = event.as_u32() else {
+            return false;
+        };
+        let channel = (cmd & 0x0F) as usize;
+        let key = ((cmd >> 8) & 0x7F) as usize;
+        match (cmd & 0xF0, (cmd >> 16) & 0xFF) {
+            (0x90, velocity) if velocity > 0 => {
+                if std::mem::replace(&mut self.held[channel][key], true) {
+                    self.dropped[channel][key] += 1;
+                    return true;
+                }
+                false
+            }
+            (0x80 | 0x90, _) => {
+                if self.dropped[channel][key] > 0 {
+                    self.dropped[channel][key] -= 1;
+                    return true;
+                }
+                !std::mem::take(&mut self.held[channel][key])
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<I, E> Iterator for FixedEvents<I>
+where
+    I: Iterator<Item = Result<Delta<u64, Event>, E>>,
+{
+    type Item = Result<Delta<u64, Event>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut event = match self.events.next()? {
+                Ok(event) => event,
+                Err(e) => return Some(Err(e)),
+            };
+            self.tick += event.delta;
+            if self.strip_sysex && matches!(event.event, Event::SystemExclusiveMessage(_)) {
+                continue;
+            }
+            if self.dedupe && self.drop_note(&event.event) {
+                continue;
+            }
+            if self.clamp_velocity
+                && let Event::NoteOn(ref mut note) = event.event
+            {
+                note.velocity = note.velocity.min(127);
+            }
+            // Rounding keeps the events in order, never before the last one returned
+            let tick = (self.tick + self.grid / 2) / self.grid * self.grid;
+            let tick = tick.max(self.output_tick);
+            event.delta = tick - self.output_tick;
+            self.output_tick = tick;
+            return Some(Ok(event));
+        }
+    }
+}
+
+/// Writes the events of `midi` with `fixes` applied to a format 0 MIDI file at `path`
+pub fn save_fixed_midi(
+    path: impl AsRef<Path>,
+    midi: &MIDIFile,
+    fixes: &[MidiFix],
+) -> std::io::Result<()> {
+    let mut writer = SmfWriter::create(path, midi.ppq())?;
+    let events = pipe!(
+        midi.iter_all_tracks()
+        |>to_vec()
+        |>merge_events_array()
+        |>fix_events(fixes, midi.ppq())
+        |>unwrap_items()
+    );
+    for event in events {
+        writer.event(event.delta, &event.event)?;
+    }
+    writer.finish()
+}
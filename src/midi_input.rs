@@ -0,0 +1,159 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use flate2::read::GzDecoder;
+
+/// Temporary file that is removed when dropped
+pub struct TempFile(PathBuf);
+
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+impl TempFile {
+    pub fn create() -> std::io::Result<(Self, File)> {
+        Self::create_with_extension("mid")
+    }
+
+    pub fn create_with_extension(extension: &str) -> std::io::Result<(Self, File)> {
+        let path = std::env::temp_dir().join(format!(
+            "ksynth-midi-renderer-{}-{}.{}",
+            std::process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed),
+            extension
+        ));
+        let file = File::create(&path)?;
+        Ok((TempFile(path), file))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Buffers the whole stdin into a temporary file, since MIDIFile needs a seekable source
+pub fn buffer_stdin_to_temp_file() -> std::io::Result<TempFile> {
+    let (temp_file, mut file) = TempFile::create()?;
+    std::io::copy(&mut std::io::stdin().lock(), &mut file)?;
+    Ok(temp_file)
+}
+
+enum Container {
+    Gzip,
+    Zstd,
+    Rmid,
+}
+
+fn detect_container(path: &Path) -> std::io::Result<Option<Container>> {
+    let mut header = [0u8; 12];
+    let mut file = File::open(path)?;
+    let mut len = 0;
+    while len < header.len() {
+        let read = file.read(&mut header[len..])?;
+        if read == 0 {
+            break;
+        }
+        len += read;
+    }
+    let header = &header[..len];
+
+    let container = if header.starts_with(&[0x1F, 0x8B]) {
+        Some(Container::Gzip)
+    } else if header.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+        Some(Container::Zstd)
+    } else if header.len() == 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"RMID" {
+        Some(Container::Rmid)
+    } else {
+        None
+    };
+
+    Ok(container)
+}
+
+fn extract_rmid_data(path: &Path, output: &mut File) -> std::io::Result<()> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(12))?;
+
+    loop {
+        let mut chunk_header = [0u8; 8];
+        file.read_exact(&mut chunk_header)?;
+        let size = u32::from_le_bytes([
+            chunk_header[4],
+            chunk_header[5],
+            chunk_header[6],
+            chunk_header[7],
+        ]) as u64;
+
+        if &chunk_header[0..4] == b"data" {
+            let copied = std::io::copy(&mut (&mut file).take(size), output)?;
+            if copied < size {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "RMIDI data chunk is truncated",
+                ));
+            }
+            return Ok(());
+        }
+
+        // RIFF chunks are padded to an even size
+        file.seek(SeekFrom::Current((size + (size & 1)) as i64))?;
+    }
+}
+
+/// Unwraps RMIDI (.rmi) and gzip/zstd compressed MIDIs into a plain MIDI temp file.
+/// Returns `None` if the file is already a plain MIDI.
+pub fn unwrap_midi_container(path: &Path) -> std::io::Result<Option<TempFile>> {
+    let mut current: Option<TempFile> = None;
+
+    // Containers can be nested (e.g. a gzipped .rmi)
+    for _ in 0..4 {
+        let current_path = current.as_ref().map(|t| t.path()).unwrap_or(path);
+        let Some(container) = detect_container(current_path)? else {
+            break;
+        };
+
+        let (temp_file, mut output) = TempFile::create()?;
+        match container {
+            Container::Gzip => {
+                let mut decoder = GzDecoder::new(File::open(current_path)?);
+                std::io::copy(&mut decoder, &mut output)?;
+            }
+            Container::Zstd => {
+                let mut decoder = zstd::stream::read::Decoder::new(File::open(current_path)?)?;
+                std::io::copy(&mut decoder, &mut output)?;
+            }
+            Container::Rmid => extract_rmid_data(current_path, &mut output)?,
+        }
+        current = Some(temp_file);
+    }
+
+    Ok(current)
+}
+
+/// File name without the MIDI and compression extensions (e.g. `song.mid.zst` -> `song`)
+pub fn midi_file_stem(path: &Path) -> String {
+    let mut name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("Unknown")
+        .to_string();
+
+    for extensions in [&["gz", "zst"][..], &["mid", "midi", "rmi", "midi2"][..]] {
+        if let Some((stem, extension)) = name.rsplit_once('.')
+            && !stem.is_empty()
+            && extensions.contains(&extension.to_lowercase().as_str())
+        {
+            name = stem.to_string();
+        }
+    }
+
+    name
+}
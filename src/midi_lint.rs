@@ -0,0 +1,229 @@
+//! `--lint-midi`: checks a Standard MIDI file for content that usually means a broken export
+//! before committing to a long render: note ons of a key that is already held, note offs of a
+//! key that isn't, tempos no song uses and events after a track's end-of-track.
+
+use std::path::Path;
+
+use midi_toolkit::events::{Delta, Event, MIDIEvent};
+
+/// 120 BPM, the tempo until the first tempo event
+const DEFAULT_TEMPO: u32 = 500_000;
+/// Tempos outside this BPM range are reported
+const MIN_BPM: f64 = 1.0;
+const MAX_BPM: f64 = 10_000.0;
+
+/// Occurrences of one kind of issue and where the first one is
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Finding {
+    pub count: u64,
+    /// Seconds from the start, or the track index for `events_after_end`
+    pub first: Option<f64>,
+}
+
+impl Finding {
+    fn add(&mut self, at: f64) {
+        self.count += 1;
+        self.first.get_or_insert(at);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    DuplicateNoteOn,
+    OrphanNoteOff,
+    AbsurdTempo,
+    EventAfterEnd,
+}
+
+impl LintKind {
+    /// Name in structured logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintKind::DuplicateNoteOn => "duplicate_note_on",
+            LintKind::OrphanNoteOff => "orphan_note_off",
+            LintKind::AbsurdTempo => "absurd_tempo",
+            LintKind::EventAfterEnd => "event_after_end_of_track",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MidiLint {
+    /// Note ons of a key the same channel already holds
+    pub duplicate_note_ons: Finding,
+    /// Note offs of a key the channel doesn't hold
+    pub orphan_note_offs: Finding,
+    /// Tempos below `MIN_BPM` or above `MAX_BPM`
+    pub absurd_tempos: Finding,
+    /// Events after the end-of-track of their track, which players ignore
+    pub events_after_end: Finding,
+}
+
+impl MidiLint {
+    /// Checks merged events with tick deltas and the track chunks of the file at `path`
+    pub fn check(
+        path: impl AsRef<Path>,
+        ppq: u16,
+        events: impl Iterator<Item = Delta<u64, Event>>,
+    ) -> std::io::Result<Self> {
+        let mut lint = MidiLint::default();
+        // Note ons minus note offs of every channel and key
+        let mut held = vec![[0u32; 128]; 16];
+        let mut tempo = DEFAULT_TEMPO;
+        let mut time = 0.0;
+        for event in events {
+            time += event.delta as f64 * tempo as f64 / 1_000_000.0 / ppq.max(1) as f64;
+            if let Event::Tempo(ref change) = event.event {
+                let bpm = 60_000_000.0 / change.tempo.max(1) as f64;
+                if change.tempo == 0 || !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+                    lint.absurd_tempos.add(time);
+                }
+                tempo = change.tempo;
+                continue;
+            }
+            let Some(cmd) = event.event.as_u32() else {
+                continue;
+            };
+            let status = (cmd & 0xF0) as u8;
+            let note = &mut held[(cmd & 0x0F) as usize][((cmd >> 8) & 0x7F) as usize];
+            let velocity = (cmd >> 16) & 0x7F;
+            match status {
+                0x90 if velocity > 0 => {
+                    if *note > 0 {
+                        lint.duplicate_note_ons.add(time);
+                    }
+                    *note += 1;
+                }
+                0x80 | 0x90 => {
+                    if *note == 0 {
+                        lint.orphan_note_offs.add(time);
+                    } else {
+                        *note -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let data = std::fs::read(path)?;
+        for (track, chunk) in track_chunks(&data).enumerate() {
+            for _ in 0..events_after_end(chunk) {
+                lint.events_after_end.add(track as f64);
+            }
+        }
+        Ok(lint)
+    }
+
+    /// Every kind of issue with its finding, in report order
+    pub fn findings(&self) -> [(LintKind, Finding); 4] {
+        [
+            (LintKind::DuplicateNoteOn, self.duplicate_note_ons),
+            (LintKind::OrphanNoteOff, self.orphan_note_offs),
+            (LintKind::AbsurdTempo, self.absurd_tempos),
+            (LintKind::EventAfterEnd, self.events_after_end),
+        ]
+    }
+
+    pub fn issue_count(&self) -> u64 {
+        self.findings()
+            .iter()
+            .map(|(_, finding)| finding.count)
+            .sum()
+    }
+}
+
+/// Data of the `MTrk` chunks, other chunks are skipped
+fn track_chunks(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        while pos + 8 <= data.len() {
+            let id = &data[pos..pos + 4];
+            let len = u32::from_be_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+            let start = pos + 8;
+            let end = start.saturating_add(len).min(data.len());
+            pos = end;
+            if id == b"MTrk" {
+                return Some(&data[start..end]);
+            }
+        }
+        None
+    })
+}
+
+/// Events of a track chunk after its end-of-track, counted until the data runs out or stops
+/// making sense
+fn events_after_end(chunk: &[u8]) -> u64 {
+    let mut pos = 0;
+    let mut running_status = 0u8;
+    let mut ended = false;
+    let mut after_end = 0;
+
+    let read_varint = |pos: &mut usize| -> Option<usize> {
+        let mut value = 0usize;
+        for _ in 0..4 {
+            let byte = *chunk.get(*pos)?;
+            *pos += 1;
+            value = (value << 7) | (byte & 0x7F) as usize;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    };
+
+    while pos < chunk.len() {
+        let Some(_delta) = read_varint(&mut pos) else {
+            break;
+        };
+        let Some(&status) = chunk.get(pos) else {
+            break;
+        };
+        let length = match status {
+            0xFF => {
+                let Some(&kind) = chunk.get(pos + 1) else {
+                    break;
+                };
+                pos += 2;
+                if kind == 0x2F && !ended {
+                    ended = true;
+                    let Some(length) = read_varint(&mut pos) else {
+                        break;
+                    };
+                    pos += length;
+                    continue;
+                }
+                read_varint(&mut pos)
+            }
+            0xF0 | 0xF7 => {
+                pos += 1;
+                read_varint(&mut pos)
+            }
+            0x80..=0xEF => {
+                running_status = status;
+                pos += 1;
+                Some(channel_data_length(status))
+            }
+            // Data byte under running status
+            0x00..=0x7F if running_status != 0 => Some(channel_data_length(running_status)),
+            _ => None,
+        };
+        let Some(length) = length else {
+            break;
+        };
+        if pos + length > chunk.len() {
+            break;
+        }
+        pos += length;
+        if ended {
+            after_end += 1;
+        }
+    }
+    after_end
+}
+
+fn channel_data_length(status: u8) -> usize {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    ops::Range,
     sync::{Arc, RwLock},
 };
 
@@ -7,34 +8,149 @@ use ksynth_core::{Channel, KSynth, drum_kit::DrumKit, sample::Sample};
 use num_cpus;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
+use crate::{
+    cc_smoothing::CcSmoothing,
+    effects::Effect,
+    instrument::{Instrument, Instruments, RemapRule, SampleBank},
+    portamento::Portamento,
+    predefined_drum_samples::DrumKitStyle,
+    sample_cache::SampleCache,
+    surround::SurroundPanner,
+    synth_event::SynthEvent,
+    synth_snapshot::{ChannelState, SynthSnapshot},
+    tuning::{SampleSources, mts_frequency},
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct NoteKey {
     channel: u8,
     note: u8,
 }
 
+/// Load of one instance, to spot skew in the note balancing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InstanceStats {
+    pub polyphony: u32,
+    pub max_voices: u32,
+    pub rendering_time_ratio: f32,
+    /// Notes currently held on the instance
+    pub held_notes: u32,
+    /// Notes routed to the instance since it was built
+    pub assigned_notes: u64,
+}
+
+/// Note that started or stopped on an instance, after notes dropped at the polyphony limit
+#[derive(Debug, Clone, Copy)]
+pub struct NoteEvent {
+    /// Synth frame the note was queued at, counted by `fill_buffer_scheduled`
+    pub frame: u64,
+    pub channel: u8,
+    pub key: u8,
+    /// 0 for note offs
+    pub velocity: u8,
+}
+
+/// Insert effects of a MIDI channel. The channel plays on an instance of its own, the last
+/// instances are the ones of the inserts in order.
+struct ChannelInsert {
+    channel: u8,
+    effects: Box<dyn Effect>,
+}
+
+/// Instance replaced by a rebuild, kept playing until its notes have faded out
+struct RetiringSynth {
+    synth: KSynth,
+    bus: usize, // Index it had before the rebuild, its bus when rendering surround
+}
+
 pub struct MultiSynth {
     synths: Vec<KSynth>,
     note_map: HashMap<NoteKey, usize>, // Note key -> instance index
-    note_counts: Vec<u32>,             // Current number of simultaneous voices per instance
-    max_voices: Vec<u32>,              // Maximum number of simultaneous voices per instance
+    retiring: Vec<RetiringSynth>,
+    retiring_notes: HashMap<NoteKey, usize>, // Note key -> retiring instance index
+    note_counts: Vec<u32>,                   // Current number of simultaneous voices per instance
+    assigned_notes: Vec<u64>,                // Notes routed to each instance since it was built
+    max_voices: Vec<u32>,                    // Maximum number of simultaneous voices per instance
     drum_kit_storage: Option<DrumKit>,
+    drum_instances: usize, // Copies of the drum kit, of every velocity layer
+    drum_layers: Vec<(u8, DrumKit)>, // Velocity layer kits by top velocity, softest first
+    drum_kit_styles: Vec<(DrumKitStyle, Vec<(u8, DrumKit)>)>, // Kits of drum programs
+    drum_kit_style: DrumKitStyle,
     sample_rate: u32,
     num_channel: Channel,
     fade_out_sample: u64,
     sample_map: Arc<RwLock<HashMap<u8, Sample>>>,
     max_total_voices: u32,
+    gain: f32,               // Master gain applied when summing the instances
+    per_instance_gain: bool, // Divide each instance by the number of instances
+    mean_squares: Vec<f32>,  // Smoothed mean square of each instance's output, for level meters
+    ignore_aftertouch: bool, // Drop channel and poly pressure instead of routing them
+    portamento: Option<Portamento>,
+    cc_smoothing: Option<CcSmoothing>,
+    sample_sources: Option<SampleSources>, // Kept for MTS retuning
+    sample_cache: Option<SampleCache>,     // Loads the melodic samples on first use
+    surround: Option<SurroundPanner>,      // One mono instance per bus when set
+    inserts: Vec<ChannelInsert>,
+    instruments: Instruments,            // Bank and program of each channel
+    drum_remap: [u8; 128],               // Key each drum note is played on
+    scheduled: Vec<(usize, SynthEvent)>, // Events at a frame offset into the next scheduled fill
+    position: u64,                       // Frames rendered by fill_buffer_scheduled
+    event_frame: u64,                    // Frame of the event being queued
+    note_events: Option<Vec<NoteEvent>>, // Notes started and stopped, when recording them
+    commands: Option<Vec<(u64, u32)>>,   // Remapped commands by frame, when recording them
+    channel_state: ChannelState,         // Queued programs, controllers and notes, for snapshots
 }
 
+/// Time constant of the level meter smoothing
+const LEVEL_SMOOTHING_SECS: f32 = 0.3;
+/// Pitch bend and controller update interval while a portamento glide or a smoothed control
+/// change is running, in frames
+const GLIDE_BLOCK_FRAMES: usize = 64;
+
 impl MultiSynth {
+    /// Copies of the drum kit for the instances that play drums
+    fn drum_kits(drum_kit: &Option<DrumKit>, count: usize) -> Vec<DrumKit> {
+        drum_kit
+            .iter()
+            .flat_map(|kit| std::iter::repeat_n(kit, count))
+            .cloned()
+            .collect()
+    }
+
+    /// Copies of the drum kit for the shared instances. With velocity layers every copy is one
+    /// kit per layer, so drum instance `i` holds layer `i % layers`.
+    fn layered_drum_kits(&self) -> Vec<DrumKit> {
+        if self.drum_layer_count() == 1 {
+            return Self::drum_kits(&self.drum_kit_storage, self.drum_instances);
+        }
+        (0..self.drum_instances)
+            .flat_map(|_| self.drum_layers.iter().map(|(_, kit)| kit.clone()))
+            .collect()
+    }
+
+    /// Velocity layers the drum instances are split into, 1 when the drums play on a single
+    /// instance (surround, an insert on channel 10)
+    fn drum_layer_count(&self) -> usize {
+        if self.surround.is_some() || self.inserts.iter().any(|insert| insert.channel == 0x09) {
+            1
+        } else {
+            self.drum_layers.len().max(1)
+        }
+    }
+
+    /// Every instance shares `sample_map`, the drum kits are moved into the first instances.
+    /// The instances of `insert_channels` come last, sharing the voices like the others, the
+    /// drum channel's takes the last drum kit.
+    #[allow(clippy::too_many_arguments)]
     fn build_synths(
         sample_rate: u32,
         num_channel: Channel,
         max_total_voices: u32,
         fade_out_sample: u64,
         sample_map: Arc<RwLock<HashMap<u8, Sample>>>,
-        drum_kit: Option<DrumKit>,
+        drum_kits: Vec<DrumKit>,
         mut num_instances: usize,
+        insert_channels: &[u8],
     ) -> (Vec<KSynth>, Vec<u32>) {
         let max_threads = num_cpus::get();
         if num_instances > max_threads {
@@ -45,21 +161,24 @@ impl MultiSynth {
             num_instances = 1;
         }
 
-        let base_voice_count = max_total_voices / num_instances as u32;
-        let mut max_voices = vec![base_voice_count; num_instances];
-        for i in 0..(max_total_voices % num_instances as u32) {
+        let instance_count = num_instances + insert_channels.len();
+        let base_voice_count = max_total_voices / instance_count as u32;
+        let mut max_voices = vec![base_voice_count; instance_count];
+        for i in 0..(max_total_voices % instance_count as u32) {
             max_voices[i as usize] += 1;
         }
+        let (shared_voices, insert_voices) = max_voices.split_at(num_instances);
 
         let mut synths = Vec::new();
         let mut filtered_max_voices = Vec::new();
+        let shared_drum_kits =
+            drum_kits.len() - usize::from(insert_channels.contains(&0x09)).min(drum_kits.len());
+        let mut drum_kits = drum_kits.into_iter();
 
-        let drum_kit_cloned = drum_kit.clone();
-
-        for (i, &voices) in max_voices.iter().enumerate() {
+        for &voices in shared_voices {
             if voices > 0 {
-                let current_drum_kit = if i == 0 && drum_kit_cloned.is_some() {
-                    drum_kit_cloned.clone()
+                let drum_kit = if synths.len() < shared_drum_kits {
+                    drum_kits.next()
                 } else {
                     None
                 };
@@ -69,15 +188,74 @@ impl MultiSynth {
                     voices,
                     fade_out_sample,
                     sample_map.clone(),
-                    current_drum_kit,
+                    drum_kit,
                 ));
                 filtered_max_voices.push(voices);
             }
         }
 
+        // The insert channels have nowhere else to play, so their instances are always built
+        for (&channel, &voices) in insert_channels.iter().zip(insert_voices) {
+            let voices = voices.max(1);
+            synths.push(KSynth::new(
+                sample_rate,
+                num_channel,
+                voices,
+                fade_out_sample,
+                sample_map.clone(),
+                if channel == 0x09 {
+                    drum_kits.next_back()
+                } else {
+                    None
+                },
+            ));
+            filtered_max_voices.push(voices);
+        }
+
         (synths, filtered_max_voices)
     }
 
+    /// One instance per surround bus, not limited by the CPU count. The first drum kit goes to
+    /// the bus of channel 10.
+    fn build_surround_synths(
+        surround: &SurroundPanner,
+        sample_rate: u32,
+        num_channel: Channel,
+        max_total_voices: u32,
+        fade_out_sample: u64,
+        sample_map: Arc<RwLock<HashMap<u8, Sample>>>,
+        drum_kits: Vec<DrumKit>,
+    ) -> (Vec<KSynth>, Vec<u32>) {
+        let mut drum_kit = drum_kits.into_iter().next();
+        let bus_count = surround.bus_count() as u32;
+        let drum_bus = surround.bus(9);
+        let max_voices: Vec<u32> = (0..bus_count)
+            .map(|bus| {
+                let extra = u32::from(bus < max_total_voices % bus_count);
+                (max_total_voices / bus_count + extra).max(1)
+            })
+            .collect();
+        let synths = max_voices
+            .iter()
+            .enumerate()
+            .map(|(bus, &voices)| {
+                KSynth::new(
+                    sample_rate,
+                    num_channel,
+                    voices,
+                    fade_out_sample,
+                    sample_map.clone(),
+                    if bus == drum_bus {
+                        drum_kit.take()
+                    } else {
+                        None
+                    },
+                )
+            })
+            .collect();
+        (synths, max_voices)
+    }
+
     pub fn new(
         sample_rate: u32,
         num_channel: Channel,
@@ -93,8 +271,9 @@ impl MultiSynth {
             max_total_voices,
             fade_out_sample,
             sample_map.clone(),
-            drum_kit.clone(),
+            Self::drum_kits(&drum_kit, 1),
             num_instances,
+            &[],
         );
 
         let synth_len = synths.len();
@@ -102,18 +281,44 @@ impl MultiSynth {
         MultiSynth {
             synths,
             note_map: HashMap::new(),
+            retiring: Vec::new(),
+            retiring_notes: HashMap::new(),
             note_counts: vec![0; synth_len],
+            assigned_notes: vec![0; synth_len],
             max_voices: filtered_max_voices,
             drum_kit_storage: drum_kit,
+            drum_instances: 1,
+            drum_layers: Vec::new(),
+            drum_kit_styles: Vec::new(),
+            drum_kit_style: DrumKitStyle::Standard,
             sample_rate,
             num_channel,
             fade_out_sample,
             sample_map,
             max_total_voices,
+            gain: 1.0,
+            per_instance_gain: false,
+            mean_squares: vec![0.0; synth_len],
+            ignore_aftertouch: false,
+            portamento: None,
+            cc_smoothing: None,
+            sample_sources: None,
+            sample_cache: None,
+            surround: None,
+            inserts: Vec::new(),
+            instruments: Instruments::default(),
+            drum_remap: std::array::from_fn(|key| key as u8),
+            scheduled: Vec::new(),
+            position: 0,
+            event_frame: 0,
+            note_events: None,
+            commands: None,
+            channel_state: ChannelState::default(),
         }
     }
 
     pub fn queue_midi_cmd(&mut self, cmd: u32) {
+        self.channel_state.record(cmd);
         let status = (cmd & 0xFF) as u8;
         let note = ((cmd >> 8) & 0xFF) as u8;
         let velocity = ((cmd >> 16) & 0xFF) as u8;
@@ -121,60 +326,278 @@ impl MultiSynth {
         let channel = status & 0x0F;
         let status_nibble = status & 0xF0;
 
-        if channel == 0x09 && self.drum_kit_storage.is_some() {
-            let idx = 0;
+        match status_nibble {
+            0xB0 => self.instruments.control_change(channel, note, velocity),
+            0xC0 => {
+                self.instruments.program_change(channel, note);
+                self.select_drum_kit(channel);
+            }
+            // Notes of muted programs aren't played
+            0x90 if velocity > 0 && self.instruments.sample_bank(channel).is_none() => return,
+            _ => {}
+        }
+
+        if self.plays_drums(channel) {
+            // Balanced across the instances holding a drum kit like melodic notes. KSynth plays
+            // its drum kit on channel 10, so drum banks on other channels are moved there.
+            let note = self.drum_remap[note as usize & 0x7F];
+            let cmd = (cmd & !0xFF0F) | 0x09 | ((note as u32) << 8);
+            if matches!(status_nibble, 0x80 | 0x90) {
+                self.record_command(cmd);
+            }
             match status_nibble {
-                0x90 => {
-                    if velocity == 0 {
-                        self.synths[idx].queue_midi_cmd(cmd);
-                    } else {
-                        self.synths[idx].queue_midi_cmd(cmd);
-                    }
-                }
-                0x80 => self.synths[idx].queue_midi_cmd(cmd),
+                0x90 if velocity > 0 => self.note_on(channel, note, cmd),
+                0x90 | 0x80 => self.note_off(channel, note, cmd),
                 _ => {}
             }
         } else {
+            if !(self.ignore_aftertouch && matches!(status_nibble, 0xA0 | 0xD0)) {
+                self.record_command(cmd);
+            }
             match status_nibble {
                 0x90 => {
                     if velocity == 0 {
                         self.note_off(channel, note, cmd);
                     } else {
+                        if let Some(bend) = self
+                            .portamento
+                            .as_mut()
+                            .and_then(|portamento| portamento.note_on(channel, note))
+                        {
+                            self.broadcast(bend);
+                        }
+                        if let Some(cache) = self.sample_cache.as_mut() {
+                            cache.play(note, &self.sample_map, |key| {
+                                self.channel_state.key_sounding(key)
+                            });
+                        }
                         self.note_on(channel, note, cmd);
                     }
                 }
                 0x80 => self.note_off(channel, note, cmd),
-                0xA0..=0xEF => {
-                    for synth in &mut self.synths {
-                        synth.queue_midi_cmd(cmd);
+                0xB0 => {
+                    if let Some(portamento) = self.portamento.as_mut() {
+                        portamento.control_change(channel, note, velocity);
+                    }
+                    match self.cc_smoothing.as_mut() {
+                        Some(cc_smoothing) => {
+                            if let Some(cmd) = cc_smoothing.control_change(channel, note, velocity)
+                            {
+                                self.broadcast(cmd);
+                            }
+                        }
+                        None => self.broadcast(cmd),
+                    }
+                }
+                0xE0 => match self.portamento.as_mut() {
+                    Some(portamento) => {
+                        let value = ((velocity as u16 & 0x7F) << 7) | (note as u16 & 0x7F);
+                        let bend = portamento.pitch_bend(channel, value);
+                        self.broadcast(bend);
+                    }
+                    None => self.broadcast(cmd),
+                },
+                0xA0 | 0xD0 if self.ignore_aftertouch => {}
+                // Poly pressure only matters to the instance playing that note
+                0xA0 => {
+                    let note_key = NoteKey { channel, note };
+                    if let Some(&idx) = self.note_map.get(&note_key) {
+                        self.synths[idx].queue_midi_cmd(cmd);
+                    } else if let Some(&idx) = self.retiring_notes.get(&note_key) {
+                        self.retiring[idx].synth.queue_midi_cmd(cmd);
                     }
                 }
+                // Channel pressure goes to the instances holding notes on that channel
+                0xD0 => {
+                    let mut sent = vec![false; self.synths.len()];
+                    for (note_key, &idx) in &self.note_map {
+                        if note_key.channel == channel && !sent[idx] {
+                            self.synths[idx].queue_midi_cmd(cmd);
+                            sent[idx] = true;
+                        }
+                    }
+                    let mut sent = vec![false; self.retiring.len()];
+                    for (note_key, &idx) in &self.retiring_notes {
+                        if note_key.channel == channel && !sent[idx] {
+                            self.retiring[idx].synth.queue_midi_cmd(cmd);
+                            sent[idx] = true;
+                        }
+                    }
+                }
+                0xC0 => self.broadcast(cmd),
                 _ => {}
             }
         }
     }
 
+    /// Replaces the sample of `key` with one playing at `frequency`, for notes started afterwards.
+    /// Only keys with a sample source can be retuned.
+    fn retune(&mut self, key: u8, frequency: f64) {
+        if let Some(source) = self
+            .sample_sources
+            .as_ref()
+            .and_then(|sources| sources.get(&key))
+        {
+            self.sample_map
+                .write()
+                .unwrap()
+                .insert(key, source.to_sample(frequency));
+        }
+    }
+
+    fn broadcast(&mut self, cmd: u32) {
+        for synth in &mut self.synths {
+            synth.queue_midi_cmd(cmd);
+        }
+        for retiring in &mut self.retiring {
+            retiring.synth.queue_midi_cmd(cmd);
+        }
+    }
+
+    /// Queues a high resolution event, scaled down to the MIDI 1.0 command KSynth expects.
+    /// Per-note controllers have no MIDI 1.0 equivalent and are dropped.
+    pub fn queue_event(&mut self, event: &SynthEvent) {
+        if let SynthEvent::NoteTuning { key, pitch } = *event {
+            self.channel_state.record_tuning(key, pitch);
+            self.retune(key, mts_frequency(pitch));
+        } else if let Some(cmd) = event.to_midi1() {
+            self.queue_midi_cmd(cmd);
+        }
+    }
+
+    /// Bank and program selected on `channel` (0-15)
+    pub fn channel_instrument(&self, channel: u8) -> Instrument {
+        self.instruments.get(channel)
+    }
+
+    /// Programs, controllers, pitch bends, held notes and retuned keys of the channels, see
+    /// `synth_snapshot`
+    pub fn snapshot(&self) -> SynthSnapshot {
+        self.channel_state
+            .snapshot(&self.instruments, self.position)
+    }
+
+    /// Brings a synth that hasn't played yet to the state of `snapshot`. The held notes start
+    /// again from the start of their samples.
+    pub fn restore(&mut self, snapshot: &SynthSnapshot) {
+        self.position = snapshot.position;
+        self.event_frame = snapshot.position;
+        for &(key, pitch) in &snapshot.tunings {
+            self.queue_event(&SynthEvent::NoteTuning { key, pitch });
+        }
+        for &cmd in &snapshot.commands {
+            self.queue_midi_cmd(cmd);
+        }
+    }
+
+    /// Switches to the built-in kit of the program selected on `channel` if it plays drums. There
+    /// is one kit at a time, the last one selected on any drum channel. Only the instances
+    /// holding a kit are replaced, unless the new kit has a different number of layers.
+    fn select_drum_kit(&mut self, channel: u8) {
+        if !self.plays_drums(channel) {
+            return;
+        }
+        let style = DrumKitStyle::from_program(self.instruments.get(channel).program);
+        if style == self.drum_kit_style {
+            return;
+        }
+        if let Some((_, layers)) = self.drum_kit_styles.iter().find(|(s, _)| *s == style) {
+            self.drum_kit_style = style;
+            let layers = layers.clone();
+            if layers.len().max(1) == self.drum_layers.len().max(1) {
+                self.store_drum_layers(layers);
+                self.replace_drum_instances();
+            } else {
+                self.set_drum_layers(layers);
+            }
+        }
+    }
+
+    /// Whether notes on `channel` play on the drum kit
+    fn plays_drums(&self, channel: u8) -> bool {
+        self.drum_kit_storage.is_some()
+            && self.instruments.sample_bank(channel) == Some(SampleBank::Drums)
+    }
+
+    fn record_note(&mut self, channel: u8, key: u8, velocity: u8) {
+        if let Some(ref mut note_events) = self.note_events {
+            note_events.push(NoteEvent {
+                frame: self.event_frame,
+                channel,
+                key,
+                velocity,
+            });
+        }
+    }
+
+    fn record_command(&mut self, cmd: u32) {
+        if let Some(ref mut commands) = self.commands {
+            commands.push((self.event_frame, cmd));
+        }
+    }
+
     fn note_on(&mut self, channel: u8, note: u8, cmd: u32) {
         let note_key = NoteKey { channel, note };
+        // Channel the instances play the note on, 10 for drum banks
+        let synth_channel = (cmd & 0x0F) as u8;
 
         if let Some(&old_idx) = self.note_map.get(&note_key) {
-            let note_off_cmd = (0x80 | channel) as u32 | ((note as u32) << 8) | (0 << 16);
+            let note_off_cmd = (0x80 | synth_channel) as u32 | ((note as u32) << 8) | (0 << 16);
             self.synths[old_idx].queue_midi_cmd(note_off_cmd);
             if self.note_counts[old_idx] > 0 {
                 self.note_counts[old_idx] -= 1;
             }
+            self.note_map.remove(&note_key);
+            self.record_note(channel, note, 0);
+        } else if let Some(old_idx) = self.retiring_notes.remove(&note_key) {
+            let note_off_cmd = (0x80 | synth_channel) as u32 | ((note as u32) << 8);
+            self.retiring[old_idx].synth.queue_midi_cmd(note_off_cmd);
+            self.record_note(channel, note, 0);
         }
 
+        // Surround channels always play on their own bus, insert channels on their instance,
+        // drums on the drum kit instances
+        let shared_instances = self.shared_instances();
+        let instances = match (self.insert_instance(channel), &self.surround) {
+            (Some(idx), _) => idx..idx + 1,
+            (None, Some(surround)) => {
+                let bus = surround.bus(synth_channel);
+                bus..bus + 1
+            }
+            (None, None) if self.plays_drums(channel) => {
+                0..(self.drum_instances * self.drum_layer_count()).min(shared_instances)
+            }
+            (None, None) => 0..shared_instances,
+        };
+        // Drums only play on the instances holding the layer of their velocity, or on any drum
+        // instance if there are too few instances for the layer
+        let layers = self.drum_layer_count();
+        let velocity = ((cmd >> 16) & 0x7F) as u8;
+        let drum_layer =
+            (layers > 1 && self.plays_drums(channel) && self.insert_instance(channel).is_none())
+                .then(|| {
+                    self.drum_layers
+                        .iter()
+                        .position(|&(top, _)| velocity <= top)
+                        .unwrap_or(layers - 1)
+                })
+                .filter(|&layer| layer < instances.len());
         if let Some((idx, _)) = self
             .note_counts
             .iter()
             .enumerate()
-            .filter(|&(i, &count)| count < self.max_voices[i])
+            .filter(|&(i, &count)| {
+                instances.contains(&i)
+                    && drum_layer.is_none_or(|layer| i % layers == layer)
+                    && count < self.max_voices[i]
+            })
             .min_by_key(|&(_, &count)| count)
         {
             self.synths[idx].queue_midi_cmd(cmd);
             self.note_map.insert(note_key, idx);
             self.note_counts[idx] += 1;
+            self.assigned_notes[idx] += 1;
+            self.record_note(channel, note, velocity);
         }
     }
 
@@ -186,31 +609,387 @@ impl MultiSynth {
             if self.note_counts[idx] > 0 {
                 self.note_counts[idx] -= 1;
             }
+            self.record_note(channel, note, 0);
+        } else if let Some(idx) = self.retiring_notes.remove(&note_key) {
+            self.retiring[idx].synth.queue_midi_cmd(cmd);
+            self.record_note(channel, note, 0);
+        }
+    }
+
+    /// Queues `event` to be applied `frame` frames into the next `fill_buffer_scheduled` call,
+    /// after the earlier scheduled events
+    pub fn schedule_event(&mut self, frame: usize, event: SynthEvent) {
+        self.scheduled.push((frame, event));
+    }
+
+    /// Renders interleaved `num_channel` frames, splitting the buffer so every scheduled event is
+    /// applied at its exact frame. Events past the end stay scheduled for the next call.
+    pub fn fill_buffer_scheduled(&mut self, output: &mut [f32], num_channel: usize) {
+        let frame_count = output.len() / num_channel.max(1);
+        let mut scheduled = std::mem::take(&mut self.scheduled);
+        scheduled.sort_by_key(|&(frame, _)| frame);
+
+        let mut start = 0;
+        for (frame, event) in scheduled {
+            if frame >= frame_count {
+                self.scheduled.push((frame - frame_count, event));
+                continue;
+            }
+            let end = frame * num_channel;
+            if end > start {
+                self.fill_buffer(&mut output[start..end]);
+                start = end;
+            }
+            self.event_frame = self.position + frame as u64;
+            self.queue_event(&event);
         }
+        self.fill_buffer(&mut output[start..]);
+        self.position += frame_count as u64;
+        self.event_frame = self.position;
     }
 
     pub fn fill_buffer(&mut self, output: &mut [f32]) {
-        let len = output.len();
-        let temp_buffers: Vec<Vec<f32>> = self
-            .synths
+        if !self
+            .portamento
+            .as_ref()
+            .is_some_and(|portamento| portamento.is_gliding())
+            && !self
+                .cc_smoothing
+                .as_ref()
+                .is_some_and(|cc_smoothing| cc_smoothing.is_smoothing())
+        {
+            self.fill_block(output);
+            return;
+        }
+
+        let num_channel = self
+            .surround
+            .as_ref()
+            .map_or(self.num_channel as usize, |surround| {
+                surround.num_speakers()
+            });
+        // Render in small blocks so the glide bends and controllers are updated smoothly
+        for block in output.chunks_mut(GLIDE_BLOCK_FRAMES * num_channel) {
+            self.fill_block(block);
+            let seconds = block.len() as f32 / (self.sample_rate as f32 * num_channel as f32);
+            let mut cmds = match self.portamento.as_mut() {
+                Some(portamento) => portamento.advance(seconds),
+                None => Vec::new(),
+            };
+            if let Some(cc_smoothing) = self.cc_smoothing.as_mut() {
+                cmds.extend(cc_smoothing.advance(seconds));
+            }
+            for cmd in cmds {
+                self.broadcast(cmd);
+            }
+        }
+    }
+
+    fn fill_block(&mut self, output: &mut [f32]) {
+        // Surround instances render one mono sample per output frame
+        let len = match self.surround {
+            Some(ref surround) => output.len() / surround.num_speakers(),
+            None => output.len(),
+        };
+        let render = |synth: &mut KSynth| {
+            let mut temp = vec![0.0f32; len];
+            synth.fill_buffer(&mut temp);
+            temp
+        };
+        let mut temp_buffers: Vec<Vec<f32>> = self.synths.par_iter_mut().map(render).collect();
+        let first_insert = self.shared_instances();
+        for (insert, buffer) in self
+            .inserts
+            .iter_mut()
+            .zip(&mut temp_buffers[first_insert..])
+        {
+            insert.effects.process(buffer);
+        }
+        let retiring_buffers: Vec<Vec<f32>> = self
+            .retiring
             .par_iter_mut()
-            .map(|synth| {
-                let mut temp = vec![0.0f32; len];
-                synth.fill_buffer(&mut temp);
-                temp
-            })
+            .map(|retiring| render(&mut retiring.synth))
             .collect();
 
+        let instance_gain = if self.per_instance_gain {
+            self.gain / self.synths.len() as f32
+        } else {
+            self.gain
+        };
+
         output.fill(0.0);
-        for buffer in &temp_buffers {
-            for (o, &s) in output.iter_mut().zip(buffer.iter()) {
-                *o += s;
+        let buses =
+            (0..temp_buffers.len()).chain(self.retiring.iter().map(|retiring| retiring.bus));
+        for (bus, buffer) in buses.zip(temp_buffers.iter().chain(&retiring_buffers)) {
+            match self.surround {
+                Some(ref surround) => surround.mix(bus, buffer, instance_gain, output),
+                None => {
+                    for (o, &s) in output.iter_mut().zip(buffer.iter()) {
+                        *o += s * instance_gain;
+                    }
+                }
+            }
+        }
+
+        if len > 0 {
+            // The instance buffers are interleaved, except the mono surround buses
+            let instance_channels = match self.surround {
+                Some(_) => 1,
+                None => self.num_channel as usize,
+            };
+            let smoothing_samples =
+                LEVEL_SMOOTHING_SECS * self.sample_rate as f32 * instance_channels as f32;
+            let smoothing = 1.0 - (-(len as f32) / smoothing_samples).exp();
+            for (mean_square, buffer) in self.mean_squares.iter_mut().zip(&temp_buffers) {
+                let buffer_mean_square = buffer.iter().map(|s| s * s).sum::<f32>() / len as f32
+                    * instance_gain
+                    * instance_gain;
+                *mean_square += smoothing * (buffer_mean_square - *mean_square);
+            }
+        }
+
+        self.drop_silent_retiring();
+    }
+
+    /// Drops the retired instances that have nothing left to play. Only called after rendering,
+    /// so the notes queued to them have started.
+    fn drop_silent_retiring(&mut self) {
+        if self
+            .retiring
+            .iter()
+            .all(|retiring| retiring.synth.get_polyphony() > 0)
+        {
+            return;
+        }
+
+        let mut new_indices = vec![None; self.retiring.len()];
+        let mut kept = 0;
+        for (new_index, retiring) in new_indices.iter_mut().zip(&self.retiring) {
+            if retiring.synth.get_polyphony() > 0 {
+                *new_index = Some(kept);
+                kept += 1;
             }
         }
+        let mut index = 0;
+        self.retiring.retain(|_| {
+            index += 1;
+            new_indices[index - 1].is_some()
+        });
+        self.retiring_notes
+            .retain(|_, idx| match new_indices[*idx] {
+                Some(new_index) => {
+                    *idx = new_index;
+                    true
+                }
+                None => false,
+            });
+    }
+
+    /// Swaps the sample set for notes started afterwards. The map is shared by every instance,
+    /// so nothing is rebuilt. MTS retuning and lazy loading are turned off as their sources
+    /// belong to the old set.
+    pub fn replace_samples(&mut self, samples: HashMap<u8, Sample>) {
+        *self.sample_map.write().unwrap() = samples;
+        self.sample_sources = None;
+        self.sample_cache = None;
+    }
+
+    /// Gives `count` instances a copy of the drum kit (one per velocity layer) and balances the
+    /// drum notes across them, so drum heavy MIDIs aren't limited to one thread. Each copy holds
+    /// its own sample data.
+    pub fn set_drum_instances(&mut self, count: usize) {
+        self.drum_instances = count.max(1);
+        self.rebuild(self.shared_instances());
+    }
+
+    /// Swaps the drum kit. It's held by an instance, so the instances are rebuilt, with the
+    /// old ones playing out their notes.
+    pub fn replace_drum_kit(&mut self, drum_kit: Option<DrumKit>) {
+        self.drum_kit_storage = drum_kit;
+        self.drum_layers.clear();
+        self.rebuild(self.shared_instances());
+    }
+
+    /// Swaps the drum kit for one with velocity layers, each kit with the highest velocity it
+    /// plays, softest first. Every drum instance becomes one per layer. The layer of velocity 100
+    /// plays wherever the drums are on a single instance.
+    pub fn set_drum_layers(&mut self, layers: Vec<(u8, DrumKit)>) {
+        self.store_drum_layers(layers);
+        self.rebuild(self.shared_instances());
+    }
+
+    fn store_drum_layers(&mut self, layers: Vec<(u8, DrumKit)>) {
+        self.drum_kit_storage = layers
+            .iter()
+            .find(|&&(top, _)| top >= 100)
+            .or(layers.last())
+            .map(|(_, kit)| kit.clone());
+        self.drum_layers = if layers.len() > 1 { layers } else { Vec::new() };
+    }
+
+    /// Replaces the instances holding a drum kit with ones holding the stored kits, laid out
+    /// like `rebuild` does. The old ones play out their notes like after a rebuild, the other
+    /// instances are left alone.
+    fn replace_drum_instances(&mut self) {
+        let kits: Vec<(usize, DrumKit)> = match (&self.surround, self.insert_instance(0x09)) {
+            (Some(surround), _) => self
+                .drum_kit_storage
+                .iter()
+                .map(|kit| (surround.bus(9), kit.clone()))
+                .collect(),
+            (None, Some(idx)) => self
+                .drum_kit_storage
+                .iter()
+                .map(|kit| (idx, kit.clone()))
+                .collect(),
+            (None, None) => self
+                .layered_drum_kits()
+                .into_iter()
+                .take(self.shared_instances())
+                .enumerate()
+                .collect(),
+        };
+        for (idx, kit) in kits {
+            let synth = KSynth::new(
+                self.sample_rate,
+                self.num_channel,
+                self.max_voices[idx],
+                self.fade_out_sample,
+                self.sample_map.clone(),
+                Some(kit),
+            );
+            let retiring_idx = self.retiring.len();
+            self.retiring.push(RetiringSynth {
+                synth: std::mem::replace(&mut self.synths[idx], synth),
+                bus: idx,
+            });
+            let moved: Vec<NoteKey> = self
+                .note_map
+                .iter()
+                .filter(|&(_, &note_idx)| note_idx == idx)
+                .map(|(&note_key, _)| note_key)
+                .collect();
+            for note_key in moved {
+                self.note_map.remove(&note_key);
+                self.retiring_notes.insert(note_key, retiring_idx);
+            }
+            self.note_counts[idx] = 0;
+            self.replay_channel_state(idx..idx + 1);
+        }
+    }
+
+    /// Enables MTS retuning of the keys in `sources`
+    pub fn set_sample_sources(&mut self, sources: SampleSources) {
+        self.sample_sources = Some(sources);
+    }
+
+    /// Loads the melodic samples through `cache` when their key is first played
+    pub fn set_sample_cache(&mut self, cache: SampleCache) {
+        self.sample_cache = Some(cache);
+    }
+
+    /// Emulates CC 5/65 portamento with pitch bend glides
+    pub fn set_portamento(&mut self, enabled: bool) {
+        self.portamento = enabled.then(Portamento::default);
+    }
+
+    /// Ramps volume and expression changes over `seconds` instead of applying them at once,
+    /// disabled at 0
+    pub fn set_cc_smoothing(&mut self, seconds: f32) {
+        self.cc_smoothing = (seconds > 0.0).then(|| CcSmoothing::new(seconds));
+    }
+
+    /// Overrides the sample bank of programs, see `instrument`
+    pub fn set_program_remap(&mut self, remap: Vec<RemapRule>) {
+        self.instruments.set_remap(remap);
+    }
+
+    /// Plays drum notes on the keys of `table`, indexed by the key in the MIDI
+    pub fn set_drum_remap(&mut self, table: [u8; 128]) {
+        self.drum_remap = table;
     }
 
+    /// Built-in kits to switch to on drum program changes, with their velocity layers like
+    /// `set_drum_layers`. The current kit is the standard kit.
+    pub fn set_drum_kit_styles(&mut self, mut kits: Vec<(DrumKitStyle, Vec<(u8, DrumKit)>)>) {
+        if !self.drum_layers.is_empty() {
+            kits.push((DrumKitStyle::Standard, self.drum_layers.clone()));
+        } else if let Some(ref kit) = self.drum_kit_storage {
+            kits.push((DrumKitStyle::Standard, vec![(127, kit.clone())]));
+        }
+        self.drum_kit_styles = kits;
+        self.drum_kit_style = DrumKitStyle::Standard;
+    }
+
+    /// Records the notes that start and stop on the instances, see `take_note_events`
+    pub fn set_record_notes(&mut self, enabled: bool) {
+        self.note_events = enabled.then(Vec::new);
+    }
+
+    /// Notes started and stopped since the last call, in the order they were queued
+    pub fn take_note_events(&mut self) -> Vec<NoteEvent> {
+        self.note_events
+            .as_mut()
+            .map_or_else(Vec::new, std::mem::take)
+    }
+
+    /// Records the commands the instances are given after drum and program remapping, without
+    /// the notes of muted programs, see `take_commands`
+    pub fn set_record_commands(&mut self, enabled: bool) {
+        self.commands = enabled.then(Vec::new);
+    }
+
+    /// `(frame, command)` of the commands queued since the last call, in order
+    pub fn take_commands(&mut self) -> Vec<(u64, u32)> {
+        self.commands.as_mut().map_or_else(Vec::new, std::mem::take)
+    }
+
+    /// Drops channel pressure and poly aftertouch entirely
+    pub fn set_ignore_aftertouch(&mut self, ignore: bool) {
+        self.ignore_aftertouch = ignore;
+    }
+
+    /// Sets the linear gain applied to the summed output
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    /// Scales each instance by `1 / instance count` so the output level doesn't depend on the thread count
+    pub fn set_per_instance_gain(&mut self, enabled: bool) {
+        self.per_instance_gain = enabled;
+    }
+
+    /// Active voices, including the ones still fading out on replaced instances
     pub fn get_polyphony(&self) -> u32 {
-        self.synths.iter().map(|synth| synth.get_polyphony()).sum()
+        self.synths
+            .iter()
+            .map(|synth| synth.get_polyphony())
+            .sum::<u32>()
+            + self
+                .retiring
+                .iter()
+                .map(|retiring| retiring.synth.get_polyphony())
+                .sum::<u32>()
+    }
+
+    /// Voices, limits, rendering time and note counts of each instance
+    pub fn instance_stats(&self) -> Vec<InstanceStats> {
+        self.synths
+            .iter()
+            .enumerate()
+            .map(|(i, synth)| InstanceStats {
+                polyphony: synth.get_polyphony(),
+                max_voices: self.max_voices[i],
+                rendering_time_ratio: synth.get_rendering_time(),
+                held_notes: self.note_counts[i],
+                assigned_notes: self.assigned_notes[i],
+            })
+            .collect()
+    }
+
+    /// Smoothed RMS level of each instance's contribution to the output
+    pub fn get_instance_rms(&self) -> Vec<f32> {
+        self.mean_squares.iter().map(|ms| ms.sqrt()).collect()
     }
 
     pub fn get_max_polyphony(&self) -> u32 {
@@ -229,59 +1008,131 @@ impl MultiSynth {
 
     pub fn set_max_polyphony(&mut self, max_total_voices: u32) {
         self.max_total_voices = max_total_voices;
+        self.rebuild(self.shared_instances());
+    }
 
-        for (note_key, &idx) in &self.note_map {
-            let channel = note_key.channel;
-            let note = note_key.note;
-            let note_off_cmd = (0x80 | channel) as u32 | ((note as u32) << 8) | (0 << 16);
-            if idx < self.synths.len() {
-                self.synths[idx].queue_midi_cmd(note_off_cmd);
-            }
-        }
+    /// Instances shared by the channels without insert effects
+    pub fn get_num_instances(&self) -> usize {
+        self.shared_instances()
+    }
 
-        let (new_synths, new_max_voices) = Self::build_synths(
-            self.sample_rate,
-            self.num_channel,
-            max_total_voices,
-            self.fade_out_sample,
-            self.sample_map.clone(),
-            self.drum_kit_storage.clone(),
-            self.synths.len(),
-        );
+    fn shared_instances(&self) -> usize {
+        self.synths.len() - self.inserts.len()
+    }
 
-        self.synths = new_synths;
-        self.max_voices = new_max_voices;
-        self.note_map.clear();
-        self.note_counts = vec![0; self.synths.len()];
+    /// Instance of `channel` if it has insert effects
+    fn insert_instance(&self, channel: u8) -> Option<usize> {
+        let position = self
+            .inserts
+            .iter()
+            .position(|insert| insert.channel == channel)?;
+        Some(self.shared_instances() + position)
     }
 
-    pub fn get_num_instances(&self) -> usize {
-        self.synths.len()
+    /// Plays each channel of `inserts` (0-15) on an instance of its own and runs its output
+    /// through the effects before it's mixed with the rest. Ignored while rendering surround.
+    pub fn set_channel_inserts(&mut self, inserts: Vec<(u8, Box<dyn Effect>)>) {
+        if self.surround.is_some() {
+            return;
+        }
+        let num_instances = self.shared_instances();
+        self.inserts = inserts
+            .into_iter()
+            .map(|(channel, effects)| ChannelInsert {
+                channel: channel & 0xF,
+                effects,
+            })
+            .collect();
+        self.rebuild(num_instances);
     }
 
+    /// Ignored while rendering surround, which always uses one instance per bus
     pub fn set_num_instances(&mut self, new_num_instances: usize) {
-        for (note_key, &idx) in &self.note_map {
-            let channel = note_key.channel;
-            let note = note_key.note;
-            let note_off_cmd = (0x80 | channel) as u32 | ((note as u32) << 8) | (0 << 16);
-            if idx < self.synths.len() {
-                self.synths[idx].queue_midi_cmd(note_off_cmd);
+        self.rebuild(new_num_instances);
+    }
+
+    /// Renders one mono instance per bus of `surround` and pans them across its speakers.
+    /// The synth must have been created with a mono channel count.
+    pub fn set_surround(&mut self, surround: SurroundPanner) {
+        self.surround = Some(surround);
+        // The buses are the instances, channels can't have one of their own
+        let num_instances = self.shared_instances();
+        self.inserts.clear();
+        self.rebuild(num_instances);
+        // Set before rendering, the old instances have nothing to play out and aren't buses
+        self.retiring.clear();
+        self.retiring_notes.clear();
+    }
+
+    /// Replaces the instances. The old ones keep playing their held notes until they're
+    /// released and have faded out, so reconfiguring while rendering doesn't cut notes off.
+    /// Until then the voices can exceed the polyphony limit. The new ones start with the
+    /// channel state the old ones had.
+    fn rebuild(&mut self, num_instances: usize) {
+        let (new_synths, new_max_voices) = match self.surround {
+            Some(ref surround) => Self::build_surround_synths(
+                surround,
+                self.sample_rate,
+                self.num_channel,
+                self.max_total_voices,
+                self.fade_out_sample,
+                self.sample_map.clone(),
+                Self::drum_kits(&self.drum_kit_storage, 1),
+            ),
+            None => {
+                let insert_channels: Vec<u8> =
+                    self.inserts.iter().map(|insert| insert.channel).collect();
+                // Drums on an insert play only on its instance
+                let drum_kits = if insert_channels.contains(&0x09) {
+                    Self::drum_kits(&self.drum_kit_storage, 1)
+                } else {
+                    self.layered_drum_kits()
+                };
+                Self::build_synths(
+                    self.sample_rate,
+                    self.num_channel,
+                    self.max_total_voices,
+                    self.fade_out_sample,
+                    self.sample_map.clone(),
+                    drum_kits,
+                    num_instances,
+                    &insert_channels,
+                )
             }
-        }
+        };
 
-        let (new_synths, new_max_voices) = Self::build_synths(
-            self.sample_rate,
-            self.num_channel,
-            self.max_total_voices,
-            self.fade_out_sample,
-            self.sample_map.clone(),
-            self.drum_kit_storage.clone(),
-            new_num_instances,
+        let offset = self.retiring.len();
+        let old_synths = std::mem::replace(&mut self.synths, new_synths);
+        self.retiring.extend(
+            old_synths
+                .into_iter()
+                .enumerate()
+                .map(|(bus, synth)| RetiringSynth { synth, bus }),
+        );
+        self.retiring_notes.extend(
+            self.note_map
+                .drain()
+                .map(|(note_key, idx)| (note_key, idx + offset)),
         );
 
-        self.synths = new_synths;
         self.max_voices = new_max_voices;
-        self.note_map.clear();
         self.note_counts = vec![0; self.synths.len()];
+        self.assigned_notes = vec![0; self.synths.len()];
+        self.mean_squares = vec![0.0; self.synths.len()];
+        self.replay_channel_state(0..self.synths.len());
+    }
+
+    /// Gives the new `instances` the programs, controllers and pitch bends the channels were
+    /// given so far. Drum channels only ever get their notes, and channel pressure only goes to
+    /// instances holding notes, which new ones don't.
+    fn replay_channel_state(&mut self, instances: Range<usize>) {
+        for cmd in self.channel_state.channel_commands(&self.instruments) {
+            if self.plays_drums((cmd & 0x0F) as u8) || cmd & 0xF0 == 0xD0 {
+                continue;
+            }
+            for synth in &mut self.synths[instances.clone()] {
+                synth.queue_midi_cmd(cmd);
+            }
+        }
     }
 }
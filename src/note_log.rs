@@ -0,0 +1,83 @@
+//! `--note-log`: the notes that actually sounded, as one JSON object per line, for visualizers
+//! that should show the render rather than every note of the MIDI file. Notes dropped at the
+//! polyphony limit never appear, and a note cut by a retrigger gets a note off.
+//!
+//! ```json
+//! {"frame":48000,"time":1.0,"channel":9,"key":36,"velocity":100}
+//! ```
+//!
+//! `frame` is the output sample frame the note starts or stops at, after oversampling, leading
+//! silence trimming and `--prepend`, and `time` the same in seconds. Velocity 0 is a note off.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde_json::json;
+
+use crate::multi_synth::NoteEvent;
+
+pub struct NoteLog {
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    oversample: u32,
+    /// Output frames written before the render
+    offset: u64,
+    /// Events held back until the leading silence trimming knows how much it trims
+    pending: Vec<NoteEvent>,
+}
+
+impl NoteLog {
+    /// `offset` is the number of output frames written before the first rendered frame
+    pub fn create(
+        path: impl AsRef<Path>,
+        sample_rate: u32,
+        oversample: u32,
+        offset: u64,
+    ) -> std::io::Result<Self> {
+        Ok(NoteLog {
+            writer: BufWriter::new(File::create(path)?),
+            sample_rate,
+            oversample,
+            offset,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Adds the notes of the last rendered block. `trimmed_frames` is the leading silence
+    /// trimmed from the output, `None` while the trimming hasn't reached sound yet.
+    pub fn record(
+        &mut self,
+        events: Vec<NoteEvent>,
+        trimmed_frames: Option<u64>,
+    ) -> std::io::Result<()> {
+        self.pending.extend(events);
+        let Some(trimmed_frames) = trimmed_frames else {
+            return Ok(());
+        };
+        for event in self.pending.drain(..) {
+            // Notes in the trimmed silence are moved to the start of the output
+            let frame =
+                (event.frame / self.oversample as u64).saturating_sub(trimmed_frames) + self.offset;
+            writeln!(
+                self.writer,
+                "{}",
+                json!({
+                    "frame": frame,
+                    "time": frame as f64 / self.sample_rate as f64,
+                    "channel": event.channel,
+                    "key": event.key,
+                    "velocity": event.velocity,
+                })
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self, trimmed_frames: u64) -> std::io::Result<()> {
+        self.record(Vec::new(), Some(trimmed_frames))?;
+        self.writer.flush()
+    }
+}
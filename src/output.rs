@@ -0,0 +1,254 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::{
+    dither::{Dither, Quantizer},
+    surround::channel_mask,
+};
+
+/// Largest file size a RIFF/WAVE header can describe
+pub const RIFF_SIZE_LIMIT: u64 = u32::MAX as u64;
+
+/// Offset of the channel mask in a WAVE_FORMAT_EXTENSIBLE file written by hound:
+/// RIFF header (12) + fmt chunk header (8) + 20 bytes of format fields
+const WAV_CHANNEL_MASK_OFFSET: u64 = 40;
+/// Tail of the KSDATAFORMAT_SUBTYPE GUIDs, after the 16-bit format tag
+const SUBFORMAT_GUID_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// Minimal RF64 (EBU Tech 3306) writer. The real sizes are stored as 64-bit values in the
+/// `ds64` chunk and the 32-bit RIFF and data sizes are set to 0xFFFFFFFF.
+struct Rf64Writer {
+    file: BufWriter<File>,
+    bits_per_sample: u16,
+    bytes_per_sample: u64,
+    channels: u64,
+    data_bytes: u64,
+}
+
+impl Rf64Writer {
+    // Offset of the ds64 payload: RF64 header (12) + ds64 chunk header (8)
+    const DS64_OFFSET: u64 = 20;
+
+    fn create(path: &str, spec: WavSpec) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let format_tag: u16 = match spec.sample_format {
+            SampleFormat::Float => 3,
+            SampleFormat::Int => 1,
+        };
+        let block_align = spec.channels * (spec.bits_per_sample / 8);
+
+        file.write_all(b"RF64")?;
+        file.write_all(&u32::MAX.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+
+        // Filled in by finalize()
+        file.write_all(b"ds64")?;
+        file.write_all(&28u32.to_le_bytes())?;
+        file.write_all(&[0; 28])?;
+
+        // Surround layouts need WAVE_FORMAT_EXTENSIBLE to carry their speaker mask
+        let mask = channel_mask(spec.channels);
+        file.write_all(b"fmt ")?;
+        file.write_all(&(if mask.is_some() { 40u32 } else { 18 }).to_le_bytes())?;
+        file.write_all(&(if mask.is_some() { 0xFFFE } else { format_tag }).to_le_bytes())?;
+        file.write_all(&spec.channels.to_le_bytes())?;
+        file.write_all(&spec.sample_rate.to_le_bytes())?;
+        file.write_all(&(spec.sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&spec.bits_per_sample.to_le_bytes())?;
+        match mask {
+            Some(mask) => {
+                file.write_all(&22u16.to_le_bytes())?;
+                file.write_all(&spec.bits_per_sample.to_le_bytes())?;
+                file.write_all(&mask.to_le_bytes())?;
+                file.write_all(&format_tag.to_le_bytes())?;
+                file.write_all(&SUBFORMAT_GUID_TAIL)?;
+            }
+            None => file.write_all(&0u16.to_le_bytes())?,
+        }
+
+        file.write_all(b"data")?;
+        file.write_all(&u32::MAX.to_le_bytes())?;
+
+        Ok(Rf64Writer {
+            file,
+            bits_per_sample: spec.bits_per_sample,
+            bytes_per_sample: spec.bits_per_sample as u64 / 8,
+            channels: spec.channels as u64,
+            data_bytes: 0,
+        })
+    }
+
+    fn write_sample<S: hound::Sample>(&mut self, sample: S) -> hound::Result<()> {
+        sample.write(&mut self.file, self.bits_per_sample)?;
+        self.data_bytes += self.bytes_per_sample;
+        Ok(())
+    }
+
+    fn finalize(mut self) -> std::io::Result<()> {
+        let end = self.file.stream_position()?;
+        let sample_count = self.data_bytes / (self.bytes_per_sample * self.channels);
+
+        self.file.seek(SeekFrom::Start(Self::DS64_OFFSET))?;
+        self.file.write_all(&(end - 8).to_le_bytes())?;
+        self.file.write_all(&self.data_bytes.to_le_bytes())?;
+        self.file.write_all(&sample_count.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+enum SegmentFileWriter {
+    Wav(WavWriter<BufWriter<File>>),
+    Rf64(Rf64Writer),
+}
+
+impl SegmentFileWriter {
+    fn write_sample<S: hound::Sample>(&mut self, sample: S) -> hound::Result<()> {
+        match self {
+            SegmentFileWriter::Wav(writer) => writer.write_sample(sample),
+            SegmentFileWriter::Rf64(writer) => writer.write_sample(sample),
+        }
+    }
+
+    fn finalize(self) -> hound::Result<()> {
+        match self {
+            SegmentFileWriter::Wav(writer) => writer.finalize(),
+            SegmentFileWriter::Rf64(writer) => Ok(writer.finalize()?),
+        }
+    }
+}
+
+/// Output file written by `SegmentedWavWriter`, with its position in the whole render
+pub struct Segment {
+    pub path: String,
+    pub start_frame: u64,
+    pub frame_count: u64,
+}
+
+/// WAV writer that rolls over to `name_part002.wav`, `name_part003.wav`, ... after a fixed
+/// number of frames, finalizing each segment so none of them hit the 4 GB WAV limit.
+/// With `rf64` set, segments are written as RF64 instead, which has no size limit.
+/// Frames are always passed as float, a 16-bit spec quantizes them with TPDF dither.
+pub struct SegmentedWavWriter {
+    base_name: String,
+    spec: WavSpec,
+    max_frames_per_segment: Option<u64>,
+    rf64: bool,
+    writer: Option<SegmentFileWriter>,
+    quantizer: Option<Quantizer>,
+    segments: Vec<Segment>,
+}
+
+impl SegmentedWavWriter {
+    /// Creates `base_name.wav`, or `base_name_part001.wav` if segmenting
+    pub fn new(
+        base_name: &str,
+        spec: WavSpec,
+        max_frames_per_segment: Option<u64>,
+        rf64: bool,
+    ) -> hound::Result<Self> {
+        let mut writer = SegmentedWavWriter {
+            base_name: base_name.to_string(),
+            spec,
+            max_frames_per_segment: max_frames_per_segment.map(|frames| frames.max(1)),
+            rf64,
+            writer: None,
+            quantizer: (spec.sample_format == SampleFormat::Int && spec.bits_per_sample == 16)
+                .then(|| Quantizer::new(Dither::Tpdf, spec.channels)),
+            segments: Vec::new(),
+        };
+        writer.start_segment(0)?;
+        Ok(writer)
+    }
+
+    /// Changes the dither used for 16-bit output
+    pub fn set_dither(&mut self, dither: Dither) {
+        if let Some(ref mut quantizer) = self.quantizer {
+            *quantizer = Quantizer::new(dither, self.spec.channels);
+        }
+    }
+
+    fn start_segment(&mut self, start_frame: u64) -> hound::Result<()> {
+        let path = if self.max_frames_per_segment.is_some() {
+            format!("{}_part{:03}.wav", self.base_name, self.segments.len() + 1)
+        } else {
+            format!("{}.wav", self.base_name)
+        };
+
+        self.writer = Some(if self.rf64 {
+            SegmentFileWriter::Rf64(Rf64Writer::create(&path, self.spec)?)
+        } else {
+            SegmentFileWriter::Wav(WavWriter::create(&path, self.spec)?)
+        });
+        self.segments.push(Segment {
+            path,
+            start_frame,
+            frame_count: 0,
+        });
+        Ok(())
+    }
+
+    pub fn write_frame(&mut self, frame: &[f32]) -> hound::Result<()> {
+        let segment = self.segments.last().expect("No active segment");
+        if let Some(max_frames) = self.max_frames_per_segment
+            && segment.frame_count >= max_frames
+        {
+            let start_frame = segment.start_frame + segment.frame_count;
+            self.finish_segment()?;
+            self.start_segment(start_frame)?;
+        }
+
+        let writer = self.writer.as_mut().expect("No active segment");
+        match self.quantizer {
+            Some(ref mut quantizer) => {
+                for sample in quantizer.quantize(frame) {
+                    writer.write_sample(sample)?;
+                }
+            }
+            None => {
+                for &sample in frame {
+                    writer.write_sample(sample)?;
+                }
+            }
+        }
+        self.segments
+            .last_mut()
+            .expect("No active segment")
+            .frame_count += 1;
+        Ok(())
+    }
+
+    /// Finalizes the current segment. hound always writes the mask of the first N speakers,
+    /// so surround WAVs get their layout's mask patched in afterwards.
+    fn finish_segment(&mut self) -> hound::Result<()> {
+        let Some(writer) = self.writer.take() else {
+            return Ok(());
+        };
+        let is_wav = matches!(writer, SegmentFileWriter::Wav(_));
+        writer.finalize()?;
+
+        if is_wav
+            && let Some(mask) = channel_mask(self.spec.channels)
+            && let Some(segment) = self.segments.last()
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .open(&segment.path)?;
+            file.seek(SeekFrom::Start(WAV_CHANNEL_MASK_OFFSET))?;
+            file.write_all(&mask.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Finalizes the current segment and returns all written segments
+    pub fn finalize(mut self) -> hound::Result<Vec<Segment>> {
+        self.finish_segment()?;
+        Ok(self.segments)
+    }
+}
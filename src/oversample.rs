@@ -0,0 +1,85 @@
+use std::f64::consts::PI;
+
+/// Parses an oversampling factor like `2x` or `4`
+pub fn parse_oversample(s: &str) -> Result<u32, String> {
+    let factor = s.trim().trim_end_matches(['x', 'X']);
+    match factor.parse::<u32>() {
+        Ok(factor @ (1 | 2 | 4)) => Ok(factor),
+        _ => Err(format!(
+            "invalid oversampling factor `{}`, expected 1x, 2x or 4x",
+            s
+        )),
+    }
+}
+
+/// Taps per factor of the windowed-sinc lowpass filter
+const TAPS_PER_FACTOR: usize = 32;
+
+/// Decimates interleaved audio rendered at `factor` times the output rate, filtering
+/// everything above the output Nyquist frequency with a Blackman-windowed sinc first.
+pub struct Downsampler {
+    factor: usize,
+    num_channel: usize,
+    taps: Vec<f32>,
+    // Input frames not yet consumed by the filter, interleaved
+    pending: Vec<f32>,
+}
+
+impl Downsampler {
+    pub fn new(factor: u32, num_channel: u16) -> Self {
+        let factor = factor as usize;
+        let num_channel = num_channel as usize;
+
+        let tap_count = TAPS_PER_FACTOR * factor + 1;
+        let center = (tap_count / 2) as f64;
+        // Cutoff slightly below the output Nyquist frequency, in cycles per input sample
+        let cutoff = 0.45 / factor as f64;
+        let mut taps: Vec<f64> = (0..tap_count)
+            .map(|n| {
+                let x = n as f64 - center;
+                let sinc = if x == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * PI * cutoff * x).sin() / (PI * x)
+                };
+                let window = 0.42 - 0.5 * (2.0 * PI * n as f64 / (tap_count - 1) as f64).cos()
+                    + 0.08 * (4.0 * PI * n as f64 / (tap_count - 1) as f64).cos();
+                sinc * window
+            })
+            .collect();
+        let sum: f64 = taps.iter().sum();
+        taps.iter_mut().for_each(|tap| *tap /= sum);
+
+        Downsampler {
+            factor,
+            num_channel,
+            taps: taps.into_iter().map(|tap| tap as f32).collect(),
+            // Half the filter length of silence, so output frames line up with the input
+            pending: vec![0.0; (tap_count / 2) * num_channel],
+        }
+    }
+
+    /// Feeds oversampled frames and returns the output frames that are ready
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        self.pending.extend_from_slice(input);
+
+        let available_frames = self.pending.len() / self.num_channel;
+        let mut output = Vec::with_capacity(input.len() / self.factor + self.num_channel);
+        let mut start = 0;
+        while start + self.taps.len() <= available_frames {
+            for channel in 0..self.num_channel {
+                let sum: f32 = self
+                    .taps
+                    .iter()
+                    .enumerate()
+                    .map(|(k, tap)| tap * self.pending[(start + k) * self.num_channel + channel])
+                    .sum();
+                output.push(sum);
+            }
+            start += self.factor;
+        }
+
+        self.pending.drain(..start * self.num_channel);
+        output
+    }
+}
@@ -0,0 +1,241 @@
+//! CLAP and VST3 instrument plugin of the engine, built with nih-plug. Build with
+//! `--features plugin` and bundle the library with nih-plug's `cargo xtask bundle`, or rename
+//! it to `.clap` for CLAP hosts.
+//!
+//! The plugin has no editor: the sample folder (or zip, .tar.zst or URL, like
+//! `--sample-folder-path`) and the sample name format are read from the `KSYNTH_SAMPLE_FOLDER`
+//! and `KSYNTH_SAMPLE_FORMAT` environment variables when a plugin instance is first set up, and
+//! saved with the DAW project from then on. Without a folder the built-in piano plays.
+
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    sync::{Arc, RwLock},
+};
+
+use ksynth_core::{Channel, sample::Sample};
+use nih_plug::prelude::*;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    builtin_samples::{BuiltinOptions, builtin_drum_kit, builtin_samples, load_builtin_drum_kits},
+    multi_synth::MultiSynth,
+    predefined_drum_samples::{DrumKitStyle, VelocityLayer},
+    sample_pack::SamplePack,
+    synth_event::SynthEvent,
+};
+
+const NUM_CHANNEL: u16 = 2;
+const MAX_POLYPHONY: u32 = 1024;
+const DEFAULT_SAMPLE_FORMAT: &str = "{key}.wav";
+
+#[derive(Params)]
+struct KSynthParams {
+    #[id = "gain"]
+    gain: FloatParam,
+
+    #[persist = "sample-folder"]
+    sample_folder: RwLock<Option<String>>,
+
+    #[persist = "sample-format"]
+    sample_format: RwLock<Option<String>>,
+}
+
+impl Default for KSynthParams {
+    fn default() -> Self {
+        KSynthParams {
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 12.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            sample_folder: RwLock::new(std::env::var("KSYNTH_SAMPLE_FOLDER").ok()),
+            sample_format: RwLock::new(std::env::var("KSYNTH_SAMPLE_FORMAT").ok()),
+        }
+    }
+}
+
+pub struct KSynthPlugin {
+    params: Arc<KSynthParams>,
+    synth: Option<MultiSynth>,
+    /// Interleaved output of a block before it's split into the host's channels
+    buffer: Vec<f32>,
+}
+
+impl Default for KSynthPlugin {
+    fn default() -> Self {
+        KSynthPlugin {
+            params: Arc::new(KSynthParams::default()),
+            synth: None,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+/// Melodic samples of a sample folder, `None` if it can't be opened or has no matching samples
+fn load_folder_samples(folder: &str, format: &str) -> Option<HashMap<u8, Sample>> {
+    let sample_pack = match SamplePack::open(folder) {
+        Ok(sample_pack) => sample_pack,
+        Err(e) => {
+            nih_error!("Failed to open sample pack {}: {}", folder, e);
+            return None;
+        }
+    };
+    let samples: HashMap<u8, Sample> = (0u8..128)
+        .into_par_iter()
+        .filter_map(|key| {
+            let source =
+                sample_pack.load_sample(&format.replace("{key}", &key.to_string()), key)?;
+            let frequency = source.frequency;
+            Some((key, source.into_sample(frequency)))
+        })
+        .collect();
+    if samples.is_empty() {
+        nih_error!("No samples matching `{}` found in {}", format, folder);
+        return None;
+    }
+    Some(samples)
+}
+
+impl Plugin for KSynthPlugin {
+    const NAME: &'static str = "KSynth";
+    const VENDOR: &'static str = "kazukazu123123";
+    const URL: &'static str = "https://github.com/kazukazu123123/ksynth-midi-renderer";
+    const EMAIL: &'static str = "";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: None,
+        main_output_channels: NonZeroU32::new(NUM_CHANNEL as u32),
+        ..AudioIOLayout::const_default()
+    }];
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    /// Builds the synth for the host's sample rate, loading the samples can take a while
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let sample_rate = buffer_config.sample_rate as u32;
+        let Ok(num_channel) = Channel::try_from(NUM_CHANNEL) else {
+            return false;
+        };
+        let folder = self.params.sample_folder.read().unwrap().clone();
+        let format = self.params.sample_format.read().unwrap().clone();
+        let folder_samples = folder.as_deref().and_then(|folder| {
+            load_folder_samples(folder, format.as_deref().unwrap_or(DEFAULT_SAMPLE_FORMAT))
+        });
+        let options = BuiltinOptions::new(NUM_CHANNEL);
+        let (samples_map, drum_kit) = match folder_samples {
+            Some(samples) => {
+                let drum_kit = builtin_drum_kit(
+                    &options,
+                    sample_rate,
+                    DrumKitStyle::Standard,
+                    VelocityLayer::Normal,
+                );
+                (samples, drum_kit)
+            }
+            None => builtin_samples(&options, sample_rate),
+        };
+        let mut synth = MultiSynth::new(
+            sample_rate,
+            num_channel,
+            MAX_POLYPHONY,
+            (sample_rate as f64 * 0.1) as u64,
+            Arc::new(RwLock::new(samples_map)),
+            Some(drum_kit),
+            num_cpus::get(),
+        );
+        load_builtin_drum_kits(&mut synth, &options, sample_rate, &DrumKitStyle::ALL);
+        self.synth = Some(synth);
+        self.buffer = vec![0.0; buffer_config.max_buffer_size as usize * NUM_CHANNEL as usize];
+        true
+    }
+
+    /// Silences every note, e.g. when the host stops
+    fn reset(&mut self) {
+        if let Some(synth) = &mut self.synth {
+            for channel in 0..16 {
+                // All sound off
+                synth.queue_midi_cmd(0xB0 | channel | (120 << 8));
+            }
+        }
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let Some(synth) = &mut self.synth else {
+            return ProcessStatus::Error("not initialized");
+        };
+        while let Some(event) = context.next_event() {
+            let timing = event.timing() as usize;
+            if let Some(MidiResult::Basic([status, data1, data2])) = event.as_midi()
+                && let Some(event) = SynthEvent::from_midi1(
+                    status as u32 | (data1 as u32) << 8 | (data2 as u32) << 16,
+                )
+            {
+                synth.schedule_event(timing, event);
+            }
+        }
+
+        let channels = NUM_CHANNEL as usize;
+        let output = &mut self.buffer[..buffer.samples() * channels];
+        output.fill(0.0);
+        synth.fill_buffer_scheduled(output, channels);
+        for (frame, mut samples) in output.chunks_exact(channels).zip(buffer.iter_samples()) {
+            let gain = self.params.gain.smoothed.next();
+            for (sample, &value) in samples.iter_mut().zip(frame) {
+                *sample = value * gain;
+            }
+        }
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for KSynthPlugin {
+    const CLAP_ID: &'static str = "com.kazukazu123123.ksynth";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Sample based synth for black MIDI");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Sampler,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for KSynthPlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"KSynthMidiRender";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[
+        Vst3SubCategory::Instrument,
+        Vst3SubCategory::Synth,
+        Vst3SubCategory::Sampler,
+        Vst3SubCategory::Stereo,
+    ];
+}
+
+nih_export_clap!(KSynthPlugin);
+nih_export_vst3!(KSynthPlugin);
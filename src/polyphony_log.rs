@@ -0,0 +1,99 @@
+//! `--polyphony-csv`: the voices playing over the render, sampled at a fixed interval of
+//! rendered audio, to graph the voice demand and pick a `--max-polyphony`. Times are seconds
+//! from the first rendered frame, before leading silence trimming.
+
+use std::{io::Write, path::Path};
+
+use serde_json::json;
+
+struct PolyphonySample {
+    frame: u64,
+    /// Voices playing at the sample
+    voices: u32,
+    /// Most voices seen since the previous sample
+    peak: u32,
+}
+
+pub struct PolyphonyLog {
+    sample_rate: u32,
+    interval_frames: u64,
+    next_frame: u64,
+    peak: u32,
+    samples: Vec<PolyphonySample>,
+}
+
+impl PolyphonyLog {
+    pub fn new(sample_rate: u32, interval_frames: u64) -> Self {
+        PolyphonyLog {
+            sample_rate,
+            interval_frames: interval_frames.max(1),
+            next_frame: 0,
+            peak: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Records the voice count after `frame` output frames were rendered. Every interval passed
+    /// since the last call gets a sample with this count.
+    pub fn record(&mut self, frame: u64, voices: u32) {
+        self.peak = self.peak.max(voices);
+        while frame >= self.next_frame {
+            self.samples.push(PolyphonySample {
+                frame: self.next_frame,
+                voices,
+                peak: self.peak,
+            });
+            self.peak = voices;
+            self.next_frame += self.interval_frames;
+        }
+    }
+
+    fn time(&self, frame: u64) -> f64 {
+        frame as f64 / self.sample_rate as f64
+    }
+
+    /// Writes the samples as CSV, one per line
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "time,frame,voices,peak")?;
+        for sample in &self.samples {
+            writeln!(
+                file,
+                "{:.6},{},{},{}",
+                self.time(sample.frame),
+                sample.frame,
+                sample.voices,
+                sample.peak
+            )?;
+        }
+        file.flush()
+    }
+
+    /// Writes the samples as JSON with the limit they were rendered with
+    pub fn write_json(&self, path: impl AsRef<Path>, max_polyphony: u32) -> std::io::Result<()> {
+        let samples: Vec<_> = self
+            .samples
+            .iter()
+            .map(|sample| {
+                json!({
+                    "time": self.time(sample.frame),
+                    "frame": sample.frame,
+                    "voices": sample.voices,
+                    "peak": sample.peak,
+                })
+            })
+            .collect();
+
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(
+            file,
+            &json!({
+                "sample_rate": self.sample_rate,
+                "interval": self.time(self.interval_frames),
+                "max_polyphony": max_polyphony,
+                "samples": samples,
+            }),
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+//! Event-only pass for `--plan-polyphony`: estimates the voices a MIDI needs over time
+//! without synthesizing anything, then suggests a polyphony limit and instance count.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::synth_event::SynthEvent;
+
+/// Voices one instance is given before another thread is worth it
+const VOICES_PER_INSTANCE: u32 = 2048;
+
+pub struct PolyphonyPlan {
+    /// Most voices playing at once, counting released notes until their fade out ends
+    pub peak_voices: u32,
+    /// Time of the peak in seconds
+    pub peak_time: f64,
+    /// Voices that cover 99% of the MIDI's duration
+    pub p99_voices: u32,
+    pub max_polyphony: u32,
+    pub thread_count: usize,
+}
+
+impl PolyphonyPlan {
+    /// Simulates the voices of `events` (delta seconds, event) with notes held for
+    /// `release_secs` after their note off, as KSynth fades them out.
+    /// The suggestion is capped at `max_voices` and `max_threads`.
+    pub fn from_events(
+        events: impl Iterator<Item = (f64, Option<SynthEvent>)>,
+        release_secs: f64,
+        max_voices: u32,
+        max_threads: usize,
+    ) -> Self {
+        let mut held = vec![[false; 128]; 16];
+        let mut held_count = 0u32;
+        // Release end times, in order since every release is equally long
+        let mut releasing: VecDeque<f64> = VecDeque::new();
+        // Seconds spent at each voice count
+        let mut durations: BTreeMap<u32, f64> = BTreeMap::new();
+        let mut time = 0.0;
+        let mut last_time = 0.0;
+        let mut peak_voices = 0;
+        let mut peak_time = 0.0;
+
+        let release = |time: f64, held_count: &mut u32, releasing: &mut VecDeque<f64>| {
+            *held_count -= 1;
+            releasing.push_back(time + release_secs);
+        };
+
+        for (delta, event) in events {
+            time += delta;
+            // Account for the releases that ended since the last event one by one
+            while let Some(&end) = releasing.front()
+                && end <= time
+            {
+                let voices = held_count + releasing.len() as u32;
+                *durations.entry(voices).or_default() += end - last_time;
+                last_time = end;
+                releasing.pop_front();
+            }
+            *durations
+                .entry(held_count + releasing.len() as u32)
+                .or_default() += time - last_time;
+            last_time = time;
+
+            match event {
+                Some(SynthEvent::NoteOn {
+                    channel,
+                    key,
+                    velocity,
+                }) if velocity > 0 => {
+                    let note = &mut held[channel as usize & 0xF][key as usize & 0x7F];
+                    // A retriggered note releases the voice already playing it
+                    if *note {
+                        release(time, &mut held_count, &mut releasing);
+                    }
+                    *note = true;
+                    held_count += 1;
+                }
+                Some(SynthEvent::NoteOn { channel, key, .. })
+                | Some(SynthEvent::NoteOff { channel, key, .. }) => {
+                    let note = &mut held[channel as usize & 0xF][key as usize & 0x7F];
+                    if *note {
+                        *note = false;
+                        release(time, &mut held_count, &mut releasing);
+                    }
+                }
+                _ => {}
+            }
+
+            let voices = held_count + releasing.len() as u32;
+            if voices > peak_voices {
+                peak_voices = voices;
+                peak_time = time;
+            }
+        }
+
+        let total: f64 = durations.values().sum();
+        let mut covered = 0.0;
+        let p99_voices = durations
+            .iter()
+            .find(|&(_, &duration)| {
+                covered += duration;
+                covered >= total * 0.99
+            })
+            .map_or(0, |(&voices, _)| voices);
+
+        let max_polyphony = peak_voices.clamp(1, max_voices.max(1));
+        let thread_count =
+            (max_polyphony.div_ceil(VOICES_PER_INSTANCE) as usize).clamp(1, max_threads.max(1));
+        PolyphonyPlan {
+            peak_voices,
+            peak_time,
+            p99_voices,
+            max_polyphony,
+            thread_count,
+        }
+    }
+
+    /// Voices each instance gets with the suggested settings
+    pub fn voices_per_instance(&self) -> u32 {
+        self.max_polyphony.div_ceil(self.thread_count as u32)
+    }
+}
@@ -0,0 +1,105 @@
+//! Portamento (CC 5 time, CC 65 on/off) for `--enable-portamento`. KSynth has no glide of
+//! its own, so a new note starts bent towards the previous one and the bend is ramped back.
+
+/// Pitch bend range assumed for the glide, the General MIDI default
+const BEND_RANGE_SEMITONES: f32 = 2.0;
+/// Glide time at CC 5 = 127
+const MAX_TIME_SECS: f32 = 4.0;
+const BEND_CENTER: u16 = 8192;
+
+#[derive(Debug, Clone, Copy)]
+struct Glide {
+    /// Current offset from the played note in semitones
+    offset: f32,
+    /// Semitones per second towards the note
+    rate: f32,
+}
+
+pub struct Portamento {
+    switch: [bool; 16],
+    time: [u8; 16],
+    last_note: [Option<u8>; 16],
+    /// Pitch bend sent by the MIDI, the glide is added on top of it
+    bend: [u16; 16],
+    glides: [Option<Glide>; 16],
+}
+
+impl Default for Portamento {
+    fn default() -> Self {
+        Portamento {
+            switch: [false; 16],
+            time: [0; 16],
+            last_note: [None; 16],
+            bend: [BEND_CENTER; 16],
+            glides: [None; 16],
+        }
+    }
+}
+
+fn bend_cmd(channel: u8, value: u16) -> u32 {
+    (0xE0 | channel) as u32 | ((value as u32 & 0x7F) << 8) | (((value as u32 >> 7) & 0x7F) << 16)
+}
+
+impl Portamento {
+    fn channel_bend(&self, channel: usize) -> u16 {
+        let offset = self.glides[channel].map_or(0.0, |glide| glide.offset);
+        let value = self.bend[channel] as f32 + offset / BEND_RANGE_SEMITONES * 8192.0;
+        value.round().clamp(0.0, 16383.0) as u16
+    }
+
+    pub fn control_change(&mut self, channel: u8, controller: u8, value: u8) {
+        let channel = channel as usize & 0xF;
+        match controller {
+            5 => self.time[channel] = value,
+            65 => self.switch[channel] = value >= 64,
+            _ => {}
+        }
+    }
+
+    /// Remembers the MIDI's own bend and returns the command to send in its place
+    pub fn pitch_bend(&mut self, channel: u8, value: u16) -> u32 {
+        self.bend[channel as usize & 0xF] = value;
+        bend_cmd(channel, self.channel_bend(channel as usize & 0xF))
+    }
+
+    /// Starts a glide from the previous note, returns the bend to queue before the note on
+    pub fn note_on(&mut self, channel: u8, note: u8) -> Option<u32> {
+        let index = channel as usize & 0xF;
+        let previous = self.last_note[index].replace(note);
+        let time = MAX_TIME_SECS * (self.time[index] as f32 / 127.0).powi(2);
+        if !self.switch[index] || time <= 0.0 {
+            return None;
+        }
+        let previous = previous.filter(|&previous| previous != note)?;
+
+        let offset =
+            (previous as f32 - note as f32).clamp(-BEND_RANGE_SEMITONES, BEND_RANGE_SEMITONES);
+        self.glides[index] = Some(Glide {
+            offset,
+            rate: offset.abs() / time,
+        });
+        Some(bend_cmd(channel, self.channel_bend(index)))
+    }
+
+    pub fn is_gliding(&self) -> bool {
+        self.glides.iter().any(|glide| glide.is_some())
+    }
+
+    /// Moves the glides `seconds` forward, returns the bends for the channels that changed
+    pub fn advance(&mut self, seconds: f32) -> Vec<u32> {
+        let mut cmds = Vec::new();
+        for channel in 0..16 {
+            let Some(glide) = self.glides[channel].as_mut() else {
+                continue;
+            };
+            let step = glide.rate * seconds;
+            if glide.offset.abs() <= step {
+                self.glides[channel] = None;
+            } else {
+                glide.offset -= step * glide.offset.signum();
+            }
+            cmds.push(bend_cmd(channel as u8, self.channel_bend(channel)));
+        }
+        cmds
+    }
+}
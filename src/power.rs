@@ -0,0 +1,85 @@
+//! System sleep and battery checks of the render loop, so a laptop render doesn't rush to catch
+//! up with the speed limit after waking up and can wait for the charger with
+//! `--pause-below-battery`.
+//!
+//! Sleep is noticed after waking up: the wall clock keeps running while the system is suspended
+//! but the monotonic clock behind `Instant` doesn't (Linux, macOS), so a wall clock jump ahead
+//! of it is the time spent asleep. The battery is read from `/sys/class/power_supply` on Linux.
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// Wall clock jumps shorter than this are taken as clock adjustments rather than sleep
+const MIN_SLEEP: Duration = Duration::from_secs(2);
+/// How often the battery is read
+const BATTERY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct PowerMonitor {
+    last_instant: Instant,
+    last_system_time: SystemTime,
+    /// Battery charge in percent below which the render pauses while discharging
+    battery_threshold: Option<u8>,
+    last_battery_check: Option<Instant>,
+    battery_low: bool,
+}
+
+impl PowerMonitor {
+    pub fn new(battery_threshold: Option<u8>) -> Self {
+        PowerMonitor {
+            last_instant: Instant::now(),
+            last_system_time: SystemTime::now(),
+            battery_threshold,
+            last_battery_check: None,
+            battery_low: false,
+        }
+    }
+
+    /// How long the system slept since the last call, if it did
+    pub fn check_sleep(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        let system_time = SystemTime::now();
+        let monotonic = now.duration_since(self.last_instant);
+        let wall = system_time
+            .duration_since(self.last_system_time)
+            .unwrap_or_default();
+        self.last_instant = now;
+        self.last_system_time = system_time;
+        Some(wall.saturating_sub(monotonic)).filter(|&slept| slept >= MIN_SLEEP)
+    }
+
+    /// Whether the battery is discharging below the threshold
+    pub fn battery_low(&mut self) -> bool {
+        let Some(threshold) = self.battery_threshold else {
+            return false;
+        };
+        if self
+            .last_battery_check
+            .is_none_or(|checked| checked.elapsed() >= BATTERY_CHECK_INTERVAL)
+        {
+            self.last_battery_check = Some(Instant::now());
+            self.battery_low =
+                discharging_battery_percent().is_some_and(|percent| percent < threshold);
+        }
+        self.battery_low
+    }
+}
+
+/// Charge of the first battery in percent while it's discharging, `None` on AC power, without a
+/// battery or where it can't be read
+#[cfg(target_os = "linux")]
+fn discharging_battery_percent() -> Option<u8> {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).ok();
+    std::fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|supply| read(supply.join("type")).is_some_and(|kind| kind.trim() == "Battery"))
+        .filter(|battery| {
+            read(battery.join("status")).is_some_and(|status| status.trim() == "Discharging")
+        })
+        .and_then(|battery| read(battery.join("capacity"))?.trim().parse().ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn discharging_battery_percent() -> Option<u8> {
+    None
+}
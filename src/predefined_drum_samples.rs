@@ -63,6 +63,10 @@ fn samples_to_i16(samples: Vec<f32>) -> Vec<i16> {
 
 const SILENCE_THRESHOLD: i16 = 50; // Threshold for silence detection
 
+/// Extra built-in sounds on keys past the GM map, reachable with `--drum-remap` (e.g. `38:88`)
+pub const RIMSHOT_KEY: u8 = 88;
+pub const BRUSH_SWIRL_KEY: u8 = 89;
+
 fn trim_silence(mut samples: Vec<i16>, sample_rate: u32) -> Vec<i16> {
     // Trim trailing silence
     let mut end_index = samples.len();
@@ -84,9 +88,8 @@ fn trim_silence(mut samples: Vec<i16>, sample_rate: u32) -> Vec<i16> {
 
 // -------------------- Drum Generators -------------------- //
 
-pub fn generate_kick_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_kick_sample(sample_rate: u32, sample_count: usize, rng: &mut impl Rng) -> Vec<i16> {
     let mut float_samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
     let fundamental_freq = 35.0;
     let overtone_freq = 80.0;
 
@@ -101,7 +104,7 @@ pub fn generate_kick_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
         let fundamental = (2.0 * PI * current_fundamental * t).sin() * main_envelope * 0.8;
         let sub_bass = (2.0 * PI * current_fundamental * 0.5 * t).sin() * sub_envelope * 0.4;
         let overtone = (2.0 * PI * overtone_freq * t).sin() * main_envelope * 0.2;
-        let click_noise = filtered_noise(&mut rng, 2000.0, 5000.0, t) * click_envelope * 0.3;
+        let click_noise = filtered_noise(rng, 2000.0, 5000.0, t) * click_envelope * 0.3;
         let modulation = (2.0 * PI * 8.0 * t).sin() * 0.1 * main_envelope;
 
         let sample = (fundamental + sub_bass + overtone + click_noise) * (1.0 + modulation);
@@ -112,9 +115,12 @@ pub fn generate_kick_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
     samples_to_i16(float_samples)
 }
 
-pub fn generate_snare_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_snare_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut float_samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
     let duration = sample_count as f32 / sample_rate as f32;
 
     for i in 0..sample_count {
@@ -129,7 +135,7 @@ pub fn generate_snare_sample(sample_rate: u32, sample_count: usize) -> Vec<i16>
         }
         snare_buzz = snare_buzz / 8.0 * envelope * 0.6;
 
-        let stick_attack = filtered_noise(&mut rng, 2000.0, 8000.0, t) * (-25.0 * t).exp() * 0.4;
+        let stick_attack = filtered_noise(rng, 2000.0, 8000.0, t) * (-25.0 * t).exp() * 0.4;
         let rim_component = (2.0 * PI * 1200.0 * t).sin() * (-40.0 * t).exp() * 0.2;
         let shell_resonance = (2.0 * PI * 250.0 * t).sin() * envelope * 0.1;
 
@@ -142,9 +148,12 @@ pub fn generate_snare_sample(sample_rate: u32, sample_count: usize) -> Vec<i16>
     samples_to_i16(float_samples)
 }
 
-pub fn generate_hihat_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_hihat_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut float_samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
     let freqs = [300.0, 450.0, 680.0, 920.0, 1200.0, 1600.0];
 
     for i in 0..sample_count {
@@ -158,9 +167,8 @@ pub fn generate_hihat_sample(sample_rate: u32, sample_count: usize) -> Vec<i16>
             sample += (2.0 * PI * freq * freq_mod * t).sin() * harmonic_envelope / (idx + 1) as f32;
         }
 
-        let sizzle = filtered_noise(&mut rng, 6000.0, 12000.0, t) * envelope * 0.3;
-        let attack_transient =
-            filtered_noise(&mut rng, 8000.0, 15000.0, t) * (-100.0 * t).exp() * 0.4;
+        let sizzle = filtered_noise(rng, 6000.0, 12000.0, t) * envelope * 0.3;
+        let attack_transient = filtered_noise(rng, 8000.0, 15000.0, t) * (-100.0 * t).exp() * 0.4;
         float_samples.push((sample * 0.6 + sizzle + attack_transient) * envelope);
     }
 
@@ -168,9 +176,12 @@ pub fn generate_hihat_sample(sample_rate: u32, sample_count: usize) -> Vec<i16>
     samples_to_i16(float_samples)
 }
 
-pub fn generate_ride_cymbal_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_ride_cymbal_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
     let bell_freq = 2000.0;
     let body_freq = 400.0;
 
@@ -187,7 +198,7 @@ pub fn generate_ride_cymbal_sample(sample_rate: u32, sample_count: usize) -> Vec
             body_sample += (2.0 * PI * freq * t).sin() / harmonic as f32;
         }
         body_sample *= body_envelope * 0.3;
-        let stick_attack = filtered_noise(&mut rng, 3000.0, 8000.0, t) * (-30.0 * t).exp() * 0.2;
+        let stick_attack = filtered_noise(rng, 3000.0, 8000.0, t) * (-30.0 * t).exp() * 0.2;
         let sample = bell_fundamental + bell_harmonic + body_sample + stick_attack;
         samples.push((sample * i16::MAX as f32).clamp(-i16::MAX as f32, i16::MAX as f32) as i16);
     }
@@ -195,9 +206,12 @@ pub fn generate_ride_cymbal_sample(sample_rate: u32, sample_count: usize) -> Vec
     trim_silence(samples, sample_rate)
 }
 
-pub fn generate_hand_clap_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_hand_clap_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
 
     for i in 0..sample_count {
         let t = i as f32 / sample_rate as f32;
@@ -217,23 +231,22 @@ pub fn generate_hand_clap_sample(sample_rate: u32, sample_count: usize) -> Vec<i
                 let clap_t = t - clap_time;
                 let clap_envelope = (-30.0 * decay_rate * clap_t).exp();
 
-                let attack_transient = filtered_noise(&mut rng, 2000.0, 8000.0, clap_t)
-                    * (-150.0 * clap_t).exp()
-                    * 0.6;
+                let attack_transient =
+                    filtered_noise(rng, 2000.0, 8000.0, clap_t) * (-150.0 * clap_t).exp() * 0.6;
                 let palm_resonance =
-                    filtered_noise(&mut rng, 800.0, 2500.0, clap_t) * clap_envelope * 0.7;
+                    filtered_noise(rng, 800.0, 2500.0, clap_t) * clap_envelope * 0.7;
                 let body_thump =
-                    filtered_noise(&mut rng, 200.0, 600.0, clap_t) * (-8.0 * clap_t).exp() * 0.3;
+                    filtered_noise(rng, 200.0, 600.0, clap_t) * (-8.0 * clap_t).exp() * 0.3;
                 let air_pop = (2.0 * PI * 1200.0 * clap_t).sin() * (-60.0 * clap_t).exp() * 0.4;
                 let finger_slap =
-                    filtered_noise(&mut rng, 3000.0, 6000.0, clap_t) * (-80.0 * clap_t).exp() * 0.5;
+                    filtered_noise(rng, 3000.0, 6000.0, clap_t) * (-80.0 * clap_t).exp() * 0.5;
 
                 let reflection_delay_samples = (0.0008 * sample_rate as f32) as usize;
                 let reflection_component = if i >= reflection_delay_samples {
                     let reflected_t =
                         clap_t - (reflection_delay_samples as f32 / sample_rate as f32);
                     if reflected_t >= 0.0 {
-                        filtered_noise(&mut rng, 1000.0, 4000.0, reflected_t)
+                        filtered_noise(rng, 1000.0, 4000.0, reflected_t)
                             * (-40.0 * reflected_t).exp()
                             * 0.15
                     } else {
@@ -265,9 +278,12 @@ pub fn generate_hand_clap_sample(sample_rate: u32, sample_count: usize) -> Vec<i
     trim_silence(samples, sample_rate)
 }
 
-pub fn generate_acoustic_bass_drum_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_acoustic_bass_drum_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut float_samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
     let fundamental_freq = 40.0; // Slightly higher fundamental for acoustic feel
     let overtone_freq = 90.0; // Slightly higher overtone
 
@@ -282,7 +298,7 @@ pub fn generate_acoustic_bass_drum_sample(sample_rate: u32, sample_count: usize)
         let fundamental = (2.0 * PI * current_fundamental * t).sin() * main_envelope * 0.9; // Stronger fundamental
         let sub_bass = (2.0 * PI * current_fundamental * 0.5 * t).sin() * sub_envelope * 0.5; // Stronger sub
         let overtone = (2.0 * PI * overtone_freq * t).sin() * main_envelope * 0.3; // More prominent overtone
-        let click_noise = filtered_noise(&mut rng, 1500.0, 4000.0, t) * click_envelope * 0.2; // Softer, lower frequency click
+        let click_noise = filtered_noise(rng, 1500.0, 4000.0, t) * click_envelope * 0.2; // Softer, lower frequency click
         let modulation = (2.0 * PI * 6.0 * t).sin() * 0.05 * main_envelope; // Less modulation
 
         let sample = (fundamental + sub_bass + overtone + click_noise) * (1.0 + modulation);
@@ -293,16 +309,19 @@ pub fn generate_acoustic_bass_drum_sample(sample_rate: u32, sample_count: usize)
     samples_to_i16(float_samples)
 }
 
-pub fn generate_side_stick_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_side_stick_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut float_samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
     let duration = sample_count as f32 / sample_rate as f32;
 
     for i in 0..sample_count {
         let t = i as f32 / sample_rate as f32;
         let envelope = adsr_envelope(t, 0.001, 0.02, 0.0, 0.05, duration); // Very short, sharp envelope
 
-        let wood_click = filtered_noise(&mut rng, 3000.0, 8000.0, t) * (-100.0 * t).exp() * 0.8; // Sharp, high-frequency click
+        let wood_click = filtered_noise(rng, 3000.0, 8000.0, t) * (-100.0 * t).exp() * 0.8; // Sharp, high-frequency click
         let rim_resonance = (2.0 * PI * 800.0 * t).sin() * (-50.0 * t).exp() * 0.4; // Resonant rim sound
         let shell_thump = (2.0 * PI * 150.0 * t).sin() * (-30.0 * t).exp() * 0.2; // Low thump from shell
 
@@ -314,9 +333,12 @@ pub fn generate_side_stick_sample(sample_rate: u32, sample_count: usize) -> Vec<
     samples_to_i16(float_samples)
 }
 
-pub fn generate_electric_snare_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_electric_snare_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut float_samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
     let duration = sample_count as f32 / sample_rate as f32;
 
     for i in 0..sample_count {
@@ -324,8 +346,8 @@ pub fn generate_electric_snare_sample(sample_rate: u32, sample_count: usize) ->
         let envelope = adsr_envelope(t, 0.001, 0.08, 0.2, 0.1, duration); // Slightly longer decay for electronic feel
 
         let body_tone = (2.0 * PI * 180.0 * t).sin() * envelope * 0.5; // Lower fundamental tone
-        let noise_component = filtered_noise(&mut rng, 500.0, 5000.0, t) * envelope * 0.7; // Broader noise spectrum
-        let snap_attack = filtered_noise(&mut rng, 4000.0, 10000.0, t) * (-30.0 * t).exp() * 0.6; // Sharp, high-frequency attack
+        let noise_component = filtered_noise(rng, 500.0, 5000.0, t) * envelope * 0.7; // Broader noise spectrum
+        let snap_attack = filtered_noise(rng, 4000.0, 10000.0, t) * (-30.0 * t).exp() * 0.6; // Sharp, high-frequency attack
 
         let sample = (body_tone + noise_component + snap_attack).tanh();
         float_samples.push(sample);
@@ -335,9 +357,12 @@ pub fn generate_electric_snare_sample(sample_rate: u32, sample_count: usize) ->
     samples_to_i16(float_samples)
 }
 
-pub fn generate_pedal_hihat_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_pedal_hihat_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut float_samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
     let freqs = [200.0, 300.0, 450.0, 600.0, 800.0]; // Lower frequencies for closed hi-hat
 
     for i in 0..sample_count {
@@ -350,7 +375,7 @@ pub fn generate_pedal_hihat_sample(sample_rate: u32, sample_count: usize) -> Vec
             sample += (2.0 * PI * freq * t).sin() * harmonic_envelope / (idx + 1) as f32;
         }
 
-        let click_noise = filtered_noise(&mut rng, 1000.0, 5000.0, t) * (-80.0 * t).exp() * 0.4; // Sharp click from pedal
+        let click_noise = filtered_noise(rng, 1000.0, 5000.0, t) * (-80.0 * t).exp() * 0.4; // Sharp click from pedal
         float_samples.push((sample * 0.7 + click_noise) * envelope);
     }
 
@@ -358,9 +383,12 @@ pub fn generate_pedal_hihat_sample(sample_rate: u32, sample_count: usize) -> Vec
     samples_to_i16(float_samples)
 }
 
-pub fn generate_crash_cymbal_sample(sample_rate: u32, sample_count: usize) -> Vec<i16> {
+pub fn generate_crash_cymbal_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut float_samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
 
     let freqs = [300.0, 500.0, 800.0, 1200.0, 1800.0, 2500.0]; // 金属的ハーモニクス
 
@@ -375,7 +403,7 @@ pub fn generate_crash_cymbal_sample(sample_rate: u32, sample_count: usize) -> Ve
         }
 
         // 高周波シズルノイズ
-        let sizzle = filtered_noise(&mut rng, 5000.0, 20000.0, t) * (-2.5 * t).exp() * 0.5;
+        let sizzle = filtered_noise(rng, 5000.0, 20000.0, t) * (-2.5 * t).exp() * 0.5;
 
         let sample = (harmonics * 0.6 + sizzle).tanh();
         float_samples.push(sample);
@@ -384,3 +412,930 @@ pub fn generate_crash_cymbal_sample(sample_rate: u32, sample_count: usize) -> Ve
     normalize_samples(&mut float_samples);
     samples_to_i16(float_samples)
 }
+
+// Membrane of a tom: a sine tuned to `freq` that bends down from a third higher as the head
+// relaxes, the skin overtone and a noisy stick attack
+fn generate_tom_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    freq: f32,
+    decay: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let pitch_bend = (-20.0 * t).exp();
+        let current_freq = freq * (1.0 + 0.25 * pitch_bend);
+        let main_envelope = (-decay * t).exp();
+        let overtone_envelope = (-decay * 2.5 * t).exp();
+
+        let membrane = (2.0 * PI * current_freq * t).sin() * main_envelope * 0.9;
+        let overtone = (2.0 * PI * current_freq * 1.59 * t).sin() * overtone_envelope * 0.25; // First circular mode of the head
+        let skin_noise = filtered_noise(rng, 200.0, 1200.0, t) * overtone_envelope * 0.15;
+        let stick_attack = filtered_noise(rng, 2000.0, 6000.0, t) * (-90.0 * t).exp() * 0.3;
+
+        let sample = membrane + overtone + skin_noise + stick_attack;
+        float_samples.push(sample.tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+pub fn generate_low_floor_tom_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    generate_tom_sample(sample_rate, sample_count, rng, 82.0, 4.5)
+}
+
+pub fn generate_high_floor_tom_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    generate_tom_sample(sample_rate, sample_count, rng, 98.0, 5.0)
+}
+
+pub fn generate_low_tom_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    generate_tom_sample(sample_rate, sample_count, rng, 116.0, 5.5)
+}
+
+pub fn generate_mid_tom_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    generate_tom_sample(sample_rate, sample_count, rng, 138.0, 6.0)
+}
+
+pub fn generate_high_tom_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    generate_tom_sample(sample_rate, sample_count, rng, 174.0, 7.0)
+}
+
+// Hand drum membrane: a short pitch bend from the palm hitting the head, `decay` sets how long it
+// rings and `slap` the level of the bright finger slap on top
+fn generate_hand_drum_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    freq: f32,
+    decay: f32,
+    slap: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let pitch_bend = (-40.0 * t).exp();
+        let current_freq = freq * (1.0 + 0.1 * pitch_bend);
+        let envelope = (-decay * t).exp();
+
+        let membrane = (2.0 * PI * current_freq * t).sin() * envelope * 0.8;
+        let overtone = (2.0 * PI * current_freq * 2.3 * t).sin() * (-decay * 2.0 * t).exp() * 0.2;
+        let palm = filtered_noise(rng, 300.0, 1500.0, t) * (-60.0 * t).exp() * 0.3;
+        let finger_slap = filtered_noise(rng, 2000.0, 7000.0, t) * (-120.0 * t).exp() * slap;
+
+        let sample = membrane + overtone + palm + finger_slap;
+        float_samples.push(sample.tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+/// Strokes on a conga head
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CongaStroke {
+    /// Fingers bounce off and the head rings
+    Open,
+    /// Fingers stay on the head and damp it
+    Mute,
+    /// Cupped hand snapping on the edge, mostly crack and little tone. The GM map has no key for
+    /// it.
+    Slap,
+}
+
+pub fn generate_conga_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    freq: f32,
+    stroke: CongaStroke,
+) -> Vec<i16> {
+    let (decay, slap) = match stroke {
+        CongaStroke::Open => (9.0, 0.2),
+        CongaStroke::Mute => (35.0, 0.3),
+        CongaStroke::Slap => (40.0, 1.0),
+    };
+    generate_hand_drum_sample(sample_rate, sample_count, rng, freq, decay, slap)
+}
+
+pub fn generate_bongo_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    freq: f32,
+) -> Vec<i16> {
+    generate_hand_drum_sample(sample_rate, sample_count, rng, freq, 14.0, 0.4)
+}
+
+pub fn generate_timbale_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    freq: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    // Inharmonic modes of the thin metal shell
+    let partials = [(1.0, 0.7), (1.72, 0.3), (2.46, 0.2), (3.61, 0.12)];
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let mut shell = 0.0;
+        for (idx, &(ratio, level)) in partials.iter().enumerate() {
+            let envelope = (-(6.0 + idx as f32 * 3.0) * t).exp();
+            shell += (2.0 * PI * freq * ratio * t).sin() * envelope * level;
+        }
+        let stick_attack = filtered_noise(rng, 3000.0, 9000.0, t) * (-70.0 * t).exp() * 0.4;
+        let head = filtered_noise(rng, 400.0, 2000.0, t) * (-20.0 * t).exp() * 0.2;
+
+        let sample = shell + stick_attack + head;
+        float_samples.push(sample.tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+pub fn generate_agogo_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    freq: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    // Partials of a small cone bell
+    let partials = [(1.0, 0.6), (2.76, 0.3), (5.4, 0.15)];
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let mut bell = 0.0;
+        for (idx, &(ratio, level)) in partials.iter().enumerate() {
+            let envelope = (-(5.0 + idx as f32 * 6.0) * t).exp();
+            bell += (2.0 * PI * freq * ratio * t).sin() * envelope * level;
+        }
+        let stick_attack = filtered_noise(rng, 4000.0, 10000.0, t) * (-150.0 * t).exp() * 0.3;
+
+        float_samples.push((bell + stick_attack).tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+pub fn generate_cabasa_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        // Beads rolled against the metal surface, a short swell and fade
+        let envelope = (t / 0.01).min(1.0) * (-25.0 * t).exp();
+        let beads = filtered_noise(rng, 4000.0, 12000.0, t) * envelope;
+        float_samples.push(beads);
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+pub fn generate_maracas_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let envelope = (t / 0.004).min(1.0) * (-45.0 * t).exp();
+        let seeds = filtered_noise(rng, 5000.0, 14000.0, t) * envelope;
+        float_samples.push(seeds);
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+/// Guiro scrape lasting `scrape_secs`, the stick clicking over the ridges about 40 times a
+/// second
+pub fn generate_guiro_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    scrape_secs: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let ridge_rate = 40.0;
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let scrape = if t < scrape_secs {
+            1.0
+        } else {
+            (-60.0 * (t - scrape_secs)).exp()
+        };
+        let ridge_phase = (t * ridge_rate).fract();
+        let ridge_click = (-ridge_phase * 12.0).exp();
+        let wood = filtered_noise(rng, 1500.0, 5000.0, t) * ridge_click * scrape;
+        let body = (2.0 * PI * 700.0 * t).sin() * ridge_click * scrape * 0.2;
+        float_samples.push((wood + body).tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    trim_silence(samples_to_i16(float_samples), sample_rate)
+}
+
+/// Hard wood struck against wood: claves and the wood blocks
+pub fn generate_claves_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    freq: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let tone = (2.0 * PI * freq * t).sin() * (-25.0 * t).exp() * 0.8;
+        let overtone = (2.0 * PI * freq * 2.7 * t).sin() * (-60.0 * t).exp() * 0.2;
+        let click = filtered_noise(rng, 3000.0, 9000.0, t) * (-300.0 * t).exp() * 0.3;
+        float_samples.push((tone + overtone + click).tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+/// Samba whistle held for `length_secs`
+pub fn generate_whistle_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    length_secs: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let duration = sample_count as f32 / sample_rate as f32;
+    let mut phase = 0.0;
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let envelope = adsr_envelope(t, 0.01, 0.02, 0.8, 0.03, length_secs.min(duration));
+        // The pea rattling in the chamber
+        let trill = 1.0 + 0.02 * (2.0 * PI * 35.0 * t).sin();
+        phase += 2.0 * PI * 2300.0 * trill / sample_rate as f32;
+        let tone = phase.sin() * 0.8;
+        let breath = filtered_noise(rng, 2000.0, 6000.0, t) * 0.15;
+        float_samples.push((tone + breath) * envelope);
+    }
+
+    normalize_samples(&mut float_samples);
+    trim_silence(samples_to_i16(float_samples), sample_rate)
+}
+
+/// Cuica friction drum, the pitch glides from `start_freq` to `end_freq` as the stick is rubbed
+pub fn generate_cuica_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    start_freq: f32,
+    end_freq: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let mut phase = 0.0;
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let glide = (t / 0.12).min(1.0);
+        let freq = start_freq + (end_freq - start_freq) * glide;
+        phase += 2.0 * PI * freq / sample_rate as f32;
+        let envelope = (t / 0.005).min(1.0) * (-12.0 * t).exp();
+        let tone = (phase.sin() + (phase * 2.0).sin() * 0.3) * envelope;
+        let friction = filtered_noise(rng, 500.0, 2000.0, t) * envelope * 0.1;
+        float_samples.push((tone + friction).tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    trim_silence(samples_to_i16(float_samples), sample_rate)
+}
+
+// Sum of decaying inharmonic partials, `(frequency, level, decay)` each
+fn metallic_partials(partials: &[(f32, f32, f32)], t: f32) -> f32 {
+    partials
+        .iter()
+        .map(|&(freq, level, decay)| (2.0 * PI * freq * t).sin() * level * (-decay * t).exp())
+        .sum()
+}
+
+pub fn generate_tambourine_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    // Small jingles, each pair rings on its own partials
+    let jingles = [
+        (5400.0, 0.3, 18.0),
+        (7100.0, 0.25, 22.0),
+        (8300.0, 0.2, 25.0),
+        (9700.0, 0.15, 30.0),
+        (11800.0, 0.1, 35.0),
+    ];
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        // The jingles hit each other a few times after the stroke
+        let rattle = 0.6 + 0.4 * (2.0 * PI * 28.0 * t).sin().abs();
+        let ring = metallic_partials(&jingles, t) * rattle;
+        let shimmer = filtered_noise(rng, 6000.0, 14000.0, t) * (-14.0 * t).exp() * rattle * 0.5;
+        let head = (2.0 * PI * 250.0 * t).sin() * (-50.0 * t).exp() * 0.2;
+        float_samples.push((ring + shimmer + head).tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+pub fn generate_cowbell_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    // Two strong modes a little over a fifth apart give the cowbell its clank
+    let partials = [
+        (562.0, 0.6, 9.0),
+        (845.0, 0.5, 11.0),
+        (1480.0, 0.2, 20.0),
+        (2210.0, 0.1, 30.0),
+    ];
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let bell = metallic_partials(&partials, t);
+        let stick_attack = filtered_noise(rng, 2000.0, 7000.0, t) * (-120.0 * t).exp() * 0.3;
+        float_samples.push((bell * 1.5 + stick_attack).tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+pub fn generate_vibraslap_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let partials = [
+        (1250.0, 0.4, 4.0),
+        (2070.0, 0.3, 5.0),
+        (3320.0, 0.2, 6.0),
+        (4650.0, 0.1, 8.0),
+    ];
+    let rattle_rate = 30.0;
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        // The rivets in the box rattle against the wood, slowing as the arm settles
+        let rattle_phase = (t * rattle_rate * (1.0 - 0.2 * t).max(0.5)).fract();
+        let rattle = (-rattle_phase * 8.0).exp();
+        let rivets = metallic_partials(&partials, t) * rattle;
+        let wood = filtered_noise(rng, 800.0, 3000.0, t) * rattle * (-4.0 * t).exp() * 0.3;
+        float_samples.push((rivets + wood).tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    trim_silence(samples_to_i16(float_samples), sample_rate)
+}
+
+/// Triangle, a `muted` one is held by the hand and only rings briefly
+pub fn generate_triangle_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    muted: bool,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let damping = if muted { 30.0 } else { 1.0 };
+    // Bending the bar spreads the modes of a thin rod
+    let partials = [
+        (4400.0, 0.5, 1.5 * damping),
+        (6050.0, 0.25, 2.0 * damping),
+        (8750.0, 0.2, 2.5 * damping),
+        (10900.0, 0.1, 3.0 * damping),
+    ];
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let ring = metallic_partials(&partials, t);
+        let beater = filtered_noise(rng, 6000.0, 12000.0, t) * (-200.0 * t).exp() * 0.2;
+        float_samples.push(ring + beater);
+    }
+
+    normalize_samples(&mut float_samples);
+    trim_silence(samples_to_i16(float_samples), sample_rate)
+}
+
+// Cymbal body from `freqs`, `decay` is the decay rate of the lowest partial and how much faster
+// each one above decays. A sizzle in `sizzle_band` and `drive` of saturation (1 is clean) on top
+fn generate_cymbal_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+    freqs: &[f32],
+    decay: (f32, f32),
+    sizzle_band: (f32, f32),
+    drive: f32,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let mut harmonics = 0.0;
+        for (idx, &freq) in freqs.iter().enumerate() {
+            let env = (-(decay.0 + idx as f32 * decay.1) * t).exp();
+            harmonics += (2.0 * PI * freq * t).sin() * env / (idx + 1) as f32;
+        }
+        let sizzle = filtered_noise(rng, sizzle_band.0, sizzle_band.1, t)
+            * (-(decay.0 * 1.2) * t).exp()
+            * 0.5;
+        let stick_attack = filtered_noise(rng, 3000.0, 9000.0, t) * (-80.0 * t).exp() * 0.3;
+
+        let sample = ((harmonics * 0.6 + sizzle) * drive).tanh() + stick_attack;
+        float_samples.push(sample);
+    }
+
+    normalize_samples(&mut float_samples);
+    trim_silence(samples_to_i16(float_samples), sample_rate)
+}
+
+/// China cymbal, the upturned edge gives a dense clashing spectrum that breaks up into trash
+pub fn generate_china_cymbal_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let freqs = [
+        340.0, 415.0, 590.0, 730.0, 960.0, 1130.0, 1470.0, 1720.0, 2280.0,
+    ];
+    generate_cymbal_sample(
+        sample_rate,
+        sample_count,
+        rng,
+        &freqs,
+        (3.5, 0.6),
+        (3000.0, 12000.0),
+        3.0,
+    )
+}
+
+/// Splash cymbal, small and thin: bright and gone within half a second
+pub fn generate_splash_cymbal_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let freqs = [820.0, 1260.0, 1930.0, 2740.0, 3610.0];
+    generate_cymbal_sample(
+        sample_rate,
+        sample_count,
+        rng,
+        &freqs,
+        (7.0, 1.0),
+        (7000.0, 20000.0),
+        1.2,
+    )
+}
+
+/// Second crash, a larger and darker one than `generate_crash_cymbal_sample`
+pub fn generate_crash_cymbal_2_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let freqs = [240.0, 410.0, 650.0, 990.0, 1420.0, 2050.0, 2870.0];
+    generate_cymbal_sample(
+        sample_rate,
+        sample_count,
+        rng,
+        &freqs,
+        (1.6, 0.35),
+        (4000.0, 15000.0),
+        1.5,
+    )
+}
+
+/// Second ride, a washier ride with less bell than `generate_ride_cymbal_sample`
+pub fn generate_ride_cymbal_2_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let freqs = [380.0, 560.0, 1710.0, 2350.0, 3120.0];
+    generate_cymbal_sample(
+        sample_rate,
+        sample_count,
+        rng,
+        &freqs,
+        (1.8, 0.8),
+        (5000.0, 12000.0),
+        1.0,
+    )
+}
+
+/// Generates the built-in sample for a GM drum key, or an empty sample for keys without a generator yet
+pub fn generate_drum_sample(key: u8, sample_rate: u32, rng: &mut impl Rng) -> Vec<i16> {
+    let drum_sample_count = (sample_rate as f32 * 2.0) as usize; // Default sample count for drums
+    match key {
+        35 => generate_acoustic_bass_drum_sample(sample_rate, drum_sample_count, rng),
+        36 => generate_kick_sample(sample_rate, drum_sample_count, rng),
+        37 => generate_side_stick_sample(sample_rate, drum_sample_count / 2, rng), // Side stick is short
+        38 => generate_snare_sample(sample_rate, drum_sample_count, rng),
+        39 => generate_hand_clap_sample(sample_rate, drum_sample_count / 2, rng), // Hand clap is short
+        40 => generate_electric_snare_sample(sample_rate, drum_sample_count, rng),
+        41 => generate_low_floor_tom_sample(sample_rate, drum_sample_count, rng),
+        42 => generate_hihat_sample(sample_rate, drum_sample_count / 2, rng), // Closed Hi-Hat
+        43 => generate_high_floor_tom_sample(sample_rate, drum_sample_count, rng),
+        44 => generate_pedal_hihat_sample(sample_rate, drum_sample_count / 2, rng), // Pedal Hi-Hat
+        45 => generate_low_tom_sample(sample_rate, drum_sample_count, rng),
+        46 => generate_hihat_sample(sample_rate, drum_sample_count, rng), // Open Hi-Hat
+        47 => generate_mid_tom_sample(sample_rate, drum_sample_count, rng), // Low-Mid Tom
+        // High-Mid Tom, the mid tom generated at a lower rate to play a whole tone higher
+        48 => generate_mid_tom_sample((sample_rate as f32 / 1.12) as u32, drum_sample_count, rng),
+        49 => generate_crash_cymbal_sample(sample_rate, drum_sample_count * 2, rng), // Crash Cymbal (longer)
+        50 => generate_high_tom_sample(sample_rate, drum_sample_count, rng),
+        51 => generate_ride_cymbal_sample(sample_rate, drum_sample_count * 3, rng), // Ride Cymbal (longer)
+        52 => generate_china_cymbal_sample(sample_rate, drum_sample_count * 2, rng),
+        54 => generate_tambourine_sample(sample_rate, drum_sample_count / 4, rng),
+        55 => generate_splash_cymbal_sample(sample_rate, drum_sample_count, rng),
+        56 => generate_cowbell_sample(sample_rate, drum_sample_count / 4, rng),
+        57 => generate_crash_cymbal_2_sample(sample_rate, drum_sample_count * 2, rng),
+        58 => generate_vibraslap_sample(sample_rate, drum_sample_count, rng),
+        59 => generate_ride_cymbal_2_sample(sample_rate, drum_sample_count * 3, rng),
+        60 => generate_bongo_sample(sample_rate, drum_sample_count / 4, rng, 420.0), // Hi Bongo
+        61 => generate_bongo_sample(sample_rate, drum_sample_count / 4, rng, 300.0), // Low Bongo
+        // Mute Hi Conga
+        62 => generate_conga_sample(
+            sample_rate,
+            drum_sample_count / 4,
+            rng,
+            330.0,
+            CongaStroke::Mute,
+        ),
+        // Open Hi Conga
+        63 => generate_conga_sample(
+            sample_rate,
+            drum_sample_count / 2,
+            rng,
+            330.0,
+            CongaStroke::Open,
+        ),
+        // Low Conga
+        64 => generate_conga_sample(
+            sample_rate,
+            drum_sample_count / 2,
+            rng,
+            220.0,
+            CongaStroke::Open,
+        ),
+        65 => generate_timbale_sample(sample_rate, drum_sample_count / 2, rng, 480.0), // High Timbale
+        66 => generate_timbale_sample(sample_rate, drum_sample_count / 2, rng, 360.0), // Low Timbale
+        67 => generate_agogo_sample(sample_rate, drum_sample_count / 2, rng, 900.0),   // High Agogo
+        68 => generate_agogo_sample(sample_rate, drum_sample_count / 2, rng, 640.0),   // Low Agogo
+        69 => generate_cabasa_sample(sample_rate, drum_sample_count / 8, rng),
+        70 => generate_maracas_sample(sample_rate, drum_sample_count / 8, rng),
+        71 => generate_whistle_sample(sample_rate, drum_sample_count / 2, rng, 0.12), // Short Whistle
+        72 => generate_whistle_sample(sample_rate, drum_sample_count / 2, rng, 0.6), // Long Whistle
+        73 => generate_guiro_sample(sample_rate, drum_sample_count / 4, rng, 0.1),   // Short Guiro
+        74 => generate_guiro_sample(sample_rate, drum_sample_count / 2, rng, 0.45),  // Long Guiro
+        75 => generate_claves_sample(sample_rate, drum_sample_count / 8, rng, 2500.0), // Claves
+        76 => generate_claves_sample(sample_rate, drum_sample_count / 8, rng, 1300.0), // Hi Wood Block
+        77 => generate_claves_sample(sample_rate, drum_sample_count / 8, rng, 950.0), // Low Wood Block
+        78 => generate_cuica_sample(sample_rate, drum_sample_count / 4, rng, 700.0, 600.0), // Mute Cuica
+        79 => generate_cuica_sample(sample_rate, drum_sample_count / 2, rng, 300.0, 420.0), // Open Cuica
+        80 => generate_triangle_sample(sample_rate, drum_sample_count / 4, rng, true), // Mute Triangle
+        81 => generate_triangle_sample(sample_rate, drum_sample_count * 2, rng, false), // Open Triangle
+        RIMSHOT_KEY => generate_rimshot_sample(sample_rate, drum_sample_count, rng),
+        BRUSH_SWIRL_KEY => generate_brush_swirl_sample(sample_rate, drum_sample_count, rng),
+        // These will need proper implementation later.
+        _ => Vec::new(),
+    }
+}
+
+/// Generator key and pitch ratio standing in for a GM drum key without a generator of its own,
+/// the closest sounding one
+fn layered_source(key: u8) -> Option<(u8, f32)> {
+    Some(match key {
+        53 => (51, 1.6), // Ride Bell
+        82 => (42, 1.1), // Shaker
+        83 => (46, 1.6), // Jingle Bell
+        84 => (51, 1.8), // Belltree
+        _ => return None,
+    })
+}
+
+/// Like `generate_drum_sample`, but the keys without a generator are synthesized by pitch
+/// shifting the closest generator
+pub fn generate_layered_drum_sample(key: u8, sample_rate: u32, rng: &mut impl Rng) -> Vec<i16> {
+    match layered_source(key) {
+        // Generating at a lower rate and playing back at `sample_rate` shifts the pitch up
+        Some((source, ratio)) => {
+            generate_drum_sample(source, (sample_rate as f32 / ratio) as u32, rng)
+        }
+        None => generate_drum_sample(key, sample_rate, rng),
+    }
+}
+
+pub fn generate_brush_snare_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        // Slow swish in, no stick attack
+        let swish = (t / 0.02).min(1.0) * (-7.0 * t).exp();
+        let wires = filtered_noise(rng, 1500.0, 7000.0, t) * swish * 0.7;
+        let head = (2.0 * PI * 190.0 * t).sin() * (-30.0 * t).exp() * 0.15;
+        float_samples.push((wires + head).tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+pub fn generate_rimshot_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let duration = sample_count as f32 / sample_rate as f32;
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let envelope = adsr_envelope(t, 0.0005, 0.06, 0.15, 0.1, duration);
+
+        // Stick hitting head and rim at once: a loud crack band-passed around the rim ring
+        let crack = filtered_noise(rng, 1800.0, 4500.0, t) * (-60.0 * t).exp() * 0.9;
+        let rim_ring = (2.0 * PI * 1650.0 * t).sin() * (-35.0 * t).exp() * 0.5;
+        let drum_head = (2.0 * PI * 210.0 * t).sin() * envelope * 0.5;
+        let snare_buzz = filtered_noise(rng, 2000.0, 7000.0, t) * envelope * 0.4;
+
+        let sample = (crack + rim_ring + drum_head + snare_buzz) * 1.5;
+        float_samples.push(sample.tanh());
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+/// Brush stirred in a circle on the snare head, a long swelling texture instead of a hit
+pub fn generate_brush_swirl_sample(
+    sample_rate: u32,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let duration = sample_count as f32 / sample_rate as f32;
+
+    for i in 0..sample_count {
+        let t = i as f32 / sample_rate as f32;
+        let envelope = adsr_envelope(t, 0.15, 0.2, 0.7, 0.4, duration);
+        // The wires brighten and darken once per turn of the brush
+        let turn = 0.5 + 0.5 * (2.0 * PI * 2.5 * t).sin();
+        let wires = filtered_noise(rng, 1200.0 + 2500.0 * turn, 6000.0, t) * envelope;
+        float_samples.push(wires * (0.7 + 0.3 * turn));
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
+
+/// Built-in drum kits, selected by program change on a drum channel like the GS kits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DrumKitStyle {
+    Standard,
+    Room,
+    Power,
+    Electronic,
+    Jazz,
+    Brush,
+}
+
+/// How a kit changes the standard generators
+struct KitParams {
+    /// Pitch ratio of every drum
+    pitch: f32,
+    /// Time constant of an extra exponential decay in seconds, `None` keeps the natural decay
+    decay: Option<f32>,
+    /// Saturation drive, 1 is clean
+    drive: f32,
+    /// Level of the room reflections added to the tail
+    room: f32,
+}
+
+impl DrumKitStyle {
+    pub const ALL: [DrumKitStyle; 6] = [
+        DrumKitStyle::Standard,
+        DrumKitStyle::Room,
+        DrumKitStyle::Power,
+        DrumKitStyle::Electronic,
+        DrumKitStyle::Jazz,
+        DrumKitStyle::Brush,
+    ];
+
+    /// Kit of a GS/GM2 drum program, the programs up to the next kit are its variations and the
+    /// kits that aren't built in play the standard kit
+    pub fn from_program(program: u8) -> Self {
+        match program / 8 {
+            1 => DrumKitStyle::Room,
+            2 => DrumKitStyle::Power,
+            3 => DrumKitStyle::Electronic,
+            4 => DrumKitStyle::Jazz,
+            5 => DrumKitStyle::Brush,
+            _ => DrumKitStyle::Standard,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DrumKitStyle::Standard => "standard",
+            DrumKitStyle::Room => "room",
+            DrumKitStyle::Power => "power",
+            DrumKitStyle::Electronic => "electronic",
+            DrumKitStyle::Jazz => "jazz",
+            DrumKitStyle::Brush => "brush",
+        }
+    }
+
+    fn params(self) -> KitParams {
+        let (pitch, decay, drive, room) = match self {
+            DrumKitStyle::Standard => (1.0, None, 1.0, 0.0),
+            DrumKitStyle::Room => (1.0, None, 1.0, 0.35),
+            DrumKitStyle::Power => (0.9, None, 2.5, 0.15),
+            DrumKitStyle::Electronic => (1.15, Some(0.2), 3.0, 0.0),
+            DrumKitStyle::Jazz | DrumKitStyle::Brush => (1.1, Some(0.4), 1.0, 0.1),
+        };
+        KitParams {
+            pitch,
+            decay,
+            drive,
+            room,
+        }
+    }
+}
+
+/// Adds the reflections of a small room, two feedback combs, and extends the tail for them
+fn add_room(samples: &mut Vec<f32>, sample_rate: u32, amount: f32) {
+    samples.resize(samples.len() + (sample_rate as f32 * 0.6) as usize, 0.0);
+    let mut wet = vec![0.0f32; samples.len()];
+    for delay_secs in [0.0297, 0.0371] {
+        let delay = ((sample_rate as f32 * delay_secs) as usize).max(1);
+        // Comb without the direct sound
+        let mut comb = vec![0.0f32; samples.len()];
+        for i in delay..comb.len() {
+            comb[i] = (samples[i - delay] + comb[i - delay]) * 0.6;
+        }
+        for (wet, comb) in wet.iter_mut().zip(&comb) {
+            *wet += comb;
+        }
+    }
+    for (sample, wet) in samples.iter_mut().zip(&wet) {
+        *sample += wet * amount * 0.5;
+    }
+}
+
+/// Generates the sample of a GM drum key in `style`, `layered` synthesizes the missing drums like
+/// `generate_layered_drum_sample`. The standard kit is the plain generators.
+pub fn generate_kit_drum_sample(
+    key: u8,
+    style: DrumKitStyle,
+    sample_rate: u32,
+    layered: bool,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    let params = style.params();
+    // Generating at a lower rate and playing back at `sample_rate` shifts the pitch up
+    let rate = (sample_rate as f32 / params.pitch) as u32;
+    let samples = match (style, key) {
+        (DrumKitStyle::Brush, 38) => generate_brush_snare_sample(rate, rate as usize, rng),
+        // The brush swirl on the key of the GS brush kit
+        (DrumKitStyle::Brush, 40) => generate_brush_swirl_sample(rate, rate as usize * 2, rng),
+        _ if layered => generate_layered_drum_sample(key, rate, rng),
+        _ => generate_drum_sample(key, rate, rng),
+    };
+    if style == DrumKitStyle::Standard || samples.is_empty() {
+        return samples;
+    }
+
+    let mut float_samples: Vec<f32> = samples
+        .into_iter()
+        .map(|s| s as f32 / i16::MAX as f32)
+        .collect();
+    for (i, sample) in float_samples.iter_mut().enumerate() {
+        let t = i as f32 / sample_rate as f32;
+        if let Some(decay) = params.decay {
+            *sample *= (-t / decay).exp();
+        }
+        *sample = (*sample * params.drive).tanh();
+    }
+    if params.room > 0.0 {
+        add_room(&mut float_samples, sample_rate, params.room);
+    }
+
+    normalize_samples(&mut float_samples);
+    trim_silence(samples_to_i16(float_samples), sample_rate)
+}
+
+/// Velocity layer of a built-in drum, the normal layer is the plain generator output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VelocityLayer {
+    Soft,
+    Normal,
+    Hard,
+}
+
+impl VelocityLayer {
+    /// The layers of `count` (1-3) velocity layers with the highest velocity each plays,
+    /// softest first
+    pub fn layers(count: u8) -> &'static [(u8, VelocityLayer)] {
+        match count {
+            0 | 1 => &[(127, VelocityLayer::Normal)],
+            2 => &[(63, VelocityLayer::Soft), (127, VelocityLayer::Normal)],
+            _ => &[
+                (40, VelocityLayer::Soft),
+                (100, VelocityLayer::Normal),
+                (127, VelocityLayer::Hard),
+            ],
+        }
+    }
+}
+
+/// Reshapes a drum sample for `layer`: the soft layer loses the bright stick attack and noise to
+/// a low pass and a slower attack, the hard layer gets its highs boosted and saturated over the
+/// first milliseconds. The level is normalized again, velocity still sets the loudness.
+pub fn velocity_layer_sample(
+    samples: Vec<i16>,
+    layer: VelocityLayer,
+    sample_rate: u32,
+) -> Vec<i16> {
+    if layer == VelocityLayer::Normal || samples.is_empty() {
+        return samples;
+    }
+
+    let mut float_samples: Vec<f32> = samples
+        .into_iter()
+        .map(|s| s as f32 / i16::MAX as f32)
+        .collect();
+    // One pole low pass, the highs are the input minus its output
+    let cutoff = match layer {
+        VelocityLayer::Soft => 2000.0,
+        _ => 3000.0,
+    };
+    let coef = (-2.0 * PI * cutoff / sample_rate as f32).exp();
+    let mut low = 0.0;
+    for (i, sample) in float_samples.iter_mut().enumerate() {
+        let t = i as f32 / sample_rate as f32;
+        low = low * coef + *sample * (1.0 - coef);
+        *sample = match layer {
+            VelocityLayer::Soft => low * (t / 0.003).min(1.0),
+            _ => {
+                let attack = (-t / 0.015).exp();
+                let high = *sample - low;
+                ((*sample + high * 1.5 * attack) * (1.0 + attack)).tanh()
+            }
+        };
+    }
+
+    normalize_samples(&mut float_samples);
+    samples_to_i16(float_samples)
+}
@@ -1,9 +1,62 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use std::f32::consts::PI;
 
-pub fn generate_piano_sample(sample_rate: u32, freq: f32, sample_count: usize) -> Vec<i16> {
+use crate::fm::{FmPreset, fm_preset, generate_fm_sample};
+
+/// RNG for generating the built-in sample of one key. With a seed, every key gets its own
+/// deterministic stream, so the result doesn't depend on the order samples are generated in.
+pub fn sample_rng(seed: Option<u64>, key: u8, drum: bool) -> StdRng {
+    match seed {
+        Some(seed) => {
+            let stream = ((drum as u64) << 8) | key as u64;
+            StdRng::seed_from_u64(seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        }
+        None => StdRng::from_os_rng(),
+    }
+}
+
+/// How many partials the built-in piano is made of, trading startup time for realism
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuiltinQuality {
+    /// Only the strong partials
+    Fast,
+    #[default]
+    Normal,
+    /// Adds the upper partials, stretched like a real string's
+    High,
+}
+
+pub fn parse_builtin_quality(s: &str) -> Result<BuiltinQuality, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "fast" => Ok(BuiltinQuality::Fast),
+        "normal" => Ok(BuiltinQuality::Normal),
+        "high" => Ok(BuiltinQuality::High),
+        _ => Err(format!(
+            "invalid built-in quality `{}`, expected fast, normal or high",
+            s
+        )),
+    }
+}
+
+/// Parses the length of the built-in samples in seconds, 0.1 to 60
+pub fn parse_builtin_seconds(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(seconds) if (0.1..=60.0).contains(&seconds) => Ok(seconds),
+        _ => Err(format!(
+            "invalid built-in sample length `{}`, expected 0.1 to 60 seconds",
+            s
+        )),
+    }
+}
+
+pub fn generate_piano_sample(
+    sample_rate: u32,
+    freq: f32,
+    sample_count: usize,
+    quality: BuiltinQuality,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
     let mut samples = Vec::with_capacity(sample_count);
-    let mut rng = rand::rng();
 
     let mut harmonics = vec![
         (1.0, 1.0),
@@ -25,6 +78,19 @@ pub fn generate_piano_sample(sample_rate: u32, freq: f32, sample_count: usize) -
         }
     }
 
+    match quality {
+        BuiltinQuality::Fast => harmonics.retain(|&(_, amplitude)| amplitude >= 0.3),
+        BuiltinQuality::Normal => {}
+        BuiltinQuality::High => {
+            // Low notes already have partials up to the 10th
+            let first = if freq < 400.0 { 11 } else { 7 };
+            harmonics.extend((first..=16).map(|n| {
+                let n = n as f32;
+                (n * (1.0 + 0.0004 * n * n).sqrt(), 0.3 / n)
+            }));
+        }
+    }
+
     let phase_shifts: Vec<f32> = (0..harmonics.len())
         .map(|_| rng.random_range(0.0..2.0 * PI))
         .collect();
@@ -145,3 +211,321 @@ pub fn generate_piano_sample(sample_rate: u32, freq: f32, sample_count: usize) -
 
     samples
 }
+
+/// Timbre of the built-in melodic samples
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BuiltinInstrument {
+    #[default]
+    Piano,
+    /// Nylon and steel guitars, GM programs 24-31
+    Guitar,
+    /// GM program 46
+    Harp,
+    /// Acoustic and electric basses, GM programs 32-39
+    Bass,
+    /// FM preset of a GM program, see `fm_preset`
+    Fm(FmPreset),
+    /// Drawbar organ, GM programs 16-23
+    Organ,
+    /// String ensemble and pads, GM programs 40-44, 48-51 and 88-95
+    Strings,
+    /// GM programs 52-54 and 91
+    Choir,
+}
+
+impl BuiltinInstrument {
+    /// Closest built-in instrument to a GM program (0-127), the piano if there's nothing closer
+    pub fn from_program(program: u8) -> Self {
+        match program {
+            24..=31 => BuiltinInstrument::Guitar,
+            32..=39 => BuiltinInstrument::Bass,
+            46 => BuiltinInstrument::Harp,
+            16..=23 => BuiltinInstrument::Organ,
+            52..=54 | 91 => BuiltinInstrument::Choir,
+            40..=44 | 48..=51 | 88..=95 => BuiltinInstrument::Strings,
+            _ => fm_preset(program).map_or(BuiltinInstrument::Piano, BuiltinInstrument::Fm),
+        }
+    }
+}
+
+/// Parses an instrument name or a GM program number (0-127)
+pub fn parse_builtin_instrument(s: &str) -> Result<BuiltinInstrument, String> {
+    let instrument = match s.trim().to_ascii_lowercase().as_str() {
+        "piano" => BuiltinInstrument::Piano,
+        "guitar" => BuiltinInstrument::Guitar,
+        "harp" => BuiltinInstrument::Harp,
+        "bass" => BuiltinInstrument::Bass,
+        "epiano" => BuiltinInstrument::from_program(4),
+        "bells" => BuiltinInstrument::from_program(14),
+        "brass" => BuiltinInstrument::from_program(61),
+        "organ" => BuiltinInstrument::Organ,
+        "strings" => BuiltinInstrument::Strings,
+        "choir" => BuiltinInstrument::Choir,
+        program => match program.parse::<u8>() {
+            Ok(program) if program < 128 => BuiltinInstrument::from_program(program),
+            _ => {
+                return Err(format!(
+                    "invalid built-in instrument `{}`, expected piano, guitar, harp, bass, epiano, \
+                     bells, brass, organ, strings, choir or a GM program 0-127",
+                    s
+                ));
+            }
+        },
+    };
+    Ok(instrument)
+}
+
+/// Start of the sustained instruments' loop, after their attack
+const SUSTAIN_LOOP_START_SECS: f32 = 0.5;
+/// Length of the sustained instruments' loop. Every oscillator is tuned to a whole number of
+/// cycles in it, which is what makes the loop seamless: 4 seconds keep that within a few cents.
+const SUSTAIN_LOOP_SECS: f32 = 4.0;
+
+/// Loop of the built-in samples of `instrument` in frames (start, end), `None` if they play once
+pub fn builtin_loop_points(
+    instrument: BuiltinInstrument,
+    sample_rate: u32,
+) -> Option<(usize, usize)> {
+    match instrument {
+        BuiltinInstrument::Organ | BuiltinInstrument::Strings | BuiltinInstrument::Choir => {
+            let start = (SUSTAIN_LOOP_START_SECS * sample_rate as f32) as usize;
+            Some((
+                start,
+                start + (SUSTAIN_LOOP_SECS * sample_rate as f32) as usize,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Generates the built-in sample of `instrument` playing at `freq`. Sustained instruments are
+/// as long as their loop needs instead of `sample_count`.
+pub fn generate_builtin_sample(
+    instrument: BuiltinInstrument,
+    sample_rate: u32,
+    freq: f32,
+    sample_count: usize,
+    quality: BuiltinQuality,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    // Brightness of the pluck (0-1), plucking position along the string (0-0.5) and the time
+    // the string takes to decay by 60 dB
+    let (brightness, position, decay_secs) = match instrument {
+        BuiltinInstrument::Piano => {
+            return generate_piano_sample(sample_rate, freq, sample_count, quality, rng);
+        }
+        BuiltinInstrument::Fm(ref preset) => {
+            return generate_fm_sample(preset, sample_rate, freq, sample_count);
+        }
+        BuiltinInstrument::Organ | BuiltinInstrument::Strings | BuiltinInstrument::Choir => {
+            return generate_sustained_sample(instrument, sample_rate, freq);
+        }
+        BuiltinInstrument::Guitar => (0.6, 0.15, 3.0),
+        BuiltinInstrument::Harp => (0.8, 0.5, 5.0),
+        BuiltinInstrument::Bass => (0.35, 0.2, 4.0),
+    };
+    generate_plucked_sample(
+        sample_rate,
+        freq,
+        sample_count,
+        brightness,
+        position,
+        decay_secs,
+        rng,
+    )
+}
+
+/// Plucked string with the Karplus-Strong algorithm: a noise burst circulates in a delay line one
+/// period long and is low pass filtered on every pass, so the highs die away first
+fn generate_plucked_sample(
+    sample_rate: u32,
+    freq: f32,
+    sample_count: usize,
+    brightness: f32,
+    position: f32,
+    decay_secs: f32,
+    rng: &mut impl Rng,
+) -> Vec<i16> {
+    // The averaging filter in the loop delays by half a sample, an allpass tunes the fraction
+    let period = (sample_rate as f32 / freq - 0.5).max(2.0);
+    let length = period as usize;
+    let fraction = period - length as f32;
+    let allpass = (1.0 - fraction) / (1.0 + fraction);
+    // Loop gain reaching -60 dB after `decay_secs`
+    let gain = 0.001f32.powf(1.0 / (decay_secs * freq));
+
+    // Excitation: low passed noise, with the notch of the plucking position
+    let mut lowpassed = 0.0;
+    let noise: Vec<f32> = (0..length)
+        .map(|_| {
+            lowpassed += brightness * (rng.random_range(-1.0..1.0) - lowpassed);
+            lowpassed
+        })
+        .collect();
+    let offset = ((position * length as f32) as usize).max(1);
+    let mut line: Vec<f32> = (0..length)
+        .map(|i| noise[i] - if i >= offset { noise[i - offset] } else { 0.0 })
+        .collect();
+    let mean = line.iter().sum::<f32>() / length as f32;
+    for sample in &mut line {
+        *sample -= mean;
+    }
+
+    let mut float_samples = Vec::with_capacity(sample_count);
+    let mut previous = 0.0;
+    let mut allpass_input = 0.0;
+    let mut allpass_output = 0.0;
+    for i in 0..sample_count {
+        let index = i % length;
+        let current = line[index];
+        float_samples.push(current);
+
+        let averaged = 0.5 * (current + previous);
+        previous = current;
+        allpass_output = allpass * averaged + allpass_input - allpass * allpass_output;
+        allpass_input = averaged;
+        line[index] = allpass_output * gain;
+    }
+
+    let peak = float_samples
+        .iter()
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    let scale = if peak > 0.0 { 0.6 / peak } else { 0.0 };
+    float_samples
+        .into_iter()
+        .map(|sample| (sample * scale * i16::MAX as f32) as i16)
+        .collect()
+}
+
+/// Organ, strings or choir held at a constant level after the attack, to be looped by
+/// `builtin_loop_points`
+fn generate_sustained_sample(
+    instrument: BuiltinInstrument,
+    sample_rate: u32,
+    freq: f32,
+) -> Vec<i16> {
+    let (_, loop_end) = builtin_loop_points(instrument, sample_rate).unwrap_or_default();
+    let loop_frames = (SUSTAIN_LOOP_SECS * sample_rate as f32) as usize;
+    // Nearest frequency with a whole number of cycles in the loop
+    let quantize = |f: f32| {
+        (f * loop_frames as f32 / sample_rate as f32)
+            .round()
+            .max(1.0)
+            * sample_rate as f32
+            / loop_frames as f32
+    };
+    let nyquist = sample_rate as f32 / 2.0;
+
+    // Partials (ratio, level), detuned voices, vibrato depth and the attack time
+    let (partials, detune, vibrato, attack): (Vec<(f32, f32)>, &[f32], f32, f32) = match instrument
+    {
+        // 16', 8', 5 1/3', 4', 2 2/3', 2', 1 3/5' and 1' drawbars
+        BuiltinInstrument::Organ => (
+            vec![
+                (0.5, 0.5),
+                (1.0, 1.0),
+                (1.5, 0.6),
+                (2.0, 0.5),
+                (3.0, 0.3),
+                (4.0, 0.25),
+                (5.0, 0.1),
+                (8.0, 0.15),
+            ],
+            &[1.0],
+            0.0,
+            0.01,
+        ),
+        // Sawtooth rolled off towards the top
+        BuiltinInstrument::Strings => (
+            (1..=24)
+                .map(|n| (n as f32, 1.0 / n as f32 * (-(n as f32) / 12.0).exp()))
+                .collect(),
+            &[0.997, 1.0, 1.003],
+            0.003,
+            0.25,
+        ),
+        // Harmonics shaped by the formants of an open "ah"
+        _ => (
+            (1..=32)
+                .map(|n| {
+                    let harmonic = freq * n as f32;
+                    let level = [(700.0, 1.0), (1200.0, 0.6), (2600.0, 0.25)]
+                        .iter()
+                        .map(|&(formant, gain)| {
+                            gain * (-((harmonic - formant) / 250.0).powi(2)).exp()
+                        })
+                        .sum::<f32>()
+                        + 0.02 / n as f32;
+                    (n as f32, level)
+                })
+                .collect(),
+            &[0.995, 0.998, 1.002, 1.005],
+            0.004,
+            0.35,
+        ),
+    };
+    let vibrato_rate = quantize(5.5);
+    let oscillators: Vec<(f32, f32)> = detune
+        .iter()
+        .flat_map(|&voice| {
+            partials
+                .iter()
+                .map(move |&(ratio, level)| (quantize(freq * voice * ratio), level))
+        })
+        .filter(|&(partial_freq, level)| partial_freq < nyquist && level >= 0.001)
+        .collect();
+
+    let mut float_samples = Vec::with_capacity(loop_end + 1);
+    for i in 0..=loop_end {
+        let t = i as f32 / sample_rate as f32;
+        let envelope = (t / attack).min(1.0);
+        let vibrato_phase = (2.0 * PI * vibrato_rate * t).sin();
+        let mut sample = 0.0;
+        for &(partial_freq, level) in &oscillators {
+            // Cycles counted in f64, f32 loses the phase after a few seconds of high partials
+            let cycles = (partial_freq as f64 * i as f64 / sample_rate as f64).fract() as f32;
+            // Frequency modulation of the vibrato, it repeats with the loop as well
+            let phase = 2.0 * PI * cycles + partial_freq * vibrato / vibrato_rate * vibrato_phase;
+            sample += phase.sin() * level;
+        }
+        float_samples.push(sample * envelope);
+    }
+
+    let peak = float_samples
+        .iter()
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    let scale = if peak > 0.0 { 0.5 / peak } else { 0.0 };
+    float_samples
+        .into_iter()
+        .map(|sample| (sample * scale * i16::MAX as f32) as i16)
+        .collect()
+}
+
+/// Spreads a built-in piano sample across the stereo field by key like a miked piano, low keys on
+/// the left and high keys on the right. The sides run through opposite allpasses so they don't
+/// stay identical.
+pub fn stereo_piano_sample(samples: &[i16], key: u8) -> Vec<(i16, i16)> {
+    // Pan -0.6 to 0.6, constant power with the center at full level like the mono sample
+    let pan = (key as f32 - 64.0) / 64.0 * 0.6;
+    let angle = (pan + 1.0) * PI / 4.0;
+    let center = (PI / 4.0).cos();
+    let gains = [angle.cos() / center, angle.sin() / center];
+    let coefficients = [0.4, -0.4];
+
+    let mut inputs = [0.0f32; 2];
+    let mut outputs = [0.0f32; 2];
+    samples
+        .iter()
+        .map(|&sample| {
+            let sample = sample as f32;
+            let [left, right] = [0, 1].map(|side| {
+                let output =
+                    coefficients[side] * sample + inputs[side] - coefficients[side] * outputs[side];
+                inputs[side] = sample;
+                outputs[side] = output;
+                (output * gains[side]).clamp(-i16::MAX as f32, i16::MAX as f32) as i16
+            });
+            (left, right)
+        })
+        .collect()
+}
@@ -0,0 +1,50 @@
+//! `--export-processed-midi`: the MIDI commands the synth was given, after the event processing
+//! (`--fix-midi`, strumming, humanizing, `--time-range`) and the drum and program remapping,
+//! written back to a Standard MIDI file. Drum notes are on channel 10 with their remapped keys
+//! and notes of muted programs are left out.
+//!
+//! The file has 960 ticks per quarter at 120 BPM, events are placed on the tick nearest to the
+//! frame they were rendered at, counted from the first rendered frame.
+
+use std::path::Path;
+
+use crate::smf_writer::SmfWriter;
+
+const PPQ: u16 = 960;
+/// 120 BPM
+const TEMPO: u32 = 500_000;
+const TICKS_PER_SECOND: f64 = PPQ as f64 * 1_000_000.0 / TEMPO as f64;
+
+pub struct ProcessedMidiExport {
+    writer: SmfWriter,
+    /// Frames per second of the synth frames the commands are recorded at
+    render_rate: u32,
+    tick: u64,
+}
+
+impl ProcessedMidiExport {
+    pub fn create(path: impl AsRef<Path>, render_rate: u32) -> std::io::Result<Self> {
+        let mut writer = SmfWriter::create(path, PPQ)?;
+        writer.meta(0, 0x51, &TEMPO.to_be_bytes()[1..])?;
+        Ok(ProcessedMidiExport {
+            writer,
+            render_rate,
+            tick: 0,
+        })
+    }
+
+    /// Writes `(frame, command)` pairs from `MultiSynth::take_commands`
+    pub fn write(&mut self, commands: &[(u64, u32)]) -> std::io::Result<()> {
+        for &(frame, cmd) in commands {
+            let tick = (frame as f64 / self.render_rate as f64 * TICKS_PER_SECOND).round() as u64;
+            let tick = tick.max(self.tick);
+            self.writer.channel_message(tick - self.tick, cmd)?;
+            self.tick = tick;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> std::io::Result<()> {
+        self.writer.finish()
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use crate::i18n::t;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Reading MIDI events and queueing them to the synth
+    Events,
+    /// `fill_buffer`, including downsampling
+    Synthesis,
+    /// Effects, earrape mode and the limiter
+    PostProcess,
+    /// Silence trimming, fades and writing the output
+    Output,
+}
+
+impl Stage {
+    const ALL: [Stage; 4] = [
+        Stage::Events,
+        Stage::Synthesis,
+        Stage::PostProcess,
+        Stage::Output,
+    ];
+
+    fn key(&self) -> &'static str {
+        match self {
+            Stage::Events => "events",
+            Stage::Synthesis => "synthesis",
+            Stage::PostProcess => "post_process",
+            Stage::Output => "output",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Stage::Events => "Events",
+            Stage::Synthesis => "Synthesis",
+            Stage::PostProcess => "Effects/Limiter",
+            Stage::Output => "Output",
+        }
+    }
+}
+
+/// Accumulates time spent in each render stage. Recording is a no-op when disabled.
+pub struct Profiler {
+    enabled: bool,
+    totals: [Duration; 4],
+    reported: [Duration; 4],
+}
+
+impl Profiler {
+    pub fn new(enabled: bool) -> Self {
+        Profiler {
+            enabled,
+            totals: [Duration::ZERO; 4],
+            reported: [Duration::ZERO; 4],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Adds the time since `start` to `stage`
+    pub fn record(&mut self, stage: Stage, start: Instant) {
+        if self.enabled {
+            self.totals[stage as usize] += start.elapsed();
+        }
+    }
+
+    /// Logs the headless `perf` line with the time spent per stage since the previous line
+    pub fn log_perf(&mut self) {
+        let [events, synthesis, post_process, output] = std::array::from_fn(|i| {
+            let delta = self.totals[i] - self.reported[i];
+            format!("{:.1}", delta.as_secs_f64() * 1000.0)
+        });
+        info!(
+            event = "perf",
+            events_ms:% = events,
+            synthesis_ms:% = synthesis,
+            post_process_ms:% = post_process,
+            output_ms:% = output;
+            ""
+        );
+        self.reported = self.totals;
+    }
+
+    pub fn log_summary(&self) {
+        let total: Duration = self.totals.iter().sum();
+        let percent = |time: Duration| {
+            if total.is_zero() {
+                0.0
+            } else {
+                time.as_secs_f64() / total.as_secs_f64() * 100.0
+            }
+        };
+
+        info!("{}", t!("profile"));
+        info!(
+            "{:<16} {:>12} {:>7}",
+            t!("profile-stage"),
+            t!("profile-time"),
+            t!("profile-share")
+        );
+        for stage in Stage::ALL {
+            let time = self.totals[stage as usize];
+            info!(
+                event = "profile",
+                stage = stage.key(),
+                total_sec:% = format!("{:.3}", time.as_secs_f64()),
+                percent:% = format!("{:.1}", percent(time));
+                "{:<16} {:>11.3}s {:>6.1}%",
+                stage.name(),
+                time.as_secs_f64(),
+                percent(time)
+            );
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use cpal::{
+    BufferSize, SampleRate, Stream, StreamConfig,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+
+/// Realtime audio output that pulls interleaved f32 audio from a render callback
+pub struct RealtimeOutput {
+    _backend: Backend,
+    device_name: String,
+}
+
+/// Only held to keep playing until the output is dropped
+#[allow(dead_code)]
+enum Backend {
+    Cpal(Stream),
+    #[cfg(all(feature = "jack", target_os = "linux"))]
+    Jack(jack::AsyncClient<(), JackProcess>),
+}
+
+/// Audio host, device and buffer size of the output, the system defaults where `None`
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    /// cpal host by name, e.g. `ASIO` (with the `asio` feature) or `WASAPI` on Windows
+    pub host: Option<String>,
+    /// Part of the output device name
+    pub device: Option<String>,
+    /// Buffer size in frames, smaller buffers lower the latency
+    pub buffer_frames: Option<u32>,
+}
+
+/// Settings of the JACK output, which also runs on PipeWire's JACK library
+pub struct JackOptions {
+    pub client_name: String,
+    /// Ports to connect the outputs to in channel order, the physical playback ports if `None`
+    pub connect: Option<Vec<String>>,
+    /// Output silence and don't render while the JACK transport is stopped
+    pub follow_transport: bool,
+}
+
+impl RealtimeOutput {
+    pub fn start<F>(sample_rate: u32, num_channel: u16, render: F) -> Result<Self, String>
+    where
+        F: FnMut(&mut [f32]) + Send + 'static,
+    {
+        Self::start_with(&OutputOptions::default(), sample_rate, num_channel, render)
+    }
+
+    pub fn start_with<F>(
+        options: &OutputOptions,
+        sample_rate: u32,
+        num_channel: u16,
+        mut render: F,
+    ) -> Result<Self, String>
+    where
+        F: FnMut(&mut [f32]) + Send + 'static,
+    {
+        let host = match &options.host {
+            Some(name) => {
+                let hosts = cpal::available_hosts();
+                let id = hosts
+                    .iter()
+                    .find(|id| id.name().eq_ignore_ascii_case(name))
+                    .ok_or_else(|| {
+                        let available = hosts.iter().map(|id| id.name()).collect::<Vec<_>>();
+                        format!(
+                            "Unknown audio host `{}`, available: {}",
+                            name,
+                            available.join(", ")
+                        )
+                    })?;
+                cpal::host_from_id(*id)
+                    .map_err(|e| format!("Failed to open audio host {}: {}", id.name(), e))?
+            }
+            None => cpal::default_host(),
+        };
+        let device = match &options.device {
+            Some(name) => host
+                .output_devices()
+                .map_err(|e| format!("Failed to list audio output devices: {}", e))?
+                .find(|device| {
+                    device
+                        .name()
+                        .is_ok_and(|device_name| device_name.contains(name))
+                })
+                .ok_or_else(|| format!("No audio output device matches `{}`", name))?,
+            None => host
+                .default_output_device()
+                .ok_or_else(|| "No audio output device available".to_string())?,
+        };
+        let device_name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+
+        let config = StreamConfig {
+            channels: num_channel,
+            sample_rate: SampleRate(sample_rate),
+            buffer_size: options
+                .buffer_frames
+                .map_or(BufferSize::Default, BufferSize::Fixed),
+        };
+
+        let stream = device
+            .build_output_stream(
+                &config,
+                move |data: &mut [f32], _| render(data),
+                |err| eprintln!("Audio output error: {}", err),
+                None,
+            )
+            .map_err(|e| format!("Failed to open audio output stream: {}", e))?;
+        stream
+            .play()
+            .map_err(|e| format!("Failed to start audio output stream: {}", e))?;
+
+        Ok(RealtimeOutput {
+            _backend: Backend::Cpal(stream),
+            device_name,
+        })
+    }
+
+    /// Plays through a JACK client with one output port per channel. The JACK server sets the
+    /// sample rate, `sample_rate` has to match it.
+    #[cfg(all(feature = "jack", target_os = "linux"))]
+    pub fn start_jack<F>(
+        options: &JackOptions,
+        sample_rate: u32,
+        num_channel: u16,
+        render: F,
+    ) -> Result<Self, String>
+    where
+        F: FnMut(&mut [f32]) + Send + 'static,
+    {
+        let (client, _) = jack::Client::new(&options.client_name, jack::ClientOptions::empty())
+            .map_err(|e| format!("Failed to connect to the JACK server: {}", e))?;
+        let server_rate = client.sample_rate() as u32;
+        if server_rate != sample_rate {
+            return Err(format!(
+                "The JACK server runs at {} Hz, use --sample-rate {}",
+                server_rate, server_rate
+            ));
+        }
+        let ports = port_names(num_channel)
+            .iter()
+            .map(|name| client.register_port(name, jack::AudioOut::default()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to register a JACK port: {}", e))?;
+        let port_names = ports
+            .iter()
+            .map(|port| port.name())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let device_name = format!("JACK: {}", client.name());
+        let process = JackProcess {
+            buffer: vec![0.0; client.buffer_size() as usize * ports.len()],
+            ports,
+            render: Box::new(render),
+            follow_transport: options.follow_transport,
+        };
+        let client = client
+            .activate_async((), process)
+            .map_err(|e| format!("Failed to start the JACK client: {}", e))?;
+
+        // Ports can only be connected once the client is active
+        let targets = match &options.connect {
+            Some(targets) => targets.clone(),
+            None => client.as_client().ports(
+                None,
+                Some("audio"),
+                jack::PortFlags::IS_INPUT | jack::PortFlags::IS_PHYSICAL,
+            ),
+        };
+        for (source, target) in port_names.iter().zip(&targets) {
+            client
+                .as_client()
+                .connect_ports_by_name(source, target)
+                .map_err(|e| format!("Failed to connect {} to {}: {}", source, target, e))?;
+        }
+
+        Ok(RealtimeOutput {
+            _backend: Backend::Jack(client),
+            device_name,
+        })
+    }
+
+    #[cfg(not(all(feature = "jack", target_os = "linux")))]
+    pub fn start_jack<F>(
+        _options: &JackOptions,
+        _sample_rate: u32,
+        _num_channel: u16,
+        _render: F,
+    ) -> Result<Self, String>
+    where
+        F: FnMut(&mut [f32]) + Send + 'static,
+    {
+        Err("This build has no JACK output, it needs Linux and the `jack` feature".to_string())
+    }
+
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+}
+
+/// JACK port names of the channels: `out` for mono, `out_left` and `out_right` for stereo and
+/// `out_1`, `out_2`, ... otherwise
+#[cfg(all(feature = "jack", target_os = "linux"))]
+fn port_names(num_channel: u16) -> Vec<String> {
+    match num_channel {
+        1 => vec!["out".to_string()],
+        2 => vec!["out_left".to_string(), "out_right".to_string()],
+        n => (1..=n).map(|channel| format!("out_{}", channel)).collect(),
+    }
+}
+
+#[cfg(all(feature = "jack", target_os = "linux"))]
+type RenderCallback = Box<dyn FnMut(&mut [f32]) + Send>;
+
+#[cfg(all(feature = "jack", target_os = "linux"))]
+struct JackProcess {
+    ports: Vec<jack::Port<jack::AudioOut>>,
+    render: RenderCallback,
+    /// Interleaved frames of a period
+    buffer: Vec<f32>,
+    follow_transport: bool,
+}
+
+#[cfg(all(feature = "jack", target_os = "linux"))]
+impl jack::ProcessHandler for JackProcess {
+    fn process(&mut self, client: &jack::Client, scope: &jack::ProcessScope) -> jack::Control {
+        let rolling = !self.follow_transport
+            || matches!(
+                client.transport().query_state(),
+                Ok(jack::TransportState::Rolling)
+            );
+        if !rolling {
+            for port in &mut self.ports {
+                port.as_mut_slice(scope).fill(0.0);
+            }
+            return jack::Control::Continue;
+        }
+
+        let channels = self.ports.len();
+        let buffer = &mut self.buffer[..scope.n_frames() as usize * channels];
+        (self.render)(buffer);
+        for (channel, port) in self.ports.iter_mut().enumerate() {
+            let frames = buffer.chunks_exact(channels);
+            for (sample, frame) in port.as_mut_slice(scope).iter_mut().zip(frames) {
+                *sample = frame[channel];
+            }
+        }
+        jack::Control::Continue
+    }
+
+    /// Called outside of `process`, allocating is fine here
+    fn buffer_size(&mut self, _: &jack::Client, size: jack::Frames) -> jack::Control {
+        self.buffer = vec![0.0; size as usize * self.ports.len()];
+        jack::Control::Continue
+    }
+}
@@ -0,0 +1,623 @@
+//! The `serve` and `worker` subcommands: render one MIDI on several machines. The coordinator
+//! splits the MIDI into segments of `--segment-length`, hands them to the workers that
+//! connect to it and joins the returned audio into `--output`, crossfading the
+//! `--crossfade` overlap of neighboring segments. Workers render a segment with
+//! `--time-range` in their own renderer process, so the notes of its preroll are sounding at
+//! its start. A worker that disconnects has its segment rendered by another one.
+//! `--parallel-segments` splits the MIDI the same way and renders the segments at once in
+//! renderer processes on this machine, each with its own synth.
+//!
+//! The coordinator's other options are passed on to the workers, paths in them (e.g. the sample
+//! folder) have to exist on the worker machines. The bit depth, dither, fades, bookends and
+//! output segmenting are applied by the coordinator when joining, `--trim-leading-silence` is
+//! ignored.
+//!
+//! Messages are frames of a u64 little-endian length and the bytes. A job is a JSON header
+//! (`{"index": 0, "range": "0:60.5", "args": [...]}`) followed by the MIDI file, the answer a
+//! JSON header (`{"error": null}`) followed by the segment as a float WAV file.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use midi_toolkit::{
+    io::MIDIFile,
+    pipe,
+    sequence::{event::get_channels_array_statistics, to_vec},
+};
+use serde_json::{Value, json};
+
+use crate::{
+    bookends::load_bookend,
+    dither::Dither,
+    fade::Fader,
+    i18n::t,
+    job_list::{args_without, last_error_line},
+    midi_input::{TempFile, unwrap_midi_container},
+    midi2_clip::Midi2Clip,
+    output::{RIFF_SIZE_LIMIT, SegmentedWavWriter},
+    time_range::TimeRange,
+};
+
+/// Options of the coordinator that aren't passed to the workers: the split render's own, the
+/// input and output, and the ones applied when joining
+const COORDINATOR_OPTIONS: [&str; 16] = [
+    "--segment-length",
+    "--parallel-segments",
+    "--crossfade",
+    "--midi-file-path",
+    "--output",
+    "-o",
+    "--bit-depth",
+    "--dither",
+    "--segment-duration",
+    "--segment-size",
+    "--fade-in",
+    "--fade-out",
+    "--prepend",
+    "--append",
+    "--log-format",
+    "--log-file",
+];
+const COORDINATOR_FLAGS: [&str; 2] = ["--force-rf64", "--trim-leading-silence"];
+/// Set for each segment of `--parallel-segments` from the thread count of the whole render
+const THREAD_COUNT_OPTIONS: [&str; 2] = ["--thread-count", "-t"];
+
+/// Largest JSON header accepted, the MIDI and audio frames are streamed
+const MAX_HEADER_BYTES: u64 = 1024 * 1024;
+/// How often the coordinator checks for new workers and segments to hand out
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Workers started before the coordinator keep trying to connect for a minute
+const CONNECT_ATTEMPTS: u32 = 30;
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+/// Frames joined at a time
+const JOIN_BLOCK_FRAMES: usize = 8192;
+
+/// Input and output of a render split into segments, the output options are applied to the
+/// joined audio
+pub struct FarmOptions {
+    pub midi_path: PathBuf,
+    /// Output path without `.wav`
+    pub base_name: String,
+    pub crossfade: Duration,
+    pub bit_depth: u16,
+    pub dither: Dither,
+    pub segment_duration: Option<Duration>,
+    pub segment_size: Option<u64>,
+    pub force_rf64: bool,
+    pub fade_in: Option<Duration>,
+    pub fade_out: Option<Duration>,
+    pub prepend: Option<String>,
+    pub append: Option<String>,
+}
+
+/// Segments still to hand out and the rendered ones
+struct Farm {
+    pending: VecDeque<usize>,
+    parts: Vec<Option<TempFile>>,
+    error: Option<String>,
+}
+
+impl Farm {
+    fn is_finished(&self) -> bool {
+        self.error.is_some() || self.parts.iter().all(Option::is_some)
+    }
+}
+
+enum SegmentError {
+    /// The worker is gone, its segment goes back to the queue
+    Disconnected(io::Error),
+    /// The segment can't be rendered, which ends the farm
+    Failed(String),
+}
+
+/// Length of the MIDI, read the same way as for rendering
+fn midi_duration(path: &Path) -> Result<Duration, String> {
+    let unwrapped =
+        unwrap_midi_container(path).map_err(|e| format!("Failed to unwrap MIDI file: {}", e))?;
+    let path = unwrapped
+        .as_ref()
+        .map_or(path, |temp_file| temp_file.path());
+    if Midi2Clip::is_clip_file(path) {
+        return Midi2Clip::open(path).map(|clip| clip.duration());
+    }
+    let midi =
+        MIDIFile::open(path, None).map_err(|e| format!("Failed to open MIDI file: {:?}", e))?;
+    let statistics = pipe!(
+        midi.iter_all_tracks()
+        |>to_vec()
+        |>get_channels_array_statistics()
+    )
+    .map_err(|e| format!("Failed to calculate MIDI statistics: {:?}", e))?;
+    Ok(statistics.calculate_total_duration(midi.ppq()))
+}
+
+/// Segments of `length` covering `duration`, each but the last extended by `overlap`
+fn segment_ranges(duration: Duration, length: Duration, overlap: Duration) -> Vec<TimeRange> {
+    let count = (duration.as_secs_f64() / length.as_secs_f64())
+        .ceil()
+        .max(1.0) as u32;
+    (0..count)
+        .map(|i| TimeRange {
+            start: length * i,
+            end: (i + 1 < count).then(|| length * (i + 1) + overlap),
+        })
+        .collect()
+}
+
+fn write_frame(stream: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    stream.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    stream.write_all(bytes)
+}
+
+fn write_file_frame(stream: &mut impl Write, path: &Path) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    stream.write_all(&file.metadata()?.len().to_le_bytes())?;
+    io::copy(&mut file, stream)?;
+    Ok(())
+}
+
+fn read_frame_len(stream: &mut impl Read) -> io::Result<u64> {
+    let mut len = [0u8; 8];
+    stream.read_exact(&mut len)?;
+    Ok(u64::from_le_bytes(len))
+}
+
+fn read_json_frame(stream: &mut impl Read) -> io::Result<Value> {
+    let len = read_frame_len(stream)?;
+    if len > MAX_HEADER_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message header too large",
+        ));
+    }
+    let mut bytes = vec![0; len as usize];
+    stream.read_exact(&mut bytes)?;
+    serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Copies the next frame to `output` without holding it in memory
+fn copy_frame(stream: &mut impl Read, output: &mut impl Write) -> io::Result<()> {
+    let len = read_frame_len(stream)?;
+    if io::copy(&mut stream.take(len), output)? < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    output.flush()
+}
+
+/// Renders the segments on the workers that connect to `address` and joins them
+pub fn serve(address: &str, segment_length: Duration, options: &FarmOptions) -> Result<(), String> {
+    let start_time = Instant::now();
+    let duration = midi_duration(&options.midi_path)?;
+    let ranges = segment_ranges(duration, segment_length, options.crossfade);
+    let midi = std::fs::read(&options.midi_path)
+        .map_err(|e| format!("Failed to read MIDI file: {}", e))?;
+    let mut args = args_without(&COORDINATOR_OPTIONS, &COORDINATOR_FLAGS);
+    // Workers render without the subcommand and its address, only global options come before it
+    if let Some(index) = args.iter().position(|arg| arg == "serve") {
+        args.remove(index);
+        if let Some(offset) = args[index..].iter().position(|arg| arg == address) {
+            args.remove(index + offset);
+        }
+    }
+
+    let listener = TcpListener::bind(address)
+        .map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    info!(
+        event = "farm_listening",
+        address,
+        segments = ranges.len();
+        "{}",
+        t!("farm-listening", address = address, segments = ranges.len())
+    );
+
+    let farm = Mutex::new(Farm {
+        pending: (0..ranges.len()).collect(),
+        parts: ranges.iter().map(|_| None).collect(),
+        error: None,
+    });
+    std::thread::scope(|scope| {
+        let (farm, ranges, midi, args) = (&farm, &ranges, &midi, &args);
+        while !farm.lock().unwrap().is_finished() {
+            match listener.accept() {
+                Ok((stream, worker)) => {
+                    scope.spawn(move || serve_worker(stream, worker, farm, ranges, midi, args));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                Err(e) => {
+                    farm.lock()
+                        .unwrap()
+                        .error
+                        .get_or_insert(format!("Failed to accept a worker: {}", e));
+                }
+            }
+        }
+    });
+
+    let farm = farm.into_inner().unwrap();
+    if let Some(e) = farm.error {
+        return Err(e);
+    }
+    let parts: Vec<TempFile> = farm.parts.into_iter().flatten().collect();
+    finish(&parts, options, start_time)
+}
+
+/// Renders `segments` segments of the MIDI at once on this machine, with `thread_count` render
+/// threads between them, and joins them
+pub fn render_parallel(
+    segments: usize,
+    thread_count: usize,
+    options: &FarmOptions,
+) -> Result<(), String> {
+    let start_time = Instant::now();
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Failed to find the renderer executable: {}", e))?;
+    let duration = midi_duration(&options.midi_path)?;
+    let segment_length = duration.div_f64(segments.max(1) as f64);
+    // Very short MIDIs are rendered in fewer, crossfade-length segments
+    let ranges = segment_ranges(
+        duration,
+        segment_length.max(options.crossfade * 2),
+        options.crossfade,
+    );
+    let mut args = args_without(
+        &[COORDINATOR_OPTIONS.as_slice(), &THREAD_COUNT_OPTIONS].concat(),
+        &COORDINATOR_FLAGS,
+    );
+    args.extend([
+        "--thread-count".to_string(),
+        (thread_count / ranges.len()).max(1).to_string(),
+    ]);
+    info!(
+        event = "parallel_segments",
+        segments = ranges.len();
+        "{}",
+        t!("parallel-segments", segments = ranges.len())
+    );
+
+    let done = AtomicUsize::new(0);
+    let parts = std::thread::scope(|scope| {
+        let handles: Vec<_> = ranges
+            .iter()
+            .enumerate()
+            .map(|(index, range)| {
+                let (exe, args, done, count) = (&exe, &args, &done, ranges.len());
+                let range = range.to_string();
+                scope.spawn(move || {
+                    let part = render_part(exe, args, &options.midi_path, &range)
+                        .map_err(|e| format!("Segment {} failed: {}", index + 1, e))?;
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    info!(
+                        event = "parallel_segment_done",
+                        index = index + 1;
+                        "{}",
+                        t!("parallel-segment-done", done = done, count = count)
+                    );
+                    Ok(part)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Result<Vec<_>, String>>()
+    })?;
+    finish(&parts, options, start_time)
+}
+
+/// Joins the rendered segments and reports the output
+fn finish(parts: &[TempFile], options: &FarmOptions, start_time: Instant) -> Result<(), String> {
+    info!(event = "farm_joining"; "{}", t!("farm-joining"));
+    let path = join_parts(parts, options)?;
+    let seconds = format!("{:.2}", start_time.elapsed().as_secs_f64());
+    info!(
+        event = "farm_finished",
+        output:% = path,
+        elapsed_sec:% = seconds;
+        "{}",
+        t!("farm-finished", seconds = seconds, path = path)
+    );
+    Ok(())
+}
+
+/// Next segment to hand out, waiting while the others are being rendered in case a worker
+/// drops one. `None` once the farm is finished.
+fn next_segment(farm: &Mutex<Farm>) -> Option<usize> {
+    loop {
+        let mut farm = farm.lock().unwrap();
+        if farm.is_finished() {
+            return None;
+        }
+        if let Some(index) = farm.pending.pop_front() {
+            return Some(index);
+        }
+        drop(farm);
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Hands segments to one worker until none are left, the connection is closed when returning
+fn serve_worker(
+    mut stream: TcpStream,
+    worker: SocketAddr,
+    farm: &Mutex<Farm>,
+    ranges: &[TimeRange],
+    midi: &[u8],
+    args: &[String],
+) {
+    // Accepted sockets can inherit the listener's non-blocking mode
+    if let Err(e) = stream.set_nonblocking(false) {
+        warn!(event = "farm_worker_lost", worker:%; "{}", t!("farm-worker-lost", worker = worker.to_string(), error = e.to_string()));
+        return;
+    }
+    info!(event = "farm_worker_joined", worker:%; "{}", t!("farm-worker-joined", worker = worker.to_string()));
+
+    while let Some(index) = next_segment(farm) {
+        match render_segment(&mut stream, index, ranges[index], midi, args) {
+            Ok(part) => {
+                farm.lock().unwrap().parts[index] = Some(part);
+                info!(
+                    event = "farm_segment_done",
+                    index = index + 1,
+                    worker:%;
+                    "{}",
+                    t!(
+                        "farm-segment-done",
+                        index = index + 1,
+                        count = ranges.len(),
+                        worker = worker.to_string()
+                    )
+                );
+            }
+            Err(SegmentError::Disconnected(e)) => {
+                farm.lock().unwrap().pending.push_front(index);
+                warn!(
+                    event = "farm_worker_lost",
+                    worker:%,
+                    error:% = e;
+                    "{}",
+                    t!("farm-worker-lost", worker = worker.to_string(), error = e.to_string())
+                );
+                return;
+            }
+            Err(SegmentError::Failed(e)) => {
+                farm.lock().unwrap().error.get_or_insert(format!(
+                    "Segment {} failed on {}: {}",
+                    index + 1,
+                    worker,
+                    e
+                ));
+                return;
+            }
+        }
+    }
+}
+
+/// Sends a segment to the worker and receives its audio into a temp file
+fn render_segment(
+    stream: &mut TcpStream,
+    index: usize,
+    range: TimeRange,
+    midi: &[u8],
+    args: &[String],
+) -> Result<TempFile, SegmentError> {
+    let header = json!({"index": index, "range": range.to_string(), "args": args});
+    write_frame(stream, header.to_string().as_bytes()).map_err(SegmentError::Disconnected)?;
+    write_frame(stream, midi).map_err(SegmentError::Disconnected)?;
+
+    let answer = read_json_frame(stream).map_err(SegmentError::Disconnected)?;
+    if let Some(error) = answer["error"].as_str() {
+        return Err(SegmentError::Failed(error.to_string()));
+    }
+    let (part, file) = TempFile::create_with_extension("wav")
+        .map_err(|e| SegmentError::Failed(format!("Failed to create temp file: {}", e)))?;
+    copy_frame(stream, &mut BufWriter::new(file)).map_err(SegmentError::Disconnected)?;
+    Ok(part)
+}
+
+/// Joins the segments, crossfading the overlap at the end of each one with the start of the
+/// next, and writes them with the output options. Returns the path of the (first) output file.
+fn join_parts(parts: &[TempFile], options: &FarmOptions) -> Result<String, String> {
+    let open = |part: &TempFile| {
+        hound::WavReader::open(part.path()).map_err(|e| format!("Failed to read segment: {}", e))
+    };
+    let first_spec = open(&parts[0])?.spec();
+    let (sample_rate, num_channel) = (first_spec.sample_rate, first_spec.channels);
+    let channels = num_channel as usize;
+    let to_frames = |duration: Option<Duration>| {
+        duration.map_or(0, |d| (d.as_secs_f64() * sample_rate as f64) as u64)
+    };
+
+    let load_bookend_option = |option: &str, path: &Option<String>| {
+        path.as_ref()
+            .map(|path| {
+                load_bookend(path, sample_rate, num_channel)
+                    .map_err(|e| format!("Failed to load {} file {}: {}", option, path, e))
+            })
+            .transpose()
+    };
+    let prepend = load_bookend_option("--prepend", &options.prepend)?;
+    let append = load_bookend_option("--append", &options.append)?;
+
+    let spec = hound::WavSpec {
+        channels: num_channel,
+        sample_rate,
+        bits_per_sample: options.bit_depth,
+        sample_format: if options.bit_depth == 16 {
+            hound::SampleFormat::Int
+        } else {
+            hound::SampleFormat::Float
+        },
+    };
+    let bytes_per_frame = num_channel as u64 * options.bit_depth as u64 / 8;
+    let max_frames_per_segment = match (options.segment_duration, options.segment_size) {
+        (Some(duration), _) => Some(to_frames(Some(duration))),
+        (None, Some(size)) => Some(size.saturating_sub(64 * 1024) / bytes_per_frame),
+        (None, None) => None,
+    };
+    let mut estimated_frames = (prepend.as_ref().map_or(0, Vec::len)
+        + append.as_ref().map_or(0, Vec::len)) as u64
+        / num_channel as u64;
+    for part in parts {
+        estimated_frames += open(part)?.duration() as u64;
+    }
+    let largest_file_bytes = estimated_frames.min(max_frames_per_segment.unwrap_or(u64::MAX))
+        * bytes_per_frame
+        + 64 * 1024;
+    let rf64 = options.force_rf64 || largest_file_bytes > RIFF_SIZE_LIMIT;
+
+    let mut writer =
+        SegmentedWavWriter::new(&options.base_name, spec, max_frames_per_segment, rf64)
+            .map_err(|e| format!("Failed to create output: {}", e))?;
+    writer.set_dither(options.dither);
+    let mut write = |samples: &[f32]| {
+        samples
+            .chunks_exact(channels)
+            .try_for_each(|frame| writer.write_frame(frame))
+            .map_err(|e| format!("Failed to write sample: {}", e))
+    };
+    let mut fader = Fader::new(
+        num_channel,
+        to_frames(options.fade_in),
+        to_frames(options.fade_out),
+    );
+    if let Some(ref prepend) = prepend {
+        write(prepend)?;
+    }
+
+    let overlap_samples = to_frames(Some(options.crossfade)) as usize * channels;
+    // End of the previous segment, faded out over the start of this one
+    let mut held: Vec<f32> = Vec::new();
+    let mut block = Vec::with_capacity(JOIN_BLOCK_FRAMES * channels);
+    for (i, part) in parts.iter().enumerate() {
+        let mut reader = open(part)?;
+        if reader.spec().channels != num_channel || reader.spec().sample_rate != sample_rate {
+            return Err(format!("Segment {} has a different format", i + 1));
+        }
+        let total = reader.len() as usize;
+        let hold = if i + 1 < parts.len() {
+            overlap_samples.min(total.saturating_sub(held.len()))
+        } else {
+            0
+        };
+        let fade_frames = (held.len() / channels).max(1) as f32;
+        let mut next_held = Vec::with_capacity(hold);
+        for (j, sample) in reader.samples::<f32>().enumerate() {
+            let sample = sample.map_err(|e| format!("Failed to read segment: {}", e))?;
+            if j < held.len() {
+                let gain = (j / channels) as f32 / fade_frames;
+                block.push(held[j] * (1.0 - gain) + sample * gain);
+            } else if j >= total - hold {
+                next_held.push(sample);
+                continue;
+            } else {
+                block.push(sample);
+            }
+            if block.len() == block.capacity() {
+                write(&fader.process(&block))?;
+                block.clear();
+            }
+        }
+        held = next_held;
+    }
+    write(&fader.process(&block))?;
+    write(&fader.finish())?;
+    if let Some(ref append) = append {
+        write(append)?;
+    }
+
+    let segments = writer
+        .finalize()
+        .map_err(|e| format!("Failed to finalize output: {}", e))?;
+    Ok(segments[0].path.clone())
+}
+
+/// Renders segments for the coordinator at `address` until it closes the connection
+pub fn work(address: &str) -> Result<(), String> {
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Failed to find the renderer executable: {}", e))?;
+    info!(event = "farm_connecting", address; "{}", t!("farm-connecting", address = address));
+    let mut attempt = 1;
+    let mut stream = loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => break stream,
+            Err(_) if attempt < CONNECT_ATTEMPTS => {
+                attempt += 1;
+                std::thread::sleep(CONNECT_RETRY_INTERVAL);
+            }
+            Err(e) => return Err(format!("Failed to connect to {}: {}", address, e)),
+        }
+    };
+
+    loop {
+        let header = match read_json_frame(&mut stream) {
+            Ok(header) => header,
+            // The coordinator closes the connection once every segment is rendered
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(format!("Lost the coordinator: {}", e)),
+        };
+        let index = header["index"].as_u64().unwrap_or_default() + 1;
+        let range = header["range"].as_str().unwrap_or_default().to_string();
+        let args: Vec<String> = header["args"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|arg| arg.as_str().map(str::to_string))
+            .collect();
+        let (midi, file) =
+            TempFile::create().map_err(|e| format!("Failed to create temp file: {}", e))?;
+        copy_frame(&mut stream, &mut BufWriter::new(file))
+            .map_err(|e| format!("Lost the coordinator: {}", e))?;
+
+        info!(event = "farm_segment_started", index, range:%; "{}", t!("farm-segment-started", index = index, range = range));
+        let sent = match render_part(&exe, &args, midi.path(), &range) {
+            Ok(part) => write_frame(&mut stream, json!({"error": null}).to_string().as_bytes())
+                .and_then(|_| write_file_frame(&mut stream, part.path())),
+            Err(e) => {
+                warn!(event = "farm_segment_failed", index, error:% = e; "{}", t!("farm-segment-failed", index = index, error = e));
+                write_frame(&mut stream, json!({"error": e}).to_string().as_bytes())
+            }
+        };
+        sent.map_err(|e| format!("Lost the coordinator: {}", e))?;
+    }
+    info!(event = "farm_worker_finished"; "{}", t!("farm-worker-finished"));
+    Ok(())
+}
+
+/// Renders a segment in a renderer process, as a float WAV so nothing is quantized twice
+fn render_part(exe: &Path, args: &[String], midi: &Path, range: &str) -> Result<TempFile, String> {
+    let (part, _) = TempFile::create_with_extension("wav")
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    let output = Command::new(exe)
+        .args(args)
+        .arg("--midi-file-path")
+        .arg(midi)
+        .arg("--output")
+        .arg(part.path())
+        .arg("--time-range")
+        .arg(range)
+        .args(["--bit-depth", "32"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(last_error_line(&output.stderr));
+    }
+    Ok(part)
+}
@@ -0,0 +1,177 @@
+//! ReplayGain 2.0 track gain and peak for `--replaygain`. The loudness is the ITU-R BS.1770
+//! integrated loudness (K-weighted, gated 400 ms blocks), and the gain brings it to the
+//! ReplayGain 2.0 reference of -18 LUFS. The peak is the sample peak.
+
+use std::{f64::consts::PI, io::Write, path::Path};
+
+use crate::surround::speakers;
+
+/// Loudness the track gain brings the output to
+pub const REFERENCE_LOUDNESS: f64 = -18.0;
+
+/// Loudness is measured in 400 ms blocks overlapping by 75%, so in steps of 100 ms
+const STEP_SECS: f64 = 0.1;
+const STEPS_PER_BLOCK: usize = 4;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Direct form I biquad
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The two stages of the BS.1770 K-weighting filter, a high shelf and a high pass,
+/// recalculated for `sample_rate`
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let k = (PI * 1681.974450955533 / fs).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        ..Default::default()
+    };
+
+    let k = (PI * 38.13547087602444 / fs).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        ..Default::default()
+    };
+
+    [shelf, high_pass]
+}
+
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+pub struct ReplayGain {
+    pub track_gain_db: f64,
+    pub track_peak: f32,
+}
+
+impl ReplayGain {
+    /// Writes the tags as `REPLAYGAIN_TRACK_GAIN=-3.21 dB` lines, for outputs without tags
+    pub fn write_sidecar(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "REPLAYGAIN_TRACK_GAIN={:.2} dB", self.track_gain_db)?;
+        writeln!(file, "REPLAYGAIN_TRACK_PEAK={:.6}", self.track_peak)?;
+        writeln!(
+            file,
+            "REPLAYGAIN_REFERENCE_LOUDNESS={:.2} LUFS",
+            REFERENCE_LOUDNESS
+        )?;
+        file.flush()
+    }
+}
+
+/// Measures the integrated loudness and sample peak of interleaved output
+pub struct LoudnessMeter {
+    /// BS.1770 channel weights, the LFE channel is ignored and side and back channels count more
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+    step_frames: usize,
+    /// Frames and weighted sum of squares of the current step
+    step_position: usize,
+    step_sum: f64,
+    /// Weighted mean square of every finished step
+    steps: Vec<f64>,
+    peak: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, num_channel: u16) -> Self {
+        let weights = match speakers(num_channel) {
+            Some(speakers) => speakers
+                .iter()
+                .map(|azimuth| match azimuth {
+                    None => 0.0,
+                    Some(azimuth) if azimuth.abs() >= 60.0 => 1.41,
+                    Some(_) => 1.0,
+                })
+                .collect(),
+            None => vec![1.0; num_channel as usize],
+        };
+        LoudnessMeter {
+            weights,
+            filters: vec![k_weighting(sample_rate); num_channel as usize],
+            step_frames: ((sample_rate as f64 * STEP_SECS).round() as usize).max(1),
+            step_position: 0,
+            step_sum: 0.0,
+            steps: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, buffer: &[f32]) {
+        for frame in buffer.chunks_exact(self.weights.len()) {
+            for ((&sample, filters), &weight) in
+                frame.iter().zip(&mut self.filters).zip(&self.weights)
+            {
+                self.peak = self.peak.max(sample.abs());
+                let filtered = filters
+                    .iter_mut()
+                    .fold(sample as f64, |x, filter| filter.process(x));
+                self.step_sum += weight * filtered * filtered;
+            }
+            self.step_position += 1;
+            if self.step_position == self.step_frames {
+                self.steps.push(self.step_sum / self.step_frames as f64);
+                self.step_position = 0;
+                self.step_sum = 0.0;
+            }
+        }
+    }
+
+    /// Gated integrated loudness in LUFS, `None` if the output is shorter than a block or silent
+    pub fn integrated_loudness(&self) -> Option<f64> {
+        let blocks: Vec<f64> = self
+            .steps
+            .windows(STEPS_PER_BLOCK)
+            .map(|steps| steps.iter().sum::<f64>() / STEPS_PER_BLOCK as f64)
+            .filter(|&block| loudness(block) > ABSOLUTE_GATE)
+            .collect();
+        if blocks.is_empty() {
+            return None;
+        }
+        let threshold = loudness(blocks.iter().sum::<f64>() / blocks.len() as f64) + RELATIVE_GATE;
+        let gated: Vec<f64> = blocks
+            .into_iter()
+            .filter(|&block| loudness(block) > threshold)
+            .collect();
+        Some(loudness(gated.iter().sum::<f64>() / gated.len() as f64))
+    }
+
+    pub fn replay_gain(&self) -> Option<ReplayGain> {
+        Some(ReplayGain {
+            track_gain_db: REFERENCE_LOUDNESS - self.integrated_loudness()?,
+            track_peak: self.peak,
+        })
+    }
+}
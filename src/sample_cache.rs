@@ -0,0 +1,76 @@
+//! `--sample-cache-mb`: melodic samples loaded on the first note of their key instead of all at
+//! startup, with the least recently played keys dropped again when the loaded samples go over
+//! the budget. Keys with notes held or kept sounding by the sustain pedal aren't dropped, so a
+//! long or looping note keeps its sample.
+
+use std::{collections::HashMap, sync::RwLock};
+
+use ksynth_core::sample::Sample;
+
+/// Loads the sample of a key, with its size in bytes
+type SampleLoader = Box<dyn Fn(u8) -> Option<(Sample, usize)> + Send>;
+
+pub struct SampleCache {
+    load: SampleLoader,
+    budget_bytes: usize,
+    /// Loaded keys and their size, least recently played first
+    loaded: Vec<(u8, usize)>,
+    loaded_bytes: usize,
+    /// Keys without a sample, not looked up again
+    missing: [bool; 128],
+}
+
+impl SampleCache {
+    pub fn new(
+        budget_bytes: usize,
+        load: impl Fn(u8) -> Option<(Sample, usize)> + Send + 'static,
+    ) -> Self {
+        SampleCache {
+            load: Box::new(load),
+            budget_bytes,
+            loaded: Vec::new(),
+            loaded_bytes: 0,
+            missing: [false; 128],
+        }
+    }
+
+    /// Makes sure `key` is in `sample_map` before a note plays it, evicting the least recently
+    /// played keys over the budget that aren't `sounding`. The key being played is never
+    /// evicted, even when it alone is over the budget.
+    pub fn play(
+        &mut self,
+        key: u8,
+        sample_map: &RwLock<HashMap<u8, Sample>>,
+        sounding: impl Fn(u8) -> bool,
+    ) {
+        let key = key & 0x7F;
+        if self.missing[key as usize] {
+            return;
+        }
+        if let Some(index) = self.loaded.iter().position(|&(loaded, _)| loaded == key) {
+            let entry = self.loaded.remove(index);
+            self.loaded.push(entry);
+            return;
+        }
+
+        let Some((sample, size)) = (self.load)(key) else {
+            self.missing[key as usize] = true;
+            return;
+        };
+        let mut sample_map = sample_map.write().unwrap();
+        sample_map.insert(key, sample);
+        self.loaded.push((key, size));
+        self.loaded_bytes += size;
+        let mut index = 0;
+        while self.loaded_bytes > self.budget_bytes && index < self.loaded.len() - 1 {
+            let (evicted, size) = self.loaded[index];
+            if sounding(evicted) {
+                index += 1;
+                continue;
+            }
+            self.loaded.remove(index);
+            sample_map.remove(&evicted);
+            self.loaded_bytes -= size;
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! `export-samples`: writes the built-in samples to WAV files to audition or edit them. The
+//! melodic samples are named `{key}.wav`, so the folder loads back with `--sample-folder-path`,
+//! the standard drum kit goes to `drums/` and the other kits to `drums/<kit>/`.
+
+use std::path::Path;
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use ksynth_core::sample::SampleData;
+
+use crate::{predefined_drum_samples::DrumKitStyle, tuning::SampleSource};
+
+/// Writes `data` as a 16-bit WAV, mono or stereo like the sample
+fn write_sample(path: &Path, sample_rate: u32, data: &SampleData) -> Result<(), String> {
+    let channels = match data {
+        SampleData::Mono(_) => 1,
+        SampleData::Stereo(_) => 2,
+    };
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let error = |e: hound::Error| format!("{}: {}", path.display(), e);
+    let mut writer = WavWriter::create(path, spec).map_err(error)?;
+    match data {
+        SampleData::Mono(samples) => {
+            for &sample in samples {
+                writer.write_sample(sample).map_err(error)?;
+            }
+        }
+        SampleData::Stereo(samples) => {
+            for &(left, right) in samples {
+                writer.write_sample(left).map_err(error)?;
+                writer.write_sample(right).map_err(error)?;
+            }
+        }
+    }
+    writer.finalize().map_err(error)
+}
+
+/// Writes the melodic `sources` and the `drum_notes` of every built-in kit, `drum_sample`
+/// generates a drum and returns an empty sample for keys the kit doesn't have. Returns the
+/// number of files written.
+pub fn export_samples(
+    dir: impl AsRef<Path>,
+    sources: &[(u8, SampleSource)],
+    drum_notes: &[u8],
+    drum_sample_rate: u32,
+    drum_sample: impl Fn(DrumKitStyle, u8) -> Vec<i16>,
+) -> Result<usize, String> {
+    let dir = dir.as_ref();
+    let create_dir =
+        |dir: &Path| std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e));
+    create_dir(dir)?;
+    let mut count = 0;
+    for (key, source) in sources {
+        write_sample(
+            &dir.join(format!("{}.wav", key)),
+            source.sample_rate,
+            &source.data,
+        )?;
+        count += 1;
+    }
+
+    for style in DrumKitStyle::ALL {
+        let kit_dir = match style {
+            DrumKitStyle::Standard => dir.join("drums"),
+            style => dir.join("drums").join(style.name()),
+        };
+        create_dir(&kit_dir)?;
+        for &key in drum_notes {
+            let samples = drum_sample(style, key);
+            if samples.is_empty() {
+                continue;
+            }
+            write_sample(
+                &kit_dir.join(format!("{}.wav", key)),
+                drum_sample_rate,
+                &SampleData::Mono(samples),
+            )?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
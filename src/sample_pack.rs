@@ -0,0 +1,346 @@
+//! Where `--sample-folder-path` reads the sample files from: a folder, a zip or tar archive read
+//! without unpacking it, so packs of thousands of samples stay a single file, or an
+//! `http(s)://` URL whose files are downloaded on first use and cached in the temp folder, so
+//! render farm nodes can share one sample repository. The files the server answers 404 for are
+//! remembered in the cache as well, delete it to pick up files added to the server.
+//!
+//! Archives are indexed when opened and each file is read from its offset when it's loaded. A
+//! .tar.zst can't be read from an offset, so it's decompressed to a temp file first.
+//!
+//! Archives whose files are all inside one top level folder (`pack/0.wav`, `pack/1.wav`...)
+//! are read as if that folder was the root.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use flate2::read::DeflateDecoder;
+use ksynth_core::sample::SampleData;
+use log::warn;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::{
+    midi_input::TempFile,
+    tuning::{SampleSource, equal_frequency},
+};
+
+/// Bytes read to detect the archive format, up to the tar magic
+const HEADER_BYTES: u64 = 262;
+
+/// Where a file of an archive is stored, it's read from there when opened
+pub struct ArchiveEntry {
+    offset: u64,
+    /// Size of the data as stored
+    size: u64,
+    deflated: bool,
+}
+
+/// File the entries of an archive are read from
+pub enum ArchiveFile {
+    Path(PathBuf),
+    /// Tar decompressed from a .tar.zst, removed with the pack
+    Temp(TempFile),
+}
+
+impl ArchiveFile {
+    fn path(&self) -> &Path {
+        match self {
+            ArchiveFile::Path(path) => path,
+            ArchiveFile::Temp(temp) => temp.path(),
+        }
+    }
+}
+
+pub enum SamplePack {
+    Folder(PathBuf),
+    /// Zip or tar archive with the entries of its files by their path
+    Archive {
+        file: ArchiveFile,
+        entries: HashMap<String, ArchiveEntry>,
+    },
+    /// Base URL ending with `/` and the folder its files are cached in
+    Http {
+        base_url: String,
+        cache: PathBuf,
+    },
+}
+
+/// Strips the top level folder when every path is inside the same one
+fn strip_common_folder<T>(files: HashMap<String, T>) -> HashMap<String, T> {
+    let mut folders = files
+        .keys()
+        .map(|name| name.split_once('/').map(|(folder, _)| folder));
+    let Some(Some(first)) = folders.next() else {
+        return files;
+    };
+    if !folders.all(|folder| folder == Some(first)) {
+        return files;
+    }
+    let prefix = format!("{}/", first);
+    files
+        .into_iter()
+        .map(|(name, file)| (name[prefix.len()..].to_string(), file))
+        .collect()
+}
+
+/// Indexes the stored and deflated files of a zip archive
+fn zip_entries(file: File) -> Result<HashMap<String, ArchiveEntry>, String> {
+    let mut archive = ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let mut entries = HashMap::new();
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index).map_err(|e| e.to_string())?;
+        let deflated = match file.compression() {
+            CompressionMethod::Stored => false,
+            CompressionMethod::Deflated => true,
+            _ => continue,
+        };
+        // Encrypted entries are left out like the ones that aren't stored or deflated
+        if file.is_dir() || file.encrypted() {
+            continue;
+        }
+        let Some(offset) = file.data_start() else {
+            continue;
+        };
+        entries.insert(
+            file.name().replace('\\', "/"),
+            ArchiveEntry {
+                offset,
+                size: file.compressed_size(),
+                deflated,
+            },
+        );
+    }
+    Ok(strip_common_folder(entries))
+}
+
+/// Indexes the regular files of an uncompressed tar archive, skipping over their contents
+fn tar_entries(file: File) -> Result<HashMap<String, ArchiveEntry>, String> {
+    let mut archive = tar::Archive::new(BufReader::new(file));
+    let mut entries = HashMap::new();
+    for entry in archive.entries_with_seek().map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(|e| e.to_string())?;
+        let name = path.to_string_lossy().replace('\\', "/");
+        let name = name.strip_prefix("./").unwrap_or(&name).to_string();
+        entries.insert(
+            name,
+            ArchiveEntry {
+                offset: entry.raw_file_position(),
+                size: entry.size(),
+                deflated: false,
+            },
+        );
+    }
+    Ok(strip_common_folder(entries))
+}
+
+/// Empty file next to where the file at `path` would be cached, for files the server doesn't
+/// have, so they aren't asked for again by this render or others sharing the cache
+fn missing_marker(path: &Path) -> PathBuf {
+    let mut marker = path.as_os_str().to_owned();
+    marker.push(".missing");
+    PathBuf::from(marker)
+}
+
+fn mark_missing(path: &Path) {
+    if let Some(folder) = path.parent() {
+        let _ = std::fs::create_dir_all(folder);
+    }
+    let _ = File::create(missing_marker(path));
+}
+
+/// Asks the server whether it has `url` without downloading it, a 404 is remembered for the
+/// file cached at `path`
+fn exists(url: &str, path: &Path) -> Result<bool, String> {
+    match ureq::head(url).call() {
+        Ok(_) => Ok(true),
+        Err(ureq::Error::StatusCode(404)) => {
+            mark_missing(path);
+            Ok(false)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Downloads `url` to `path`, `false` if the server doesn't have it
+fn download(url: &str, path: &Path) -> Result<bool, String> {
+    let response = match ureq::get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::StatusCode(404)) => {
+            mark_missing(path);
+            return Ok(false);
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+    }
+    // Written next to the cached file and renamed, so other renders sharing the cache never
+    // read a partial download
+    let partial = path.with_extension(format!("{}.part", std::process::id()));
+    let mut file = File::create(&partial).map_err(|e| e.to_string())?;
+    std::io::copy(&mut response.into_body().into_reader(), &mut file)
+        .and_then(|_| std::fs::rename(&partial, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&partial);
+            e.to_string()
+        })?;
+    Ok(true)
+}
+
+impl SamplePack {
+    /// Opens a URL, a folder, or an archive detected by its contents: zip, zstd compressed tar
+    /// or tar
+    pub fn open(path: &str) -> Result<Self, String> {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            let base_url = format!("{}/", path.trim_end_matches('/'));
+            let folder: String = base_url
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            let cache = std::env::temp_dir()
+                .join("ksynth-midi-renderer-samples")
+                .join(folder);
+            return Ok(SamplePack::Http { base_url, cache });
+        }
+        let path = Path::new(path);
+        if path.is_dir() {
+            return Ok(SamplePack::Folder(path.to_path_buf()));
+        }
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut header = Vec::new();
+        (&file)
+            .take(HEADER_BYTES)
+            .read_to_end(&mut header)
+            .map_err(|e| e.to_string())?;
+        (&file)
+            .seek(SeekFrom::Start(0))
+            .map_err(|e| e.to_string())?;
+        let (file, entries) = if header.starts_with(b"PK") {
+            (ArchiveFile::Path(path.to_path_buf()), zip_entries(file)?)
+        } else if header.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            // Zstd frames can't be seeked, so the tar is decompressed to a temp file
+            let (temp, mut tar) = TempFile::create_with_extension("tar")
+                .map_err(|e| format!("Failed to create temp file: {}", e))?;
+            zstd::stream::copy_decode(BufReader::new(file), &mut tar).map_err(|e| e.to_string())?;
+            let tar = File::open(temp.path()).map_err(|e| e.to_string())?;
+            (ArchiveFile::Temp(temp), tar_entries(tar)?)
+        } else if header.get(257..262) == Some(b"ustar") {
+            (ArchiveFile::Path(path.to_path_buf()), tar_entries(file)?)
+        } else {
+            return Err("not a folder, zip, .tar.zst or .tar archive".to_string());
+        };
+        Ok(SamplePack::Archive { file, entries })
+    }
+
+    /// Whether the file at `name` exists, without downloading it. Files of URLs that aren't
+    /// cached are looked up on the server.
+    pub fn contains(&self, name: &str) -> bool {
+        match self {
+            SamplePack::Folder(folder) => folder.join(name).is_file(),
+            SamplePack::Archive { entries, .. } => entries.contains_key(name),
+            SamplePack::Http { base_url, cache } => {
+                let path = cache.join(name);
+                if path.is_file() {
+                    return true;
+                }
+                if missing_marker(&path).is_file() {
+                    return false;
+                }
+                let url = format!("{}{}", base_url, name);
+                exists(&url, &path).unwrap_or_else(|e| {
+                    warn!(event = "sample_lookup_failed", url:% = url, error:% = e; "Failed to look up {}: {}", url, e);
+                    false
+                })
+            }
+        }
+    }
+
+    /// Reads the mono or stereo WAV at `name` as the sample of `key`, `None` if it's missing or
+    /// broken. Integer samples deeper than 16 bits are cut down to 16, float samples are scaled
+    /// to the 16 bit range.
+    pub fn load_sample(&self, name: &str, key: u8) -> Option<SampleSource> {
+        let mut reader = hound::WavReader::new(self.open_file(name)?).ok()?;
+        let spec = reader.spec();
+        let samples: Result<Vec<i16>, _> = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .samples::<f32>()
+                .map(|s| {
+                    s.map(|s| (s * i16::MAX as f32).clamp(-i16::MAX as f32, i16::MAX as f32) as i16)
+                })
+                .collect(),
+            hound::SampleFormat::Int if spec.bits_per_sample <= 16 => {
+                reader.samples::<i16>().collect()
+            }
+            hound::SampleFormat::Int => {
+                let shift = spec.bits_per_sample - 16;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| (s >> shift) as i16))
+                    .collect()
+            }
+        };
+        let samples = samples.ok()?;
+        let data = match spec.channels {
+            1 => SampleData::Mono(samples),
+            2 => SampleData::Stereo(
+                samples
+                    .chunks_exact(2)
+                    .map(|chunk| (chunk[0], chunk[1]))
+                    .collect(),
+            ),
+            _ => return None,
+        };
+        Some(SampleSource {
+            sample_rate: spec.sample_rate,
+            data,
+            frequency: equal_frequency(key),
+            loop_points: None,
+        })
+    }
+
+    /// Opens the file at `name`, relative to the folder or the archive root
+    pub fn open_file(&self, name: &str) -> Option<Box<dyn Read + '_>> {
+        match self {
+            SamplePack::Folder(folder) => {
+                let file = File::open(folder.join(name)).ok()?;
+                Some(Box::new(BufReader::new(file)))
+            }
+            SamplePack::Archive { file, entries } => {
+                let entry = entries.get(name)?;
+                let mut file = File::open(file.path()).ok()?;
+                file.seek(SeekFrom::Start(entry.offset)).ok()?;
+                let data = BufReader::new(file).take(entry.size);
+                Some(if entry.deflated {
+                    Box::new(DeflateDecoder::new(data))
+                } else {
+                    Box::new(data)
+                })
+            }
+            SamplePack::Http { base_url, cache } => {
+                let path = cache.join(name);
+                if missing_marker(&path).is_file() {
+                    return None;
+                }
+                if !path.is_file() {
+                    let url = format!("{}{}", base_url, name);
+                    match download(&url, &path) {
+                        Ok(true) => {}
+                        Ok(false) => return None,
+                        Err(e) => {
+                            warn!(event = "sample_download_failed", url:% = url, error:% = e; "Failed to download {}: {}", url, e);
+                            return None;
+                        }
+                    }
+                }
+                Some(Box::new(BufReader::new(File::open(path).ok()?)))
+            }
+        }
+    }
+}
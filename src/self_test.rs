@@ -0,0 +1,300 @@
+use std::{
+    f64::consts::PI,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use serde_json::json;
+
+use crate::{
+    error::{ErrorKind, RenderError},
+    midi_input::TempFile,
+};
+
+const SEED: u64 = 1;
+const SAMPLE_RATE: u32 = 48000;
+const NUM_CHANNEL: usize = 2;
+
+/// Window length of the loudness envelope, 100 ms
+const ENVELOPE_WINDOW: usize = SAMPLE_RATE as usize / 10;
+/// Frequencies of the spectral fingerprint
+const SPECTRUM_FREQS: [f64; 12] = [
+    50.0, 100.0, 200.0, 400.0, 800.0, 1200.0, 2000.0, 3000.0, 5000.0, 8000.0, 12000.0, 16000.0,
+];
+/// Allowed difference in dB before a render counts as changed
+const TOLERANCE_DB: f64 = 1.0;
+/// Levels below this are clamped so silence compares equal
+const FLOOR_DB: f64 = -90.0;
+
+/// Directory the golden files are stored in
+pub fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// (channel, key, velocity, start tick, length in ticks)
+type TestNote = (u8, u8, u8, u32, u32);
+
+/// Small test MIDIs by name
+fn test_cases() -> Vec<(&'static str, Vec<TestNote>)> {
+    let scale = [60, 62, 64, 65, 67, 69, 71, 72]
+        .iter()
+        .enumerate()
+        .map(|(i, &key)| (0, key, 100, i as u32 * 240, 220))
+        .collect();
+
+    let chords = [[48, 55, 64], [53, 60, 69], [55, 62, 71], [48, 60, 76]]
+        .iter()
+        .enumerate()
+        .flat_map(|(i, chord)| {
+            chord
+                .iter()
+                .map(move |&key| (0, key, 90, i as u32 * 960, 900))
+        })
+        .collect();
+
+    let drums = (0..16)
+        .flat_map(|step: u32| {
+            let mut notes = vec![(9, 42, 80, step * 120, 60)];
+            if step.is_multiple_of(4) {
+                notes.push((9, 36, 120, step * 120, 60));
+            }
+            if step % 8 == 4 {
+                notes.push((9, 38, 110, step * 120, 60));
+            }
+            notes
+        })
+        .collect();
+
+    vec![("scale", scale), ("chords", chords), ("drums", drums)]
+}
+
+fn write_vlq(data: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    data.extend(bytes.iter().rev());
+}
+
+/// Builds a format 0 SMF with 480 PPQ at 120 BPM
+fn build_midi(notes: &[TestNote]) -> Vec<u8> {
+    let mut events: Vec<(u32, [u8; 3])> = Vec::new();
+    for &(channel, key, velocity, start, length) in notes {
+        events.push((start, [0x90 | channel, key, velocity]));
+        events.push((start + length, [0x80 | channel, key, 0]));
+    }
+    // Note offs first when events share a tick
+    events.sort_by_key(|&(tick, bytes)| (tick, bytes[0] & 0xF0 == 0x90));
+
+    let mut track = Vec::new();
+    track.extend_from_slice(&[0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20]);
+    let mut last_tick = 0;
+    for (tick, bytes) in events {
+        write_vlq(&mut track, tick - last_tick);
+        track.extend_from_slice(&bytes);
+        last_tick = tick;
+    }
+    track.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+    let mut data = Vec::new();
+    data.extend_from_slice(b"MThd");
+    data.extend_from_slice(&6u32.to_be_bytes());
+    data.extend_from_slice(&[0, 0, 0, 1, 0x01, 0xE0]);
+    data.extend_from_slice(b"MTrk");
+    data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    data.extend_from_slice(&track);
+    data
+}
+
+/// Renders with this executable in headless mode and returns the raw PCM
+fn render(midi: &[u8]) -> Result<Vec<f32>, String> {
+    let (temp_file, mut file) = TempFile::create().map_err(|e| e.to_string())?;
+    file.write_all(midi).map_err(|e| e.to_string())?;
+    drop(file);
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let output = Command::new(exe)
+        .arg("--headless")
+        .arg("--seed")
+        .arg(SEED.to_string())
+        .arg("--sample-rate")
+        .arg(SAMPLE_RATE.to_string())
+        .arg("--num-channel")
+        .arg(NUM_CHANNEL.to_string())
+        .arg("--thread-count")
+        .arg("1")
+        .arg("--midi-file-path")
+        .arg(temp_file.path())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!("render exited with {}", output.status));
+    }
+
+    Ok(output
+        .stdout
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect())
+}
+
+/// FNV-1a over the PCM bytes
+fn hash(pcm: &[f32]) -> String {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in pcm.iter().flat_map(|sample| sample.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01B3);
+    }
+    format!("{:016x}", hash)
+}
+
+fn to_db(value: f64) -> f64 {
+    (20.0 * value.log10()).max(FLOOR_DB)
+}
+
+/// RMS level per 100 ms window, in dB
+fn envelope(mono: &[f64]) -> Vec<f64> {
+    mono.chunks(ENVELOPE_WINDOW)
+        .map(|window| {
+            to_db((window.iter().map(|s| s * s).sum::<f64>() / window.len() as f64).sqrt())
+        })
+        .collect()
+}
+
+/// Magnitude at each of `SPECTRUM_FREQS` over the whole render (Goertzel), in dB
+fn spectrum(mono: &[f64]) -> Vec<f64> {
+    SPECTRUM_FREQS
+        .iter()
+        .map(|&freq| {
+            let coefficient = 2.0 * (2.0 * PI * freq / SAMPLE_RATE as f64).cos();
+            let (mut s1, mut s2) = (0.0, 0.0);
+            for &sample in mono {
+                let s0 = sample + coefficient * s1 - s2;
+                s2 = s1;
+                s1 = s0;
+            }
+            let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+            to_db(power.max(0.0).sqrt() / mono.len().max(1) as f64)
+        })
+        .collect()
+}
+
+/// Largest difference between two fingerprints, `None` if their lengths differ
+fn max_difference(a: &[f64], b: &[f64]) -> Option<f64> {
+    (a.len() == b.len()).then(|| {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max)
+    })
+}
+
+fn read_fingerprint(value: &serde_json::Value, key: &str) -> Vec<f64> {
+    value[key]
+        .as_array()
+        .map(|values| values.iter().filter_map(|v| v.as_f64()).collect())
+        .unwrap_or_default()
+}
+
+/// Renders the bundled test MIDIs and compares them to the golden files.
+/// With `bless`, the golden files are (re)written instead. Fails if any case failed.
+pub fn run(bless: bool) -> Result<(), RenderError> {
+    let golden_dir = golden_dir();
+    let mut failed = 0;
+
+    for (name, notes) in test_cases() {
+        let pcm = match render(&build_midi(&notes)) {
+            Ok(pcm) => pcm,
+            Err(e) => {
+                println!("FAIL {}: {}", name, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let mono: Vec<f64> = pcm
+            .chunks_exact(NUM_CHANNEL)
+            .map(|frame| frame.iter().map(|&s| s as f64).sum::<f64>() / NUM_CHANNEL as f64)
+            .collect();
+        let hash = hash(&pcm);
+        let envelope = envelope(&mono);
+        let spectrum = spectrum(&mono);
+        let golden_path = golden_dir.join(format!("{}.json", name));
+
+        if bless {
+            let golden = json!({
+                "hash": hash,
+                "envelope_db": envelope,
+                "spectrum_db": spectrum,
+            });
+            let result = std::fs::create_dir_all(&golden_dir)
+                .and_then(|_| std::fs::write(&golden_path, serde_json::to_string_pretty(&golden)?));
+            match result {
+                Ok(()) => println!("BLESS {} -> {}", name, golden_path.display()),
+                Err(e) => {
+                    println!("FAIL {}: {}", name, e);
+                    failed += 1;
+                }
+            }
+            continue;
+        }
+
+        let golden: serde_json::Value = match std::fs::read_to_string(&golden_path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+        {
+            Some(golden) => golden,
+            None => {
+                println!(
+                    "FAIL {}: no golden file, run with --self-test-bless to create it",
+                    name
+                );
+                failed += 1;
+                continue;
+            }
+        };
+
+        if golden["hash"].as_str() == Some(hash.as_str()) {
+            println!("PASS {}", name);
+            continue;
+        }
+
+        let envelope_difference =
+            max_difference(&envelope, &read_fingerprint(&golden, "envelope_db"));
+        let spectrum_difference =
+            max_difference(&spectrum, &read_fingerprint(&golden, "spectrum_db"));
+        match (envelope_difference, spectrum_difference) {
+            (Some(envelope_difference), Some(spectrum_difference))
+                if envelope_difference <= TOLERANCE_DB && spectrum_difference <= TOLERANCE_DB =>
+            {
+                println!(
+                    "PASS {} (output changed, fingerprints within {:.2} dB / {:.2} dB)",
+                    name, envelope_difference, spectrum_difference
+                );
+            }
+            (Some(envelope_difference), Some(spectrum_difference)) => {
+                println!(
+                    "FAIL {}: envelope differs by {:.2} dB, spectrum by {:.2} dB",
+                    name, envelope_difference, spectrum_difference
+                );
+                failed += 1;
+            }
+            _ => {
+                println!("FAIL {}: output length changed", name);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(RenderError::new(
+            ErrorKind::Failed,
+            format!("{} self test cases failed", failed),
+        ));
+    }
+    Ok(())
+}
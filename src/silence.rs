@@ -0,0 +1,180 @@
+use std::{fmt, time::Duration};
+
+use crate::units::parse_duration;
+
+/// Auto tails stop after this many seconds even if the synth never goes quiet
+pub const AUTO_TAIL_MAX_SECS: u64 = 30;
+/// Sustain tails stop after this many seconds, in case a note is never released
+pub const SUSTAIN_TAIL_MAX_SECS: u64 = 600;
+
+/// How long to keep rendering after the last MIDI event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tail {
+    /// Render until no voices are playing and the output is below the silence threshold
+    Auto,
+    Fixed(Duration),
+}
+
+impl fmt::Display for Tail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tail::Auto => write!(f, "auto"),
+            Tail::Fixed(duration) => write!(f, "{}s", duration.as_secs_f64()),
+        }
+    }
+}
+
+/// Parses `auto` or a duration like `2s` / `500ms`
+pub fn parse_tail(s: &str) -> Result<Tail, String> {
+    if s.trim().eq_ignore_ascii_case("auto") {
+        Ok(Tail::Auto)
+    } else {
+        parse_duration(s).map(Tail::Fixed)
+    }
+}
+
+/// How the render ends after the last MIDI event, for `--cut-mode`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CutMode {
+    /// Stop exactly at the last event
+    Hard,
+    /// Keep rendering until every voice has decayed
+    Sustain,
+    /// Stop once the output is silent, or at the `--tail` length at the latest
+    Auto,
+}
+
+impl fmt::Display for CutMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CutMode::Hard => write!(f, "hard"),
+            CutMode::Sustain => write!(f, "sustain"),
+            CutMode::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+pub fn parse_cut_mode(s: &str) -> Result<CutMode, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "hard" => Ok(CutMode::Hard),
+        "sustain" => Ok(CutMode::Sustain),
+        "auto" => Ok(CutMode::Auto),
+        _ => Err(format!(
+            "invalid cut mode `{}`, expected hard, sustain or auto",
+            s
+        )),
+    }
+}
+
+/// Largest absolute sample value in the buffer
+pub fn peak(buffer: &[f32]) -> f32 {
+    buffer
+        .iter()
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+}
+
+/// Drops frames from the start of the output until the first frame above the threshold
+pub struct LeadingSilenceTrimmer {
+    threshold: f32,
+    /// Frames still to drop before looking for the first frame above the threshold
+    skip_frames: u64,
+    trimmed_frames: u64,
+    done: bool,
+}
+
+impl LeadingSilenceTrimmer {
+    pub fn new(threshold: f32) -> Self {
+        LeadingSilenceTrimmer {
+            threshold,
+            skip_frames: 0,
+            trimmed_frames: 0,
+            done: false,
+        }
+    }
+
+    /// Drops the first `frames` frames whatever their level, e.g. the preroll of `--time-range`.
+    /// A threshold of 0 only drops these.
+    pub fn skipping(mut self, frames: u64) -> Self {
+        self.skip_frames = frames;
+        self
+    }
+
+    /// Returns the part of `buffer` that should be written
+    pub fn trim<'a>(&mut self, buffer: &'a [f32], num_channel: u16) -> &'a [f32] {
+        if self.done {
+            return buffer;
+        }
+
+        let num_channel = num_channel as usize;
+        let skipped = (buffer.len() / num_channel).min(self.skip_frames as usize);
+        self.skip_frames -= skipped as u64;
+        self.trimmed_frames += skipped as u64;
+        let buffer = &buffer[skipped * num_channel..];
+        if self.skip_frames > 0 {
+            return &[];
+        }
+        match buffer
+            .chunks_exact(num_channel)
+            .position(|frame| peak(frame) >= self.threshold)
+        {
+            Some(first_frame) => {
+                self.done = true;
+                self.trimmed_frames += first_frame as u64;
+                &buffer[first_frame * num_channel..]
+            }
+            None => {
+                self.trimmed_frames += (buffer.len() / num_channel) as u64;
+                &[]
+            }
+        }
+    }
+
+    /// Number of frames dropped so far
+    pub fn trimmed_frames(&self) -> u64 {
+        self.trimmed_frames
+    }
+
+    /// Whether the first frame above the threshold was found, so no more frames are dropped
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_to_the_first_frame_above_the_threshold() {
+        // (threshold, skipped frames, stereo frames, written frames, trimmed frames)
+        type Case<'a> = (f32, u64, &'a [f32], &'a [f32], u64);
+        let cases: [Case; 4] = [
+            (
+                0.5,
+                0,
+                &[0.1, 0.1, 0.6, 0.0, 0.2, 0.2],
+                &[0.6, 0.0, 0.2, 0.2],
+                1,
+            ),
+            (0.5, 1, &[0.9, 0.9, 0.1, 0.1, 0.0, 0.7], &[0.0, 0.7], 2),
+            (0.0, 2, &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0], &[0.0, 0.0], 2),
+            (0.5, 0, &[0.1, 0.1, 0.2, 0.2], &[], 2),
+        ];
+        for (threshold, skip, buffer, written, trimmed) in cases {
+            let mut trimmer = LeadingSilenceTrimmer::new(threshold).skipping(skip);
+            assert_eq!(trimmer.trim(buffer, 2), written);
+            assert_eq!(trimmer.trimmed_frames(), trimmed);
+            assert_eq!(trimmer.is_done(), !written.is_empty());
+        }
+    }
+
+    #[test]
+    fn skips_across_buffers_and_stops_trimming() {
+        let mut trimmer = LeadingSilenceTrimmer::new(0.0).skipping(3);
+        assert_eq!(trimmer.trim(&[1.0, 1.0], 1), &[] as &[f32]);
+        assert_eq!(trimmer.trim(&[1.0, 0.5], 1), &[0.5]);
+        assert_eq!(trimmer.trimmed_frames(), 3);
+        assert_eq!(trimmer.trim(&[0.0, 0.0], 1), &[0.0, 0.0]);
+        assert_eq!(trimmer.trimmed_frames(), 3);
+    }
+}
@@ -0,0 +1,139 @@
+//! Minimal Standard MIDI file writer for the MIDI exports: one track (format 0) of channel
+//! messages, SysEx and meta events with tick deltas, streamed to the file so long MIDIs don't
+//! have to fit in memory.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use midi_toolkit::events::{Event, MIDIEvent};
+
+/// Offset of the track length in the file, after the header chunk and the `MTrk` id
+const TRACK_LENGTH_OFFSET: u64 = 18;
+
+pub struct SmfWriter {
+    file: BufWriter<File>,
+    /// Bytes of the track chunk written so far
+    track_length: u32,
+    /// Ticks since the last written event
+    pending_delta: u64,
+}
+
+impl SmfWriter {
+    pub fn create(path: impl AsRef<Path>, ppq: u16) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(b"MThd")?;
+        file.write_all(&6u32.to_be_bytes())?;
+        // Format 0, one track
+        file.write_all(&0u16.to_be_bytes())?;
+        file.write_all(&1u16.to_be_bytes())?;
+        file.write_all(&ppq.to_be_bytes())?;
+        file.write_all(b"MTrk")?;
+        // Patched by `finish`
+        file.write_all(&0u32.to_be_bytes())?;
+        Ok(SmfWriter {
+            file,
+            track_length: 0,
+            pending_delta: 0,
+        })
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.track_length += bytes.len() as u32;
+        self.file.write_all(bytes)
+    }
+
+    /// Variable-length quantity, 7 bits per byte with the high bit set on all but the last
+    fn write_varint(&mut self, value: u32) -> std::io::Result<()> {
+        let mut bytes = [(value & 0x7F) as u8; 4];
+        let mut len = 1;
+        let mut value = value >> 7;
+        while value > 0 && len < 4 {
+            bytes[len] = (value & 0x7F) as u8 | 0x80;
+            value >>= 7;
+            len += 1;
+        }
+        bytes[..len].reverse();
+        self.write_bytes(&bytes[..len])
+    }
+
+    fn write_delta(&mut self, delta: u64) -> std::io::Result<()> {
+        let delta = self.pending_delta + delta;
+        self.pending_delta = 0;
+        self.write_varint(delta.min(0x0FFF_FFFF) as u32)
+    }
+
+    /// Adds a packed MIDI 1.0 channel message
+    pub fn channel_message(&mut self, delta: u64, cmd: u32) -> std::io::Result<()> {
+        self.write_delta(delta)?;
+        let status = (cmd & 0xFF) as u8;
+        let data1 = ((cmd >> 8) & 0x7F) as u8;
+        let data2 = ((cmd >> 16) & 0x7F) as u8;
+        if matches!(status & 0xF0, 0xC0 | 0xD0) {
+            self.write_bytes(&[status, data1])
+        } else {
+            self.write_bytes(&[status, data1, data2])
+        }
+    }
+
+    /// Adds a meta event of `kind`
+    pub fn meta(&mut self, delta: u64, kind: u8, data: &[u8]) -> std::io::Result<()> {
+        self.write_delta(delta)?;
+        self.write_bytes(&[0xFF, kind])?;
+        self.write_varint(data.len() as u32)?;
+        self.write_bytes(data)
+    }
+
+    /// Adds a SysEx message, with or without its F0 and F7 bytes
+    pub fn sysex(&mut self, delta: u64, data: &[u8]) -> std::io::Result<()> {
+        self.write_delta(delta)?;
+        let data = data.strip_prefix(&[0xF0]).unwrap_or(data);
+        let data = data.strip_suffix(&[0xF7]).unwrap_or(data);
+        self.write_bytes(&[0xF0])?;
+        self.write_varint(data.len() as u32 + 1)?;
+        self.write_bytes(data)?;
+        self.write_bytes(&[0xF7])
+    }
+
+    /// Adds nothing, only moves the next event `delta` ticks later
+    pub fn skip(&mut self, delta: u64) {
+        self.pending_delta += delta;
+    }
+
+    /// Adds an event read by midi_toolkit. Channel messages, SysEx, tempos, time signatures and
+    /// text events are kept, other events are skipped with their delta carried over.
+    pub fn event(&mut self, delta: u64, event: &Event) -> std::io::Result<()> {
+        match event {
+            Event::Tempo(tempo) => self.meta(delta, 0x51, &tempo.tempo.to_be_bytes()[1..]),
+            Event::TimeSignature(signature) => self.meta(
+                delta,
+                0x58,
+                &[
+                    signature.numerator,
+                    signature.denominator,
+                    signature.ticks_per_click,
+                    signature.bb,
+                ],
+            ),
+            Event::Text(text) => self.meta(delta, text.kind as u8, &text.bytes),
+            Event::SystemExclusiveMessage(sysex) => self.sysex(delta, &sysex.data),
+            _ => match event.as_u32() {
+                Some(cmd) => self.channel_message(delta, cmd),
+                None => {
+                    self.skip(delta);
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Ends the track and fills in its length
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.meta(0, 0x2F, &[])?;
+        self.file.seek(SeekFrom::Start(TRACK_LENGTH_OFFSET))?;
+        self.file.write_all(&self.track_length.to_be_bytes())?;
+        self.file.flush()
+    }
+}
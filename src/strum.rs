@@ -0,0 +1,139 @@
+//! `--strum`: spreads the note ons a channel starts at the same time, lowest key first, like a
+//! guitar or harp strum. Note offs move with their note on so note lengths are kept. The drum
+//! channel is left alone.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    iter::Peekable,
+};
+
+use crate::{humanize::Pending, synth_event::SynthEvent};
+
+/// Channel 10, where chords are drum hits played together
+const DRUM_CHANNEL: u8 = 9;
+
+pub struct Strummed<I: Iterator> {
+    events: Peekable<I>,
+    /// Delay between the notes of a chord in seconds
+    spacing: f64,
+    input_time: f64,
+    output_time: f64,
+    /// Delay of the held notes by channel and key, reused for their note offs
+    note_delays: HashMap<(u8, u8), f64>,
+    /// Delayed time of the last note off by channel and key, a retriggered note never starts
+    /// before it or the note off would end the new note
+    note_off_times: HashMap<(u8, u8), f64>,
+    pending: BinaryHeap<Reverse<Pending>>,
+    sequence: u64,
+    finished: bool,
+}
+
+/// Strums the chords of `(delta seconds, event)` pairs with `spacing` seconds between their notes
+pub fn strum<I>(events: I, spacing: f64) -> Strummed<I>
+where
+    I: Iterator<Item = (f64, Option<SynthEvent>)>,
+{
+    Strummed {
+        events: events.peekable(),
+        spacing,
+        input_time: 0.0,
+        output_time: 0.0,
+        note_delays: HashMap::new(),
+        note_off_times: HashMap::new(),
+        pending: BinaryHeap::new(),
+        sequence: 0,
+        finished: false,
+    }
+}
+
+impl<I> Strummed<I>
+where
+    I: Iterator<Item = (f64, Option<SynthEvent>)>,
+{
+    /// Reads the events at the next input time and queues them at their delayed times.
+    /// Returns false at the end of the input.
+    fn read_chord(&mut self) -> bool {
+        let Some((delta, first)) = self.events.next() else {
+            return false;
+        };
+        self.input_time += delta;
+        let mut chord = vec![first];
+        while let Some((_, event)) = self.events.next_if(|(delta, _)| *delta == 0.0) {
+            chord.push(event);
+        }
+
+        // Rank of every note on among the note ons of its channel, by key
+        let mut note_ons: Vec<(u8, u8)> = chord
+            .iter()
+            .filter_map(|event| match event {
+                Some(SynthEvent::NoteOn { channel, key, .. }) if *channel != DRUM_CHANNEL => {
+                    Some((*channel, *key))
+                }
+                _ => None,
+            })
+            .collect();
+        note_ons.sort_unstable();
+        note_ons.dedup();
+        let rank = |channel: u8, key: u8| {
+            let first = note_ons.partition_point(|&(c, _)| c < channel);
+            let index = note_ons.partition_point(|&note| note < (channel, key));
+            (index - first) as f64
+        };
+
+        for event in chord {
+            let time = match event {
+                Some(SynthEvent::NoteOn { channel, key, .. }) if channel != DRUM_CHANNEL => {
+                    let time = (self.input_time + rank(channel, key) * self.spacing).max(
+                        self.note_off_times
+                            .get(&(channel, key))
+                            .copied()
+                            .unwrap_or(0.0),
+                    );
+                    self.note_delays
+                        .insert((channel, key), time - self.input_time);
+                    time
+                }
+                Some(SynthEvent::NoteOff { channel, key, .. }) => {
+                    let delay = self.note_delays.remove(&(channel, key)).unwrap_or(0.0);
+                    let time = self.input_time + delay;
+                    self.note_off_times.insert((channel, key), time);
+                    time
+                }
+                _ => self.input_time,
+            };
+            self.pending.push(Reverse(Pending {
+                time,
+                sequence: self.sequence,
+                event,
+            }));
+            self.sequence += 1;
+        }
+        true
+    }
+}
+
+impl<I> Iterator for Strummed<I>
+where
+    I: Iterator<Item = (f64, Option<SynthEvent>)>,
+{
+    type Item = (f64, Option<SynthEvent>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // Events are only delayed, so everything up to the last chord read is final
+            if let Some(Reverse(first)) = self.pending.peek()
+                && (self.finished || first.time <= self.input_time)
+            {
+                let Reverse(first) = self.pending.pop()?;
+                let delta = (first.time - self.output_time).max(0.0);
+                self.output_time = first.time;
+                return Some((delta, first.event));
+            }
+            if self.finished {
+                return None;
+            }
+            self.finished = !self.read_chord();
+        }
+    }
+}
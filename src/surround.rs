@@ -0,0 +1,162 @@
+//! Surround output for `--num-channel` 4 (quad), 6 (5.1) and 8 (7.1). KSynth only renders
+//! mono or stereo, so every placement of MIDI channels gets its own mono instance, panned
+//! between the two nearest speakers with a constant power pan law.
+
+use std::f32::consts::FRAC_PI_2;
+
+/// Speaker azimuths in degrees in WAV channel order, 0 is front center and negative is left.
+/// The LFE channel has no position and is left silent.
+pub fn speakers(num_channel: u16) -> Option<&'static [Option<f32>]> {
+    match num_channel {
+        // FL FR BL BR
+        4 => Some(&[Some(-45.0), Some(45.0), Some(-135.0), Some(135.0)]),
+        // FL FR FC LFE BL BR
+        6 => Some(&[
+            Some(-30.0),
+            Some(30.0),
+            Some(0.0),
+            None,
+            Some(-110.0),
+            Some(110.0),
+        ]),
+        // FL FR FC LFE BL BR SL SR
+        8 => Some(&[
+            Some(-30.0),
+            Some(30.0),
+            Some(0.0),
+            None,
+            Some(-150.0),
+            Some(150.0),
+            Some(-90.0),
+            Some(90.0),
+        ]),
+        _ => None,
+    }
+}
+
+/// Whether `num_channel` is one of the surround layouts
+pub fn is_surround(num_channel: u16) -> bool {
+    speakers(num_channel).is_some()
+}
+
+/// WAVE_FORMAT_EXTENSIBLE speaker mask of the layout, `None` for mono, stereo and unknown layouts
+pub fn channel_mask(num_channel: u16) -> Option<u32> {
+    match num_channel {
+        4 => Some(0x33),
+        6 => Some(0x3F),
+        8 => Some(0x63F),
+        _ => None,
+    }
+}
+
+/// Parses a `channel=azimuth` placement, e.g. `10=0` for drums front center
+pub fn parse_placement(s: &str) -> Result<(u8, f32), String> {
+    let invalid = || {
+        format!(
+            "invalid placement `{}`, expected <MIDI channel 1-16>=<degrees, negative is left>",
+            s
+        )
+    };
+    let (channel, azimuth) = s.split_once('=').ok_or_else(invalid)?;
+    let channel: u8 = channel.trim().parse().map_err(|_| invalid())?;
+    let azimuth: f32 = azimuth.trim().parse().map_err(|_| invalid())?;
+    if !(1..=16).contains(&channel) || !azimuth.is_finite() {
+        return Err(invalid());
+    }
+    Ok((channel - 1, azimuth))
+}
+
+/// Constant power gains placing a source at `azimuth` between the two speakers around it
+fn pan_gains(speakers: &[Option<f32>], azimuth: f32) -> Vec<f32> {
+    let mut positions: Vec<(usize, f32)> = speakers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, speaker)| Some((i, (*speaker)?)))
+        .collect();
+    positions.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut gains = vec![0.0; speakers.len()];
+    for (i, &(left, left_azimuth)) in positions.iter().enumerate() {
+        // The last pair wraps around behind the listener
+        let (right, right_azimuth) = positions[(i + 1) % positions.len()];
+        let span = (right_azimuth - left_azimuth).rem_euclid(360.0);
+        let offset = (azimuth - left_azimuth).rem_euclid(360.0);
+        if offset <= span {
+            let position = offset / span * FRAC_PI_2;
+            gains[left] += position.cos();
+            gains[right] += position.sin();
+            break;
+        }
+    }
+    gains
+}
+
+/// Maps MIDI channels to buses and mixes each bus into the speakers
+pub struct SurroundPanner {
+    num_speakers: usize,
+    /// Bus of each MIDI channel
+    channel_buses: [usize; 16],
+    /// Speaker gains of each bus
+    bus_gains: Vec<Vec<f32>>,
+}
+
+impl SurroundPanner {
+    /// Places the MIDI channels for a surround `num_channel`, `None` for other channel counts.
+    /// Channels without a placement are spread evenly from back left to back right, drums
+    /// (channel 10) are placed front center.
+    pub fn new(num_channel: u16, placements: &[(u8, f32)]) -> Option<Self> {
+        let speakers = speakers(num_channel)?;
+        let mut azimuths = [0.0f32; 16];
+        for (melodic_index, channel) in (0..16).filter(|&channel| channel != 9).enumerate() {
+            azimuths[channel] = -150.0 + 300.0 * melodic_index as f32 / 14.0;
+        }
+        for &(channel, azimuth) in placements {
+            azimuths[channel as usize & 0xF] = azimuth;
+        }
+
+        // Channels at the same position share a bus
+        let mut bus_azimuths: Vec<f32> = Vec::new();
+        let mut channel_buses = [0; 16];
+        for (channel, &azimuth) in azimuths.iter().enumerate() {
+            channel_buses[channel] = match bus_azimuths.iter().position(|&a| a == azimuth) {
+                Some(bus) => bus,
+                None => {
+                    bus_azimuths.push(azimuth);
+                    bus_azimuths.len() - 1
+                }
+            };
+        }
+
+        Some(SurroundPanner {
+            num_speakers: speakers.len(),
+            channel_buses,
+            bus_gains: bus_azimuths
+                .iter()
+                .map(|&azimuth| pan_gains(speakers, azimuth))
+                .collect(),
+        })
+    }
+
+    pub fn num_speakers(&self) -> usize {
+        self.num_speakers
+    }
+
+    pub fn bus_count(&self) -> usize {
+        self.bus_gains.len()
+    }
+
+    /// Bus rendering the MIDI `channel` (0-15)
+    pub fn bus(&self, channel: u8) -> usize {
+        self.channel_buses[channel as usize & 0xF]
+    }
+
+    /// Adds the mono output of `bus` to the interleaved speaker frames in `output`
+    pub fn mix(&self, bus: usize, mono: &[f32], gain: f32, output: &mut [f32]) {
+        let gains = &self.bus_gains[bus];
+        for (frame, &sample) in output.chunks_exact_mut(self.num_speakers).zip(mono) {
+            for (o, &speaker_gain) in frame.iter_mut().zip(gains) {
+                *o += sample * speaker_gain * gain;
+            }
+        }
+    }
+}
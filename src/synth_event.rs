@@ -0,0 +1,173 @@
+/// Channel voice event with MIDI 2.0 resolution (16-bit velocity, 32-bit controllers, per-note controllers).
+/// KSynth only understands packed MIDI 1.0 commands, so events are scaled down with `to_midi1` when queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SynthEvent {
+    NoteOff {
+        channel: u8,
+        key: u8,
+        velocity: u16,
+    },
+    NoteOn {
+        channel: u8,
+        key: u8,
+        velocity: u16,
+    },
+    PolyPressure {
+        channel: u8,
+        key: u8,
+        value: u32,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u32,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelPressure {
+        channel: u8,
+        value: u32,
+    },
+    PitchBend {
+        channel: u8,
+        value: u32,
+    },
+    PerNoteController {
+        channel: u8,
+        key: u8,
+        controller: u8,
+        value: u32,
+    },
+    PerNotePitchBend {
+        channel: u8,
+        key: u8,
+        value: u32,
+    },
+    /// MIDI Tuning Standard retuning of a key, `pitch` is semitone << 14 | 14-bit fraction
+    NoteTuning {
+        key: u8,
+        pitch: u32,
+    },
+}
+
+// Min-center-max upscaling from the MIDI 2.0 translation spec, so that
+// center values stay centered and maximum values stay at maximum
+fn upscale(value: u32, src_bits: u32, dst_bits: u32) -> u32 {
+    let scale_bits = dst_bits - src_bits;
+    let mut bit_shifted = value << scale_bits;
+    let src_center = 1 << (src_bits - 1);
+    if value <= src_center {
+        return bit_shifted;
+    }
+
+    let repeat_bits = src_bits - 1;
+    let repeat_mask = (1 << repeat_bits) - 1;
+    let mut repeat_value = value & repeat_mask;
+    if scale_bits > repeat_bits {
+        repeat_value <<= scale_bits - repeat_bits;
+    } else {
+        repeat_value >>= repeat_bits - scale_bits;
+    }
+    while repeat_value != 0 {
+        bit_shifted |= repeat_value;
+        repeat_value >>= repeat_bits;
+    }
+
+    bit_shifted
+}
+
+impl SynthEvent {
+    /// Converts a packed MIDI 1.0 command (`status | data1 << 8 | data2 << 16`)
+    pub fn from_midi1(cmd: u32) -> Option<Self> {
+        let status = (cmd & 0xFF) as u8;
+        let data1 = ((cmd >> 8) & 0x7F) as u8;
+        let data2 = ((cmd >> 16) & 0x7F) as u8;
+        let channel = status & 0x0F;
+
+        let event = match status & 0xF0 {
+            0x80 => SynthEvent::NoteOff {
+                channel,
+                key: data1,
+                velocity: upscale(data2 as u32, 7, 16) as u16,
+            },
+            0x90 if data2 == 0 => SynthEvent::NoteOff {
+                channel,
+                key: data1,
+                velocity: 0,
+            },
+            0x90 => SynthEvent::NoteOn {
+                channel,
+                key: data1,
+                velocity: upscale(data2 as u32, 7, 16) as u16,
+            },
+            0xA0 => SynthEvent::PolyPressure {
+                channel,
+                key: data1,
+                value: upscale(data2 as u32, 7, 32),
+            },
+            0xB0 => SynthEvent::ControlChange {
+                channel,
+                controller: data1,
+                value: upscale(data2 as u32, 7, 32),
+            },
+            0xC0 => SynthEvent::ProgramChange {
+                channel,
+                program: data1,
+            },
+            0xD0 => SynthEvent::ChannelPressure {
+                channel,
+                value: upscale(data1 as u32, 7, 32),
+            },
+            0xE0 => SynthEvent::PitchBend {
+                channel,
+                value: upscale(((data2 as u32) << 7) | data1 as u32, 14, 32),
+            },
+            _ => return None,
+        };
+
+        Some(event)
+    }
+
+    /// Converts to a packed MIDI 1.0 command, or `None` for events MIDI 1.0 can't express
+    pub fn to_midi1(&self) -> Option<u32> {
+        let (status, data1, data2) = match *self {
+            SynthEvent::NoteOff {
+                channel,
+                key,
+                velocity,
+            } => (0x80 | channel, key, (velocity >> 9) as u8),
+            SynthEvent::NoteOn {
+                channel,
+                key,
+                velocity,
+            } => (0x90 | channel, key, ((velocity >> 9) as u8).max(1)),
+            SynthEvent::PolyPressure {
+                channel,
+                key,
+                value,
+            } => (0xA0 | channel, key, (value >> 25) as u8),
+            SynthEvent::ControlChange {
+                channel,
+                controller,
+                value,
+            } => (0xB0 | channel, controller, (value >> 25) as u8),
+            SynthEvent::ProgramChange { channel, program } => (0xC0 | channel, program, 0),
+            SynthEvent::ChannelPressure { channel, value } => {
+                (0xD0 | channel, (value >> 25) as u8, 0)
+            }
+            SynthEvent::PitchBend { channel, value } => {
+                let value = value >> 18;
+                (0xE0 | channel, (value & 0x7F) as u8, (value >> 7) as u8)
+            }
+            SynthEvent::PerNoteController { .. }
+            | SynthEvent::PerNotePitchBend { .. }
+            | SynthEvent::NoteTuning { .. } => {
+                return None;
+            }
+        };
+
+        Some(status as u32 | ((data1 as u32 & 0x7F) << 8) | ((data2 as u32 & 0x7F) << 16))
+    }
+}
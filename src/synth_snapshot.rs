@@ -0,0 +1,332 @@
+//! Channel and note state of a `MultiSynth`, taken with `MultiSynth::snapshot` and applied to a
+//! new synth with `MultiSynth::restore`, e.g. to continue a render from the middle on another
+//! synth with the same programs, controllers and held notes. Snapshots of the same state are
+//! identical, so they can be compared and stored.
+//!
+//! KSynth doesn't expose its voices, so a sounding note can't be captured mid-sample: restoring
+//! starts the held notes (and the released ones the sustain pedal holds) again from the start of
+//! their samples. Notes that were fading out after their release aren't restored.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Value, json};
+
+use crate::instrument::Instruments;
+
+const BANK_SELECT_MSB: u8 = 0;
+const DATA_ENTRY_MSB: u8 = 6;
+const BANK_SELECT_LSB: u8 = 32;
+const DATA_ENTRY_LSB: u8 = 38;
+const NRPN_LSB: u8 = 98;
+const NRPN_MSB: u8 = 99;
+const RPN_LSB: u8 = 100;
+const RPN_MSB: u8 = 101;
+/// The RPN and NRPN selection of no parameter
+const NULL_PARAMETER: (u8, u8) = (127, 127);
+const SUSTAIN_PEDAL: u8 = 64;
+const ALL_SOUND_OFF: u8 = 120;
+const RESET_ALL_CONTROLLERS: u8 = 121;
+const ALL_NOTES_OFF: u8 = 123;
+/// Controllers Reset All Controllers leaves alone (RP-015): bank select, volume, pan and the
+/// effect depths
+const KEPT_ON_RESET: [u8; 9] = [0, 7, 10, 32, 91, 92, 93, 94, 95];
+
+/// Data entry `(MSB, LSB)` of the RPNs and NRPNs by `(is NRPN, MSB, LSB)` of the parameter
+type Parameters = BTreeMap<(bool, u8, u8), (u8, Option<u8>)>;
+
+/// MIDI state of the channels as it was queued
+#[derive(Debug, Clone)]
+pub struct ChannelState {
+    controllers: [[Option<u8>; 128]; 16],
+    programs: [Option<u8>; 16],
+    pitch_bends: [Option<u16>; 16],
+    pressures: [Option<u8>; 16],
+    parameters: [Parameters; 16],
+    /// Whether the NRPN was selected after the RPN
+    nrpn_selected: [bool; 16],
+    /// Velocity of the held notes, 0 if not held
+    notes: [[u8; 128]; 16],
+    /// Velocity of the released notes the sustain pedal keeps sounding
+    sustained: [[u8; 128]; 16],
+    /// MTS retuning by key
+    tunings: BTreeMap<u8, u32>,
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        ChannelState {
+            controllers: [[None; 128]; 16],
+            programs: [None; 16],
+            pitch_bends: [None; 16],
+            pressures: [None; 16],
+            parameters: std::array::from_fn(|_| BTreeMap::new()),
+            nrpn_selected: [false; 16],
+            notes: [[0; 128]; 16],
+            sustained: [[0; 128]; 16],
+            tunings: BTreeMap::new(),
+        }
+    }
+}
+
+impl ChannelState {
+    /// Updates the state with a packed MIDI 1.0 command
+    pub fn record(&mut self, cmd: u32) {
+        let status = (cmd & 0xFF) as u8;
+        let data1 = ((cmd >> 8) & 0x7F) as u8;
+        let data2 = ((cmd >> 16) & 0x7F) as u8;
+        let channel = (status & 0x0F) as usize;
+        let sustain_down =
+            self.controllers[channel][SUSTAIN_PEDAL as usize].is_some_and(|v| v >= 64);
+
+        match status & 0xF0 {
+            0x90 if data2 > 0 => {
+                self.notes[channel][data1 as usize] = data2;
+                self.sustained[channel][data1 as usize] = 0;
+            }
+            0x80 | 0x90 => {
+                let velocity = std::mem::take(&mut self.notes[channel][data1 as usize]);
+                if velocity > 0 && sustain_down {
+                    self.sustained[channel][data1 as usize] = velocity;
+                }
+            }
+            0xB0 => match data1 {
+                ALL_SOUND_OFF => {
+                    self.notes[channel] = [0; 128];
+                    self.sustained[channel] = [0; 128];
+                }
+                ALL_NOTES_OFF => {
+                    for key in 0..128 {
+                        let velocity = std::mem::take(&mut self.notes[channel][key]);
+                        if velocity > 0 && sustain_down {
+                            self.sustained[channel][key] = velocity;
+                        }
+                    }
+                }
+                RESET_ALL_CONTROLLERS => {
+                    for (controller, value) in self.controllers[channel].iter_mut().enumerate() {
+                        if !KEPT_ON_RESET.contains(&(controller as u8)) {
+                            *value = None;
+                        }
+                    }
+                    self.pitch_bends[channel] = None;
+                    self.pressures[channel] = None;
+                    self.sustained[channel] = [0; 128];
+                }
+                // Channel mode messages don't set a controller
+                122..=127 => {}
+                DATA_ENTRY_MSB | DATA_ENTRY_LSB => {
+                    if let Some(parameter) = self.selected_parameter(channel) {
+                        let value = self.parameters[channel]
+                            .entry(parameter)
+                            .or_insert((0, None));
+                        if data1 == DATA_ENTRY_MSB {
+                            *value = (data2, None);
+                        } else {
+                            value.1 = Some(data2);
+                        }
+                    }
+                }
+                controller => {
+                    match controller {
+                        NRPN_LSB | NRPN_MSB => self.nrpn_selected[channel] = true,
+                        RPN_LSB | RPN_MSB => self.nrpn_selected[channel] = false,
+                        _ => {}
+                    }
+                    self.controllers[channel][controller as usize] = Some(data2);
+                    if controller == SUSTAIN_PEDAL && data2 < 64 {
+                        self.sustained[channel] = [0; 128];
+                    }
+                }
+            },
+            0xC0 => self.programs[channel] = Some(data1),
+            0xD0 => self.pressures[channel] = Some(data1),
+            0xE0 => self.pitch_bends[channel] = Some(data1 as u16 | (data2 as u16) << 7),
+            _ => {}
+        }
+    }
+
+    pub fn record_tuning(&mut self, key: u8, pitch: u32) {
+        self.tunings.insert(key, pitch);
+    }
+
+    /// Whether a note of `key` is held or kept sounding by the sustain pedal on any channel
+    pub fn key_sounding(&self, key: u8) -> bool {
+        let key = key as usize & 0x7F;
+        (0..16).any(|channel| self.notes[channel][key] > 0 || self.sustained[channel][key] > 0)
+    }
+
+    /// RPN or NRPN the data entry of `channel` goes to, `None` for the null parameter
+    fn selected_parameter(&self, channel: usize) -> Option<(bool, u8, u8)> {
+        let nrpn = self.nrpn_selected[channel];
+        let (msb, lsb) = if nrpn {
+            (NRPN_MSB, NRPN_LSB)
+        } else {
+            (RPN_MSB, RPN_LSB)
+        };
+        let controllers = &self.controllers[channel];
+        let parameter = (controllers[msb as usize]?, controllers[lsb as usize]?);
+        (parameter != NULL_PARAMETER).then_some((nrpn, parameter.0, parameter.1))
+    }
+
+    /// Commands that bring the channels of a new synth to this state without the notes: per
+    /// channel the program, controllers, RPNs and NRPNs, pitch bend and channel pressure
+    pub fn channel_commands(&self, instruments: &Instruments) -> Vec<u32> {
+        let mut commands = Vec::new();
+        for channel in 0..16 {
+            self.push_channel_commands(instruments, channel, &mut commands);
+        }
+        commands
+    }
+
+    fn push_channel_commands(
+        &self,
+        instruments: &Instruments,
+        channel: usize,
+        commands: &mut Vec<u32>,
+    ) {
+        let cc = |controller: u8, value: u8| {
+            0xB0 | channel as u32 | (controller as u32) << 8 | (value as u32) << 16
+        };
+        // The bank of the program first, then the bank selects waiting for the next program
+        // change with the other controllers
+        if let Some(program) = self.programs[channel] {
+            let instrument = instruments.get(channel as u8);
+            commands.push(cc(BANK_SELECT_MSB, instrument.bank_msb));
+            commands.push(cc(BANK_SELECT_LSB, instrument.bank_lsb));
+            commands.push(0xC0 | channel as u32 | (program as u32) << 8);
+        }
+        let parameter_controllers = [
+            DATA_ENTRY_MSB,
+            DATA_ENTRY_LSB,
+            NRPN_LSB,
+            NRPN_MSB,
+            RPN_LSB,
+            RPN_MSB,
+        ];
+        for (controller, value) in self.controllers[channel].iter().enumerate() {
+            if let Some(value) = *value
+                && !parameter_controllers.contains(&(controller as u8))
+            {
+                commands.push(cc(controller as u8, value));
+            }
+        }
+        // Each parameter is selected again for its data entry
+        for (&(nrpn, msb, lsb), &(value, fine)) in &self.parameters[channel] {
+            let (msb_controller, lsb_controller) = if nrpn {
+                (NRPN_MSB, NRPN_LSB)
+            } else {
+                (RPN_MSB, RPN_LSB)
+            };
+            commands.push(cc(msb_controller, msb));
+            commands.push(cc(lsb_controller, lsb));
+            commands.push(cc(DATA_ENTRY_MSB, value));
+            if let Some(fine) = fine {
+                commands.push(cc(DATA_ENTRY_LSB, fine));
+            }
+        }
+        // Then the selection as it was, the selected kind last
+        let selection = if self.nrpn_selected[channel] {
+            [RPN_MSB, RPN_LSB, NRPN_MSB, NRPN_LSB]
+        } else {
+            [NRPN_MSB, NRPN_LSB, RPN_MSB, RPN_LSB]
+        };
+        for controller in selection {
+            if let Some(value) = self.controllers[channel][controller as usize] {
+                commands.push(cc(controller, value));
+            }
+        }
+        if let Some(bend) = self.pitch_bends[channel] {
+            commands.push(
+                0xE0 | channel as u32 | ((bend & 0x7F) as u32) << 8 | ((bend >> 7) as u32) << 16,
+            );
+        }
+        if let Some(pressure) = self.pressures[channel] {
+            commands.push(0xD0 | channel as u32 | (pressure as u32) << 8);
+        }
+    }
+
+    /// Snapshot of the state, `instruments` has the banks the programs were selected in
+    pub fn snapshot(&self, instruments: &Instruments, position: u64) -> SynthSnapshot {
+        let mut commands = Vec::new();
+        for channel in 0..16 {
+            self.push_channel_commands(instruments, channel, &mut commands);
+            for key in 0..128 {
+                let note = channel as u32 | (key as u32) << 8;
+                if self.notes[channel][key] > 0 {
+                    commands.push(0x90 | note | (self.notes[channel][key] as u32) << 16);
+                }
+                // Released again right away, the sustain pedal is already down
+                if self.sustained[channel][key] > 0 {
+                    commands.push(0x90 | note | (self.sustained[channel][key] as u32) << 16);
+                    commands.push(0x80 | note);
+                }
+            }
+        }
+        SynthSnapshot {
+            position,
+            commands,
+            tunings: self
+                .tunings
+                .iter()
+                .map(|(&key, &pitch)| (key, pitch))
+                .collect(),
+        }
+    }
+}
+
+/// State of a `MultiSynth` at a point of the render
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SynthSnapshot {
+    /// Frames rendered by `fill_buffer_scheduled` when it was taken
+    pub position: u64,
+    /// Packed MIDI 1.0 commands that bring a new synth's channels to the same state, in the
+    /// order they're queued: per channel the program, controllers, RPNs and NRPNs, pitch bend,
+    /// channel pressure and notes
+    pub commands: Vec<u32>,
+    /// MTS retuned keys as `(key, pitch)`, see `SynthEvent::NoteTuning`
+    pub tunings: Vec<(u8, u32)>,
+}
+
+impl SynthSnapshot {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "position": self.position,
+            "commands": self.commands,
+            "tunings": self.tunings,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let invalid = |field: &str| format!("invalid snapshot: bad `{}`", field);
+        let position = value["position"]
+            .as_u64()
+            .ok_or_else(|| invalid("position"))?;
+        let commands = value["commands"]
+            .as_array()
+            .ok_or_else(|| invalid("commands"))?
+            .iter()
+            .map(|cmd| {
+                cmd.as_u64()
+                    .and_then(|cmd| u32::try_from(cmd).ok())
+                    .ok_or_else(|| invalid("commands"))
+            })
+            .collect::<Result<_, _>>()?;
+        let tunings = value["tunings"]
+            .as_array()
+            .ok_or_else(|| invalid("tunings"))?
+            .iter()
+            .map(|tuning| {
+                let key = tuning[0].as_u64().and_then(|key| u8::try_from(key).ok());
+                let pitch = tuning[1]
+                    .as_u64()
+                    .and_then(|pitch| u32::try_from(pitch).ok());
+                key.zip(pitch).ok_or_else(|| invalid("tunings"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(SynthSnapshot {
+            position,
+            commands,
+            tunings,
+        })
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::json;
+
+/// Render state served by `TelemetryServer`
+#[derive(Debug, Clone, Default)]
+pub struct Telemetry {
+    pub current_sec: f64,
+    pub total_sec: f64,
+    pub active_voices: u32,
+    pub max_voices: u32,
+    pub peak_voices: u32,
+    pub rt_percent: f32,
+    pub nps: usize,
+    /// Peak level per output channel since the last update, in dBFS
+    pub peak_db: Vec<f32>,
+    pub finished: bool,
+}
+
+impl Telemetry {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "current_sec": self.current_sec,
+            "total_sec": self.total_sec,
+            "percent": if self.total_sec > 0.0 {
+                (self.current_sec / self.total_sec * 100.0).min(100.0)
+            } else {
+                0.0
+            },
+            "active_voices": self.active_voices,
+            "max_voices": self.max_voices,
+            "peak_voices": self.peak_voices,
+            "rt_percent": self.rt_percent,
+            "nps": self.nps,
+            // -inf isn't valid JSON, so silence is reported as null
+            "peak_db": self
+                .peak_db
+                .iter()
+                .map(|db| db.is_finite().then_some(*db))
+                .collect::<Vec<_>>(),
+            "finished": self.finished,
+        })
+    }
+}
+
+/// Minimal HTTP server answering every request with the current `Telemetry` as JSON
+pub struct TelemetryServer {
+    state: Arc<Mutex<Telemetry>>,
+}
+
+impl TelemetryServer {
+    pub fn start(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let state = Arc::new(Mutex::new(Telemetry::default()));
+
+        let server_state = state.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                // The request itself doesn't matter, read it so the client sees a clean close
+                let _ = stream.set_read_timeout(Some(Duration::from_millis(200)));
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+
+                let body = server_state.lock().unwrap().to_json().to_string();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        Ok(TelemetryServer { state })
+    }
+
+    pub fn update(&self, update: impl FnOnce(&mut Telemetry)) {
+        update(&mut self.state.lock().unwrap());
+    }
+}
+
+/// Counts note-ons within the last second of MIDI time
+#[derive(Default)]
+pub struct NpsCounter {
+    note_times: VecDeque<f64>,
+}
+
+impl NpsCounter {
+    pub fn note_on(&mut self, time: f64) {
+        self.note_times.push_back(time);
+    }
+
+    pub fn nps(&mut self, time: f64) -> usize {
+        while self.note_times.front().is_some_and(|&t| t <= time - 1.0) {
+            self.note_times.pop_front();
+        }
+        self.note_times.len()
+    }
+}
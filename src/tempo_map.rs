@@ -0,0 +1,247 @@
+//! Tempo map and bar/beat grid for `--export-tempo-map`, derived from the tempo and time
+//! signature events in ticks so beat positions line up with the rendered audio.
+
+use std::{io::Write, path::Path};
+
+use midi_toolkit::events::{Delta, Event};
+use serde_json::json;
+
+/// 120 BPM, the tempo until the first tempo event
+const DEFAULT_TEMPO: u32 = 500_000;
+
+struct TempoChange {
+    tick: u64,
+    /// Seconds from the start of the MIDI
+    time: f64,
+    /// Microseconds per quarter note
+    tempo: u32,
+}
+
+struct TimeSignatureChange {
+    tick: u64,
+    numerator: u8,
+    /// Power of two of the beat unit, 2 for quarter notes
+    denominator: u8,
+}
+
+/// Beat of the grid with its position in the output
+pub struct Beat {
+    pub bar: u64,
+    pub beat: u32,
+    pub tick: u64,
+    pub time: f64,
+    pub bpm: f64,
+    pub numerator: u8,
+    pub denominator: u32,
+}
+
+pub struct TempoMap {
+    ppq: u16,
+    tempos: Vec<TempoChange>,
+    time_signatures: Vec<TimeSignatureChange>,
+    end_tick: u64,
+}
+
+impl TempoMap {
+    /// Collects the tempo and time signature changes of merged events with tick deltas
+    pub fn from_events(ppq: u16, events: impl Iterator<Item = Delta<u64, Event>>) -> Self {
+        let mut tempos = vec![TempoChange {
+            tick: 0,
+            time: 0.0,
+            tempo: DEFAULT_TEMPO,
+        }];
+        let mut time_signatures = vec![TimeSignatureChange {
+            tick: 0,
+            numerator: 4,
+            denominator: 2,
+        }];
+        let mut tick = 0;
+        for event in events {
+            tick += event.delta;
+            match event.event {
+                Event::Tempo(ref tempo) => {
+                    let time = seconds_at(&tempos, ppq, tick);
+                    // A change at the same tick replaces the previous one
+                    tempos.retain(|change| change.tick < tick);
+                    tempos.push(TempoChange {
+                        tick,
+                        time,
+                        tempo: tempo.tempo,
+                    });
+                }
+                Event::TimeSignature(ref signature) if signature.numerator > 0 => {
+                    time_signatures.retain(|change| change.tick < tick);
+                    time_signatures.push(TimeSignatureChange {
+                        tick,
+                        numerator: signature.numerator,
+                        denominator: signature.denominator.min(6),
+                    });
+                }
+                _ => {}
+            }
+        }
+        TempoMap {
+            ppq: ppq.max(1),
+            tempos,
+            time_signatures,
+            end_tick: tick,
+        }
+    }
+
+    /// `(tick, seconds, BPM)` of the tempo changes, starting with the tempo at tick 0
+    pub fn tempo_changes(&self) -> Vec<(u64, f64, f64)> {
+        self.tempos
+            .iter()
+            .map(|change| {
+                let bpm = 60_000_000.0 / change.tempo.max(1) as f64;
+                (change.tick, change.time, bpm)
+            })
+            .collect()
+    }
+
+    /// Quarter notes from the start of the MIDI to `time` seconds, the last tempo continues
+    /// after the last event
+    pub fn quarters_at(&self, time: f64) -> f64 {
+        let index = self.tempos.partition_point(|change| change.time <= time);
+        let change = &self.tempos[index.saturating_sub(1)];
+        let quarters_since =
+            (time - change.time).max(0.0) * 1_000_000.0 / change.tempo.max(1) as f64;
+        change.tick as f64 / self.ppq as f64 + quarters_since
+    }
+
+    fn tempo_at(&self, tick: u64) -> &TempoChange {
+        let index = self.tempos.partition_point(|change| change.tick <= tick);
+        &self.tempos[index.saturating_sub(1)]
+    }
+
+    /// Beats from the start to the last event. A time signature change starts a new bar.
+    pub fn beats(&self) -> Vec<Beat> {
+        let mut beats = Vec::new();
+        let mut bar = 1;
+        for (i, signature) in self.time_signatures.iter().enumerate() {
+            let end = self
+                .time_signatures
+                .get(i + 1)
+                .map_or(self.end_tick + 1, |next| next.tick);
+            let beat_ticks = ((self.ppq as u64 * 4) >> signature.denominator).max(1);
+            let mut tick = signature.tick;
+            let mut beat = 1;
+            while tick < end {
+                let tempo = self.tempo_at(tick);
+                beats.push(Beat {
+                    bar,
+                    beat,
+                    tick,
+                    time: seconds_at(&self.tempos, self.ppq, tick),
+                    bpm: 60_000_000.0 / tempo.tempo.max(1) as f64,
+                    numerator: signature.numerator,
+                    denominator: 1 << signature.denominator,
+                });
+                tick += beat_ticks;
+                if beat == signature.numerator as u32 {
+                    beat = 1;
+                    bar += 1;
+                } else {
+                    beat += 1;
+                }
+            }
+            // An unfinished bar still counts before the next signature
+            if beat > 1 {
+                bar += 1;
+            }
+        }
+        beats
+    }
+
+    /// Writes the grid as CSV, one beat per line
+    pub fn write_csv(&self, path: impl AsRef<Path>, sample_rate: u32) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "bar,beat,tick,time,frame,bpm,time_signature")?;
+        for beat in self.beats() {
+            writeln!(
+                file,
+                "{},{},{},{:.6},{},{:.3},{}/{}",
+                beat.bar,
+                beat.beat,
+                beat.tick,
+                beat.time,
+                frame_at(beat.time, sample_rate),
+                beat.bpm,
+                beat.numerator,
+                beat.denominator
+            )?;
+        }
+        file.flush()
+    }
+
+    /// Writes the tempo changes, time signatures and grid as JSON
+    pub fn write_json(&self, path: impl AsRef<Path>, sample_rate: u32) -> std::io::Result<()> {
+        let tempos: Vec<_> = self
+            .tempos
+            .iter()
+            .map(|change| {
+                json!({
+                    "tick": change.tick,
+                    "time": change.time,
+                    "frame": frame_at(change.time, sample_rate),
+                    "bpm": 60_000_000.0 / change.tempo.max(1) as f64,
+                })
+            })
+            .collect();
+        let time_signatures: Vec<_> = self
+            .time_signatures
+            .iter()
+            .map(|change| {
+                let time = seconds_at(&self.tempos, self.ppq, change.tick);
+                json!({
+                    "tick": change.tick,
+                    "time": time,
+                    "frame": frame_at(time, sample_rate),
+                    "numerator": change.numerator,
+                    "denominator": 1u32 << change.denominator,
+                })
+            })
+            .collect();
+        let beats: Vec<_> = self
+            .beats()
+            .iter()
+            .map(|beat| {
+                json!({
+                    "bar": beat.bar,
+                    "beat": beat.beat,
+                    "tick": beat.tick,
+                    "time": beat.time,
+                    "frame": frame_at(beat.time, sample_rate),
+                })
+            })
+            .collect();
+
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(
+            file,
+            &json!({
+                "ppq": self.ppq,
+                "sample_rate": sample_rate,
+                "tempos": tempos,
+                "time_signatures": time_signatures,
+                "beats": beats,
+            }),
+        )?;
+        Ok(())
+    }
+}
+
+/// Seconds at `tick` with the tempo changes before it
+fn seconds_at(tempos: &[TempoChange], ppq: u16, tick: u64) -> f64 {
+    let index = tempos.partition_point(|change| change.tick <= tick);
+    let change = &tempos[index.saturating_sub(1)];
+    change.time
+        + (tick - change.tick.min(tick)) as f64 * change.tempo as f64
+            / 1_000_000.0
+            / ppq.max(1) as f64
+}
+
+/// Output frame an event at `time` is rendered at, events are placed on the frame they fall in
+fn frame_at(time: f64, sample_rate: u32) -> u64 {
+    (time * sample_rate as f64).floor() as u64
+}
@@ -0,0 +1,193 @@
+//! `--time-range`: renders only a part of the MIDI. Rendering starts `--preroll` before the
+//! range so the notes and effects sounding at its start are heard, the preroll is dropped from
+//! the output. Events before the preroll only set the controllers, programs and pitch bends,
+//! their notes aren't played.
+
+use std::{fmt, time::Duration};
+
+use crate::{synth_event::SynthEvent, units::parse_duration};
+
+/// Part of the MIDI to render, to the end of the MIDI without `end`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start: Duration,
+    pub end: Option<Duration>,
+}
+
+impl fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.start.as_secs_f64())?;
+        match self.end {
+            Some(end) => write!(f, "{}", end.as_secs_f64()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses `START:END` or `START:` with durations like `90`, `1m30s` or `500ms`
+pub fn parse_time_range(s: &str) -> Result<TimeRange, String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid time range `{}`, expected START:END", s))?;
+    let start = parse_duration(start)?;
+    let end = match end.trim() {
+        "" => None,
+        end => Some(parse_duration(end)?),
+    };
+    if end.is_some_and(|end| end <= start) {
+        return Err(format!("time range `{}` ends before it starts", s));
+    }
+    Ok(TimeRange { start, end })
+}
+
+impl TimeRange {
+    /// Time rendering starts at, `preroll` before the range
+    pub fn render_start(&self, preroll: Duration) -> Duration {
+        self.start.saturating_sub(preroll)
+    }
+}
+
+pub struct Selected<I> {
+    events: I,
+    /// Time of the last input event in seconds
+    time: f64,
+    /// Input time the output starts at
+    from: f64,
+    end: Option<f64>,
+    finished: bool,
+}
+
+/// Events of `(delta seconds, event)` pairs from `preroll` before `range` to its end, with the
+/// deltas relative to the start of the preroll. A range with an end is padded with an empty
+/// event if the MIDI ends before it.
+pub fn select<I>(events: I, range: TimeRange, preroll: Duration) -> Selected<I>
+where
+    I: Iterator<Item = (f64, Option<SynthEvent>)>,
+{
+    Selected {
+        events,
+        time: 0.0,
+        from: range.render_start(preroll).as_secs_f64(),
+        end: range.end.map(|end| end.as_secs_f64()),
+        finished: false,
+    }
+}
+
+impl<I> Iterator for Selected<I>
+where
+    I: Iterator<Item = (f64, Option<SynthEvent>)>,
+{
+    type Item = (f64, Option<SynthEvent>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let output_time = self.time.max(self.from);
+            let Some((delta, event)) = self.events.next() else {
+                self.finished = true;
+                return self
+                    .end
+                    .filter(|&end| end > output_time)
+                    .map(|end| (end - output_time, None));
+            };
+            let time = self.time + delta;
+            if let Some(end) = self.end
+                && time >= end
+            {
+                self.finished = true;
+                return Some(((end - output_time).max(0.0), None));
+            }
+            self.time = time;
+            if time >= self.from {
+                return Some((time - output_time, event));
+            }
+            // Before the preroll only the channel state is kept
+            match event {
+                Some(
+                    SynthEvent::NoteOn { .. }
+                    | SynthEvent::NoteOff { .. }
+                    | SynthEvent::PolyPressure { .. }
+                    | SynthEvent::PerNoteController { .. }
+                    | SynthEvent::PerNotePitchBend { .. },
+                )
+                | None => {}
+                Some(event) => return Some((0.0, Some(event))),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: f64) -> Duration {
+        Duration::from_secs_f64(seconds)
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let cases = [
+            ("10:20", secs(10.0), Some(secs(20.0))),
+            ("1m30s:", secs(90.0), None),
+            ("500ms:2s", secs(0.5), Some(secs(2.0))),
+            ("0: ", secs(0.0), None),
+        ];
+        for (input, start, end) in cases {
+            assert_eq!(
+                parse_time_range(input),
+                Ok(TimeRange { start, end }),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        for input in ["10", "20:10", "10:10", "x:5", ":5"] {
+            assert!(parse_time_range(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn render_start_is_before_the_preroll() {
+        let cases = [(5.0, 2.0, 3.0), (1.0, 2.0, 0.0), (4.0, 0.0, 4.0)];
+        for (start, preroll, render_start) in cases {
+            let range = TimeRange {
+                start: secs(start),
+                end: None,
+            };
+            assert_eq!(range.render_start(secs(preroll)), secs(render_start));
+        }
+    }
+
+    #[test]
+    fn selects_the_events_of_the_range() {
+        let control = SynthEvent::ControlChange {
+            channel: 0,
+            controller: 7,
+            value: 0,
+        };
+        let note = |key| SynthEvent::NoteOn {
+            channel: 0,
+            key,
+            velocity: 0x8000,
+        };
+        // At 0s, 1s and 3s
+        let events = vec![
+            (0.0, Some(control)),
+            (1.0, Some(note(60))),
+            (2.0, Some(note(62))),
+        ];
+        let range = TimeRange {
+            start: secs(2.0),
+            end: Some(secs(4.0)),
+        };
+        let selected: Vec<_> = select(events.into_iter(), range, secs(0.5)).collect();
+        assert_eq!(
+            selected,
+            [(0.0, Some(control)), (1.5, Some(note(62))), (1.0, None)]
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::VecDeque,
+    io::Stdout,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    Terminal,
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Gauge, Paragraph, Sparkline},
+};
+
+use crate::{multi_synth::InstanceStats, synth_event::SynthEvent};
+
+/// Minimum time between two redraws
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// Number of NPS samples kept for the graph, one per redraw
+const NPS_HISTORY: usize = 300;
+/// Number of log lines kept
+const LOG_LINES: usize = 100;
+/// Lowest level shown on the meters
+const METER_FLOOR_DB: f32 = -60.0;
+
+const ACTIVITY_SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+/// Values shown on the dashboard, collected by the render loop
+#[derive(Default)]
+pub struct DashboardStats {
+    pub time: String,
+    pub progress: f64,
+    pub active_voices: u32,
+    pub peak_voices: u32,
+    pub max_voices: u32,
+    pub rt_percent: f32,
+    pub nps: usize,
+    pub instances: Vec<InstanceStats>,
+    pub instance_rms_db: Vec<f32>,
+    pub peak_db: Vec<f32>,
+}
+
+/// Full screen dashboard for `--tui`, used instead of the progress bar.
+/// The terminal is restored when it's dropped.
+pub struct Dashboard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    /// Held notes per channel and key
+    held_notes: Box<[[u16; 128]; 16]>,
+    nps_history: VecDeque<u64>,
+    log: VecDeque<String>,
+    stats: DashboardStats,
+    last_draw: Option<Instant>,
+}
+
+impl Dashboard {
+    pub fn start() -> std::io::Result<Self> {
+        enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        if let Err(e) = execute!(stdout, EnterAlternateScreen) {
+            let _ = disable_raw_mode();
+            return Err(e);
+        }
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        terminal.clear()?;
+        Ok(Dashboard {
+            terminal,
+            held_notes: Box::new([[0; 128]; 16]),
+            nps_history: VecDeque::with_capacity(NPS_HISTORY),
+            log: VecDeque::with_capacity(LOG_LINES),
+            stats: DashboardStats::default(),
+            last_draw: None,
+        })
+    }
+
+    /// Tracks held notes for the channel activity matrix
+    pub fn handle_event(&mut self, event: &SynthEvent) {
+        match *event {
+            SynthEvent::NoteOn {
+                channel,
+                key,
+                velocity,
+            } if velocity > 0 => {
+                let held = &mut self.held_notes[channel as usize & 0xF][key as usize & 0x7F];
+                *held = held.saturating_add(1);
+            }
+            SynthEvent::NoteOn { channel, key, .. } | SynthEvent::NoteOff { channel, key, .. } => {
+                let held = &mut self.held_notes[channel as usize & 0xF][key as usize & 0x7F];
+                *held = held.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds a line to the log pane
+    pub fn log(&mut self, message: impl Into<String>) {
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(message.into());
+    }
+
+    /// Whether enough time has passed since the last redraw
+    pub fn is_due(&self) -> bool {
+        self.last_draw
+            .is_none_or(|last_draw| last_draw.elapsed() >= REDRAW_INTERVAL)
+    }
+
+    /// Replaces the shown values and redraws
+    pub fn update(&mut self, stats: DashboardStats) -> std::io::Result<()> {
+        if self.nps_history.len() == NPS_HISTORY {
+            self.nps_history.pop_front();
+        }
+        self.nps_history.push_back(stats.nps as u64);
+        self.stats = stats;
+        self.redraw()
+    }
+
+    /// Redraws with the last values, e.g. to show a new log line while paused
+    pub fn redraw(&mut self) -> std::io::Result<()> {
+        self.last_draw = Some(Instant::now());
+
+        let stats = &self.stats;
+        let held_notes = &self.held_notes;
+        let nps_history = &self.nps_history;
+        let log = &self.log;
+        self.terminal.draw(|frame| {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(4),
+                    Constraint::Length(18),
+                    Constraint::Length(8),
+                    Constraint::Min(3),
+                ])
+                .split(frame.area());
+            let middle = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(40), Constraint::Length(30)])
+                .split(rows[1]);
+            let graphs = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(rows[2]);
+
+            let summary = format!(
+                "{}  Voices: {} (Peak: {}) / {}  RT: {:.2}%  NPS: {}",
+                stats.time,
+                stats.active_voices,
+                stats.peak_voices,
+                stats.max_voices,
+                stats.rt_percent,
+                stats.nps
+            );
+            frame.render_widget(
+                Gauge::default()
+                    .block(Block::default().borders(Borders::ALL).title(summary))
+                    .gauge_style(Style::default().fg(Color::Cyan))
+                    .ratio(stats.progress.clamp(0.0, 1.0)),
+                rows[0],
+            );
+
+            frame.render_widget(channel_matrix(held_notes, middle[0]), middle[0]);
+            render_meters(frame, &stats.peak_db, middle[1]);
+
+            frame.render_widget(
+                Sparkline::default()
+                    .block(Block::default().borders(Borders::ALL).title("NPS"))
+                    .style(Style::default().fg(Color::Green))
+                    .data(nps_history.iter().copied()),
+                graphs[0],
+            );
+            frame.render_widget(instance_bars(stats), graphs[1]);
+
+            let visible = rows[3].height.saturating_sub(2) as usize;
+            let lines: Vec<Line> = log
+                .iter()
+                .skip(log.len().saturating_sub(visible))
+                .map(|line| Line::from(line.as_str()))
+                .collect();
+            frame.render_widget(
+                Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Log")),
+                rows[3],
+            );
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+/// One row per MIDI channel, keys grouped into as many columns as fit
+fn channel_matrix(held_notes: &[[u16; 128]; 16], area: Rect) -> Paragraph<'static> {
+    let columns = (area.width.saturating_sub(7) as usize).clamp(1, 128);
+    let lines: Vec<Line> = held_notes
+        .iter()
+        .enumerate()
+        .map(|(channel, keys)| {
+            let cells: String = (0..columns)
+                .map(|column| {
+                    let start = column * 128 / columns;
+                    let end = ((column + 1) * 128 / columns).max(start + 1);
+                    let held: u32 = keys[start..end].iter().map(|&n| n as u32).sum();
+                    ACTIVITY_SHADES[(held as usize).min(ACTIVITY_SHADES.len() - 1)]
+                })
+                .collect();
+            Line::from(format!("{:>4}  {}", channel + 1, cells))
+        })
+        .collect();
+    Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Channel activity"),
+    )
+}
+
+fn render_meters(frame: &mut ratatui::Frame, peak_db: &[f32], area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title("Levels");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let meters = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(1); peak_db.len()])
+        .split(inner);
+    for (i, (&db, &meter_area)) in peak_db.iter().zip(meters.iter()).enumerate() {
+        let db = db.max(METER_FLOOR_DB);
+        let color = if db > -1.0 {
+            Color::Red
+        } else if db > -12.0 {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .ratio(((db - METER_FLOOR_DB) / -METER_FLOOR_DB) as f64)
+                .label(format!("{} {:.1} dB", i + 1, db)),
+            meter_area,
+        );
+    }
+}
+
+/// Voices of each instance as bars, labelled with the RMS level and rendering time
+fn instance_bars(stats: &DashboardStats) -> BarChart<'_> {
+    let bars: Vec<Bar> = stats
+        .instances
+        .iter()
+        .enumerate()
+        .map(|(i, instance)| {
+            let rms_db = stats.instance_rms_db.get(i).copied().unwrap_or(-120.0);
+            Bar::default()
+                .value(instance.polyphony as u64)
+                .text_value(format!("{:.0}%", instance.rendering_time_ratio * 100.0))
+                .label(Line::from(format!("{:.0}dB", rms_db)))
+        })
+        .collect();
+    let instance_max = stats
+        .instances
+        .iter()
+        .map(|instance| instance.max_voices as u64)
+        .max()
+        .unwrap_or(0);
+    // Notes assigned to each instance, to show skew in the balancing
+    let notes = stats
+        .instances
+        .iter()
+        .map(|instance| instance.assigned_notes.to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+    BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Voices, RT and RMS per instance (notes {})", notes)),
+        )
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(6)
+        .bar_gap(1)
+        .max(instance_max.max(1))
+}
@@ -0,0 +1,456 @@
+//! Scala (`.scl`) scales, keyboard mappings (`.kbm`) and MIDI Tuning Standard SysEx.
+//! KSynth plays one sample per key, so a retuned key is played back at a sample rate scaled
+//! by the frequency ratio.
+
+use std::collections::HashMap;
+
+use ksynth_core::sample::{Sample, SampleData};
+
+/// 12-TET frequency of a MIDI key
+pub fn equal_frequency(key: u8) -> f64 {
+    440.0 * 2f64.powf((key as f64 - 69.0) / 12.0)
+}
+
+/// Frequency of an MTS pitch (semitone << 14 | 14-bit fraction of a semitone)
+pub fn mts_frequency(pitch: u32) -> f64 {
+    let semitones = (pitch >> 14) as f64 + (pitch & 0x3FFF) as f64 / 16384.0;
+    440.0 * 2f64.powf((semitones - 69.0) / 12.0)
+}
+
+/// Non-comment lines of a Scala or keyboard mapping file
+fn content_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with('!'))
+}
+
+/// Parses a scale degree, cents if it contains a period, otherwise a ratio
+fn parse_pitch(value: &str) -> Result<f64, String> {
+    let token = value.split_whitespace().next().unwrap_or("");
+    if token.contains('.') {
+        return token
+            .parse::<f64>()
+            .map_err(|_| format!("invalid cents value `{}`", token));
+    }
+    let (numerator, denominator) = token.split_once('/').unwrap_or((token, "1"));
+    match (numerator.parse::<f64>(), denominator.parse::<f64>()) {
+        (Ok(numerator), Ok(denominator)) if numerator > 0.0 && denominator > 0.0 => {
+            Ok(1200.0 * (numerator / denominator).log2())
+        }
+        _ => Err(format!("invalid ratio `{}`", token)),
+    }
+}
+
+/// Scale degrees in cents, without the implicit 0 cent unison. The last degree is the period.
+fn parse_scala(text: &str) -> Result<Vec<f64>, String> {
+    let mut lines = content_lines(text);
+    // Description, may be empty
+    lines.next().ok_or("missing description line")?;
+    let count: usize = lines
+        .next()
+        .and_then(|line| line.split_whitespace().next()?.parse().ok())
+        .ok_or("missing note count")?;
+    let degrees = lines
+        .filter(|line| !line.is_empty())
+        .take(count)
+        .map(parse_pitch)
+        .collect::<Result<Vec<_>, _>>()?;
+    if degrees.len() != count || count == 0 {
+        return Err(format!("expected {} notes, found {}", count, degrees.len()));
+    }
+    Ok(degrees)
+}
+
+struct KeyboardMapping {
+    middle_note: i32,
+    reference_note: u8,
+    reference_frequency: f64,
+    octave_degree: i32,
+    /// Scale degree for each key of the pattern, `None` if unmapped. Empty for a linear mapping.
+    mapping: Vec<Option<i32>>,
+}
+
+impl Default for KeyboardMapping {
+    /// Scala's default: degree 0 on middle C, tuned to its 12-TET frequency
+    fn default() -> Self {
+        KeyboardMapping {
+            middle_note: 60,
+            reference_note: 60,
+            reference_frequency: equal_frequency(60),
+            octave_degree: 0,
+            mapping: Vec::new(),
+        }
+    }
+}
+
+fn parse_kbm(text: &str) -> Result<KeyboardMapping, String> {
+    let mut values = content_lines(text)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.split_whitespace().next().unwrap_or(""));
+    let mut next = |name: &str| values.next().ok_or(format!("missing {}", name));
+
+    let parse_int = |value: &str, name: &str| {
+        value
+            .parse::<i32>()
+            .map_err(|_| format!("invalid {} `{}`", name, value))
+    };
+    let map_size = parse_int(next("map size")?, "map size")?;
+    // First and last mapped note, all keys are retuned anyway
+    next("first note")?;
+    next("last note")?;
+    let middle_note = parse_int(next("middle note")?, "middle note")?;
+    let reference_note = parse_int(next("reference note")?, "reference note")?;
+    let reference_frequency = next("reference frequency")?
+        .parse::<f64>()
+        .map_err(|_| "invalid reference frequency".to_string())?;
+    let octave_degree = parse_int(next("octave degree")?, "octave degree")?;
+    let mapping = (0..map_size)
+        .map(|_| match next("mapping entry")? {
+            "x" | "X" => Ok(None),
+            value => parse_int(value, "mapping entry").map(Some),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !(0..128).contains(&reference_note) || reference_frequency <= 0.0 {
+        return Err("reference note must be 0-127 with a positive frequency".to_string());
+    }
+    Ok(KeyboardMapping {
+        middle_note,
+        reference_note: reference_note as u8,
+        reference_frequency,
+        octave_degree,
+        mapping,
+    })
+}
+
+/// Frequency of every MIDI key
+pub struct Tuning {
+    frequencies: [f64; 128],
+}
+
+impl Tuning {
+    /// Loads a Scala scale with an optional keyboard mapping
+    pub fn load(scala_path: &str, kbm_path: Option<&str>) -> Result<Self, String> {
+        let read = |path: &str| {
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+        };
+        let degrees =
+            parse_scala(&read(scala_path)?).map_err(|e| format!("{}: {}", scala_path, e))?;
+        let mapping = match kbm_path {
+            Some(path) => parse_kbm(&read(path)?).map_err(|e| format!("{}: {}", path, e))?,
+            None => KeyboardMapping::default(),
+        };
+        Ok(Self::from_scale(&degrees, &mapping))
+    }
+
+    fn from_scale(degrees: &[f64], mapping: &KeyboardMapping) -> Self {
+        let size = degrees.len() as i32;
+        let period = degrees[degrees.len() - 1];
+        let degree_cents = |degree: i32| {
+            let index = degree.rem_euclid(size);
+            let cents = if index == 0 {
+                0.0
+            } else {
+                degrees[index as usize - 1]
+            };
+            degree.div_euclid(size) as f64 * period + cents
+        };
+        let key_cents = |key: i32| {
+            let offset = key - mapping.middle_note;
+            if mapping.mapping.is_empty() {
+                return Some(degree_cents(offset));
+            }
+            let pattern = mapping.mapping.len() as i32;
+            let degree = mapping.mapping[offset.rem_euclid(pattern) as usize]?;
+            Some(degree_cents(
+                offset.div_euclid(pattern) * mapping.octave_degree + degree,
+            ))
+        };
+
+        // Unmapped keys keep their 12-TET pitch relative to the middle note
+        let reference_cents = key_cents(mapping.reference_note as i32)
+            .unwrap_or(100.0 * (mapping.reference_note as i32 - mapping.middle_note) as f64);
+        let mut frequencies = [0.0; 128];
+        for (key, frequency) in frequencies.iter_mut().enumerate() {
+            let cents =
+                key_cents(key as i32).unwrap_or(100.0 * (key as i32 - mapping.middle_note) as f64);
+            *frequency =
+                mapping.reference_frequency * 2f64.powf((cents - reference_cents) / 1200.0);
+        }
+        Tuning { frequencies }
+    }
+
+    pub fn frequency(&self, key: u8) -> f64 {
+        self.frequencies[key as usize & 0x7F]
+    }
+}
+
+/// Parses MTS single note tuning changes and bulk dumps into (key, MTS pitch) pairs.
+/// Other SysEx messages return nothing.
+pub fn parse_mts(data: &[u8]) -> Vec<(u8, u32)> {
+    let data = data.strip_prefix(&[0xF0]).unwrap_or(data);
+    let data = data.strip_suffix(&[0xF7]).unwrap_or(data);
+    let pitch = |entry: &[u8]| match entry {
+        // 7F 7F 7F means "no change"
+        [0x7F, 0x7F, 0x7F] => None,
+        [semitone, msb, lsb] => Some(
+            ((*semitone as u32 & 0x7F) << 14) | ((*msb as u32 & 0x7F) << 7) | (*lsb as u32 & 0x7F),
+        ),
+        _ => None,
+    };
+    let note_changes = |entries: &[u8], count: u8| -> Vec<(u8, u32)> {
+        entries
+            .chunks_exact(4)
+            .take(count as usize)
+            .filter_map(|entry| Some((entry[0] & 0x7F, pitch(&entry[1..])?)))
+            .collect()
+    };
+
+    match data {
+        // Single note tuning change
+        [0x7E | 0x7F, _, 0x08, 0x02, _program, count, entries @ ..] => {
+            note_changes(entries, *count)
+        }
+        // Single note tuning change with bank select
+        [
+            0x7E | 0x7F,
+            _,
+            0x08,
+            0x07,
+            _bank,
+            _program,
+            count,
+            entries @ ..,
+        ] => note_changes(entries, *count),
+        // Bulk tuning dump: 16 byte name, then 128 entries
+        [0x7E, _, 0x08, 0x01, _program, rest @ ..] if rest.len() >= 16 + 128 * 3 => rest[16..]
+            .chunks_exact(3)
+            .take(128)
+            .enumerate()
+            .filter_map(|(key, entry)| Some((key as u8, pitch(entry)?)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Sample data kept so a key can be retuned while rendering
+pub struct SampleSource {
+    pub sample_rate: u32,
+    pub data: SampleData,
+    /// Pitch the sample plays at its own sample rate
+    pub frequency: f64,
+    /// Loop start and end in frames
+    pub loop_points: Option<(usize, usize)>,
+}
+
+impl SampleSource {
+    fn rate_for(&self, frequency: f64) -> u32 {
+        if frequency == self.frequency {
+            self.sample_rate
+        } else {
+            (self.sample_rate as f64 * frequency / self.frequency).round() as u32
+        }
+    }
+
+    /// Sample that plays at `frequency`
+    pub fn to_sample(&self, frequency: f64) -> Sample {
+        Sample::new(
+            self.rate_for(frequency),
+            self.data.clone(),
+            self.loop_points,
+        )
+    }
+
+    /// Size of the sample data in bytes
+    pub fn data_bytes(&self) -> usize {
+        match &self.data {
+            SampleData::Mono(samples) => samples.len() * 2,
+            SampleData::Stereo(samples) => samples.len() * 4,
+        }
+    }
+
+    pub fn into_sample(self, frequency: f64) -> Sample {
+        Sample::new(self.rate_for(frequency), self.data, self.loop_points)
+    }
+}
+
+/// Sources of the retunable keys
+pub type SampleSources = HashMap<u8, SampleSource>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWELVE_TET: &str = "! 12tet.scl\n!\n12 tone equal temperament\n 12\n!\n 100.0\n 200.0\n 300.0\n 400.0\n 500.0\n 600.0\n 700.0\n 800.0\n 900.0\n 1000.0\n 1100.0\n 2/1\n";
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn parses_scala_degrees() {
+        let cases: [(&str, &[f64]); 3] = [
+            (
+                "! just.scl\n!\nJust fifth\n 3\n!\n 100.0\n 3/2\n 2\n",
+                &[100.0, 701.955000865387, 1200.0],
+            ),
+            // An empty description, and text after the values
+            ("\n 2\n 600.0 tritone\n 2/1 octave\n", &[600.0, 1200.0]),
+            ("Cents only\n1\n1200.0\n", &[1200.0]),
+        ];
+        for (text, expected) in cases {
+            let degrees = parse_scala(text).unwrap();
+            assert_eq!(degrees.len(), expected.len(), "{}", text);
+            for (&degree, &cents) in degrees.iter().zip(expected) {
+                assert_close(degree, cents);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_scala_files() {
+        let cases = [
+            "",
+            "No count\n",
+            "Too few\n 3\n 100.0\n 2/1\n",
+            "Empty\n 0\n",
+            "Bad ratio\n 1\n 0/1\n",
+            "Bad value\n 1\n abc\n",
+        ];
+        for text in cases {
+            assert!(parse_scala(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn parses_keyboard_mappings() {
+        let text = "! a.kbm\n12\n0\n127\n60\n69\n440.0\n12\n0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\nx\n";
+        let mapping = parse_kbm(text).unwrap();
+        assert_eq!(mapping.middle_note, 60);
+        assert_eq!(mapping.reference_note, 69);
+        assert_close(mapping.reference_frequency, 440.0);
+        assert_eq!(mapping.octave_degree, 12);
+        assert_eq!(mapping.mapping.len(), 12);
+        assert_eq!(mapping.mapping[0], Some(0));
+        assert_eq!(mapping.mapping[11], None);
+
+        let linear = parse_kbm("0\n0\n127\n60\n69\n440\n12\n").unwrap();
+        assert!(linear.mapping.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_keyboard_mappings() {
+        let cases = [
+            "",
+            "2\n0\n127\n60\n69\n440\n12\n0\n",
+            "0\n0\n127\n60\n128\n440\n12\n",
+            "0\n0\n127\n60\n69\n0\n12\n",
+            "0\n0\n127\n60\nA4\n440\n12\n",
+        ];
+        for text in cases {
+            assert!(parse_kbm(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn tunes_the_keys() {
+        let equal = Tuning::from_scale(&parse_scala(TWELVE_TET).unwrap(), &Default::default());
+        for key in 0..128 {
+            assert_close(equal.frequency(key), equal_frequency(key));
+        }
+
+        // Two degrees of 600 cents from middle C
+        let tritones = Tuning::from_scale(&[600.0, 1200.0], &Default::default());
+        let cases = [
+            (60, 0.0),
+            (61, 600.0),
+            (62, 1200.0),
+            (59, -600.0),
+            (58, -1200.0),
+        ];
+        for (key, cents) in cases {
+            assert_close(
+                tritones.frequency(key),
+                equal_frequency(60) * 2f64.powf(cents / 1200.0),
+            );
+        }
+
+        // Unmapped keys keep their 12-TET pitch, A4 is the reference
+        let text = "12\n0\n127\n60\n69\n440.0\n12\n0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\nx\n";
+        let mapped =
+            Tuning::from_scale(&parse_scala(TWELVE_TET).unwrap(), &parse_kbm(text).unwrap());
+        assert_close(mapped.frequency(69), 440.0);
+        assert_close(mapped.frequency(71), equal_frequency(71));
+        assert_close(mapped.frequency(72), equal_frequency(72));
+    }
+
+    #[test]
+    fn parses_mts_messages() {
+        let a4 = 69 << 14;
+        let half_above_c4 = (60 << 14) | (0x40 << 7);
+        type Case<'a> = (&'a [u8], &'a [(u8, u32)]);
+        let cases: [Case; 6] = [
+            // Single note tuning change
+            (
+                &[0xF0, 0x7F, 0x00, 0x08, 0x02, 0x00, 0x01, 69, 69, 0, 0, 0xF7],
+                &[(69, a4)],
+            ),
+            // With bank select, without the SysEx framing
+            (
+                &[0x7E, 0x00, 0x08, 0x07, 0x00, 0x00, 0x01, 60, 60, 0x40, 0],
+                &[(60, half_above_c4)],
+            ),
+            // "No change" entries are skipped
+            (
+                &[
+                    0xF0, 0x7F, 0x00, 0x08, 0x02, 0x00, 0x02, 60, 60, 0x40, 0, 61, 0x7F, 0x7F,
+                    0x7F, 0xF7,
+                ],
+                &[(60, half_above_c4)],
+            ),
+            // Only `count` entries are read
+            (
+                &[
+                    0x7F, 0x00, 0x08, 0x02, 0x00, 0x01, 69, 69, 0, 0, 60, 60, 0, 0,
+                ],
+                &[(69, a4)],
+            ),
+            // GS reset
+            (
+                &[
+                    0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+                ],
+                &[],
+            ),
+            // Truncated bulk dump
+            (&[0x7E, 0x00, 0x08, 0x01, 0x00, 0, 0, 0], &[]),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(parse_mts(data), expected, "{:02X?}", data);
+        }
+        assert_close(mts_frequency(a4), 440.0);
+    }
+
+    #[test]
+    fn parses_mts_bulk_dumps() {
+        let mut data = vec![0xF0, 0x7E, 0x00, 0x08, 0x01, 0x00];
+        data.extend(b"bulk dump name  ");
+        for key in 0..128u8 {
+            data.extend(if key == 0 {
+                [0x7F, 0x7F, 0x7F]
+            } else {
+                [key, 0, 0]
+            });
+        }
+        data.push(0xF7);
+        let tunings = parse_mts(&data);
+        assert_eq!(tunings.len(), 127);
+        assert_eq!(tunings[0], (1, 1 << 14));
+        assert_eq!(tunings[126], (127, 127 << 14));
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+/// Parses durations like `90`, `90s`, `500ms`, `10m`, `1h` or `1h30m` (a bare number is seconds)
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("empty duration".to_string());
+    }
+    if let Ok(seconds) = s.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string());
+    }
+
+    let mut total = 0.0f64;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        if number_len == 0 {
+            return Err(format!("invalid duration `{}`", s));
+        }
+        let value: f64 = rest[..number_len]
+            .parse()
+            .map_err(|_| format!("invalid duration `{}`", s))?;
+        rest = &rest[number_len..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let multiplier = match &rest[..unit_len] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            unit => return Err(format!("unknown duration unit `{}` in `{}`", unit, s)),
+        };
+        rest = &rest[unit_len..];
+
+        total += value * multiplier;
+    }
+
+    Duration::try_from_secs_f64(total).map_err(|e| e.to_string())
+}
+
+/// Parses byte sizes like `2GB`, `500MiB` or `1048576` (KB/MB/GB are decimal, KiB/MiB/GiB are binary)
+pub fn parse_byte_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let number_len = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let value: f64 = s[..number_len]
+        .parse()
+        .map_err(|_| format!("invalid size `{}`", s))?;
+
+    let multiplier = match s[number_len..].trim().to_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "K" | "KB" => 1e3,
+        "M" | "MB" => 1e6,
+        "G" | "GB" => 1e9,
+        "T" | "TB" => 1e12,
+        "KIB" => 1024.0,
+        "MIB" => 1024.0 * 1024.0,
+        "GIB" => 1024.0 * 1024.0 * 1024.0,
+        "TIB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        unit => return Err(format!("unknown size unit `{}` in `{}`", unit, s)),
+    };
+
+    Ok((value * multiplier) as u64)
+}
+
+/// Converts decibels to a linear amplitude factor
+pub fn db_to_amplitude(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Converts a linear amplitude to decibels, silence is clamped to -120 dB
+pub fn amplitude_to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-6).log10()
+}
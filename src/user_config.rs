@@ -0,0 +1,150 @@
+//! Files kept in the user's config folder: `%APPDATA%\ksynth-midi-renderer` on Windows,
+//! `~/Library/Application Support/ksynth-midi-renderer` on macOS and
+//! `$XDG_CONFIG_HOME/ksynth-midi-renderer` (`~/.config`) elsewhere.
+//!
+//! Interactive runs save their sample folder and format, sample rate, master gain and limiter
+//! setting to `prefs.yaml`, and the next ones use them for the options not given on the command
+//! line. Renders of job lists, the self test and headless runs neither read nor write them.
+
+use std::path::PathBuf;
+
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader, yaml::Hash};
+
+use crate::job_list::option_args;
+
+const LAST_LOCATIONS_FILE: &str = "last-locations.yaml";
+const PREFS_FILE: &str = "prefs.yaml";
+/// Options saved by interactive runs, by their long name
+const SAVED_OPTIONS: [&str; 5] = [
+    "sample-folder-path",
+    "sample-format",
+    "sample-rate",
+    "master-gain-db",
+    "disable-limiter",
+];
+
+pub fn config_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".config")))
+    };
+    Some(base?.join("ksynth-midi-renderer"))
+}
+
+/// Reads a YAML file of the config folder, `None` if it doesn't exist or can't be read
+fn load_yaml(name: &str) -> Option<Yaml> {
+    let text = std::fs::read_to_string(config_dir()?.join(name)).ok()?;
+    YamlLoader::load_from_str(&text).ok()?.into_iter().next()
+}
+
+/// Writes a mapping to a YAML file of the config folder, null values are left out
+fn save_yaml(name: &str, entries: Vec<(&str, Yaml)>) -> Result<(), String> {
+    let dir = config_dir().ok_or("the config folder is unknown")?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut hash = Hash::new();
+    for (key, value) in entries {
+        if value != Yaml::Null {
+            hash.insert(Yaml::String(key.to_string()), value);
+        }
+    }
+    let mut text = String::new();
+    YamlEmitter::new(&mut text)
+        .dump(&Yaml::Hash(hash))
+        .map_err(|e| e.to_string())?;
+    text.push('\n');
+    let path = dir.join(name);
+    std::fs::write(&path, text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Folders the file dialogs were last used in
+#[derive(Debug, Clone, Default)]
+pub struct LastLocations {
+    pub midi: Option<PathBuf>,
+    pub sample_folder: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+}
+
+impl LastLocations {
+    pub fn load() -> Self {
+        let Some(yaml) = load_yaml(LAST_LOCATIONS_FILE) else {
+            return LastLocations::default();
+        };
+        let path = |key: &str| yaml[key].as_str().map(PathBuf::from);
+        LastLocations {
+            midi: path("midi"),
+            sample_folder: path("sample-folder"),
+            output: path("output"),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = |path: &Option<PathBuf>| match path {
+            Some(path) => Yaml::String(path.to_string_lossy().into_owned()),
+            None => Yaml::Null,
+        };
+        save_yaml(
+            LAST_LOCATIONS_FILE,
+            vec![
+                ("midi", path(&self.midi)),
+                ("sample-folder", path(&self.sample_folder)),
+                ("output", path(&self.output)),
+            ],
+        )
+    }
+}
+
+/// Command line arguments of the saved options that `missing` accepts, the ones not given on the
+/// command line
+pub fn saved_pref_args(missing: impl Fn(&str) -> bool) -> Result<Vec<String>, String> {
+    let Some(Yaml::Hash(saved)) = load_yaml(PREFS_FILE) else {
+        return Ok(Vec::new());
+    };
+    let options = saved
+        .into_iter()
+        .filter(|(option, _)| {
+            option
+                .as_str()
+                .is_some_and(|option| SAVED_OPTIONS.contains(&option) && missing(option))
+        })
+        .collect();
+    option_args(&Yaml::Hash(options), &[])
+}
+
+/// Options of an interactive run, saved for the next ones
+pub struct Prefs {
+    pub sample_folder_path: Option<String>,
+    pub sample_format: String,
+    pub sample_rate: u32,
+    pub master_gain_db: f32,
+    pub disable_limiter: bool,
+}
+
+impl Prefs {
+    pub fn save(&self) -> Result<(), String> {
+        save_yaml(
+            PREFS_FILE,
+            vec![
+                (
+                    "sample-folder-path",
+                    self.sample_folder_path
+                        .clone()
+                        .map_or(Yaml::Null, Yaml::String),
+                ),
+                ("sample-format", Yaml::String(self.sample_format.clone())),
+                ("sample-rate", Yaml::Integer(self.sample_rate as i64)),
+                (
+                    "master-gain-db",
+                    Yaml::Real(self.master_gain_db.to_string()),
+                ),
+                ("disable-limiter", Yaml::Boolean(self.disable_limiter)),
+            ],
+        )
+    }
+}
@@ -0,0 +1,218 @@
+//! wasm-bindgen API for rendering in the browser. Build with
+//! `wasm-pack build --target web -- --no-default-features --features wasm`.
+//! Everything is passed in as bytes, nothing here touches the filesystem.
+
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{Arc, RwLock},
+};
+
+use ksynth_core::{
+    Channel,
+    drum_kit::DrumKit,
+    sample::{Sample, SampleData},
+};
+use midi_toolkit::{
+    events::MIDIEvent,
+    io::MIDIFile,
+    pipe,
+    sequence::{
+        TimeCaster,
+        event::{cancel_tempo_events, merge_events_array, scale_event_time},
+        to_vec, unwrap_items,
+    },
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    builtin_samples::{BuiltinOptions, builtin_drum_kit_styles, builtin_samples},
+    multi_synth::MultiSynth,
+    predefined_drum_samples::DrumKitStyle,
+    synth_event::SynthEvent,
+};
+
+/// Decodes a mono or stereo WAV the same way the sample folder loader does
+fn decode_sample(bytes: &[u8]) -> Result<Sample, String> {
+    let mut reader = hound::WavReader::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let samples: Vec<i16> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .map(|s| s.map(|s| s as i16))
+            .collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => reader.samples::<i16>().collect::<Result<_, _>>(),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let sample_data = match spec.channels {
+        1 => SampleData::Mono(samples),
+        2 => SampleData::Stereo(
+            samples
+                .chunks_exact(2)
+                .map(|chunk| (chunk[0], chunk[1]))
+                .collect(),
+        ),
+        channels => return Err(format!("unsupported channel count {}", channels)),
+    };
+    Ok(Sample::new(spec.sample_rate, sample_data, None))
+}
+
+/// Renders a MIDI file in chunks so the page can stream or play it while rendering
+#[wasm_bindgen]
+pub struct Renderer {
+    sample_rate: u32,
+    num_channel: u16,
+    max_polyphony: u32,
+    samples_map: Arc<RwLock<HashMap<u8, Sample>>>,
+    drum_kit: DrumKit,
+    /// The other built-in kits, for drum program changes
+    drum_kit_styles: Vec<(DrumKitStyle, Vec<(u8, DrumKit)>)>,
+    /// Events with the output frame they're queued at
+    events: Vec<(u64, SynthEvent)>,
+    next_event: usize,
+    current_frame: u64,
+    synth: Option<MultiSynth>,
+}
+
+#[wasm_bindgen]
+impl Renderer {
+    /// Creates a renderer with the built-in samples
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        sample_rate: u32,
+        num_channel: u16,
+        max_polyphony: u32,
+    ) -> Result<Renderer, JsError> {
+        if sample_rate == 0 || max_polyphony == 0 {
+            return Err(JsError::new(
+                "sample rate and max polyphony must be positive",
+            ));
+        }
+        // Validated here so building the synth later can't fail
+        let _: Channel = num_channel
+            .try_into()
+            .map_err(|e: String| JsError::new(&e))?;
+        let options = BuiltinOptions::new(num_channel);
+        let (samples_map, drum_kit) = builtin_samples(&options, sample_rate);
+        let drum_kit_styles = builtin_drum_kit_styles(&options, sample_rate, &DrumKitStyle::ALL);
+        Ok(Renderer {
+            sample_rate,
+            num_channel,
+            max_polyphony,
+            samples_map: Arc::new(RwLock::new(samples_map)),
+            drum_kit,
+            drum_kit_styles,
+            events: Vec::new(),
+            next_event: 0,
+            current_frame: 0,
+            synth: None,
+        })
+    }
+
+    /// Loads a Standard MIDI file and rewinds to its start
+    pub fn load_midi(&mut self, bytes: &[u8]) -> Result<(), JsError> {
+        let midi = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let ppq = midi.ppq();
+        let merged = pipe!(
+            midi.iter_all_tracks()
+            |>to_vec()
+            |>merge_events_array()
+            |>TimeCaster::<f64>::cast_event_delta()
+            |>cancel_tempo_events(250000)
+            |>scale_event_time(1.0 / ppq as f64)
+            |>unwrap_items()
+        );
+
+        let mut time = 0.0;
+        self.events = merged
+            .filter_map(|merged_event| {
+                time += merged_event.delta;
+                let event = merged_event
+                    .event
+                    .as_u32()
+                    .and_then(SynthEvent::from_midi1)?;
+                Some(((time * self.sample_rate as f64) as u64, event))
+            })
+            .collect();
+        self.next_event = 0;
+        self.current_frame = 0;
+        self.synth = None;
+        Ok(())
+    }
+
+    /// Replaces the sample for `key` with a WAV file
+    pub fn load_sample(&mut self, key: u8, bytes: &[u8]) -> Result<(), JsError> {
+        if key > 127 {
+            return Err(JsError::new("key must be 0-127"));
+        }
+        let sample = decode_sample(bytes).map_err(|e| JsError::new(&e))?;
+        self.samples_map.write().unwrap().insert(key, sample);
+        Ok(())
+    }
+
+    /// Renders up to `frame_count` interleaved frames, returned as a Float32Array
+    pub fn render(&mut self, frame_count: usize) -> Vec<f32> {
+        let num_channel = self.num_channel as usize;
+        let synth = self.synth.get_or_insert_with(|| {
+            let mut synth = MultiSynth::new(
+                self.sample_rate,
+                self.num_channel
+                    .try_into()
+                    .expect("Channel count was validated in new"),
+                self.max_polyphony,
+                (self.sample_rate as f64 * 0.1) as u64,
+                self.samples_map.clone(),
+                Some(self.drum_kit.clone()),
+                1,
+            );
+            synth.set_drum_kit_styles(self.drum_kit_styles.clone());
+            synth
+        });
+
+        let mut output = vec![0.0f32; frame_count * num_channel];
+        let mut written = 0;
+        while written < frame_count {
+            while let Some((frame, event)) = self.events.get(self.next_event)
+                && *frame <= self.current_frame
+            {
+                synth.queue_event(event);
+                self.next_event += 1;
+            }
+
+            let until_next_event = match self.events.get(self.next_event) {
+                Some((frame, _)) => (frame - self.current_frame) as usize,
+                None => usize::MAX,
+            };
+            let chunk_frames = until_next_event.min(frame_count - written);
+            synth.fill_buffer(
+                &mut output[written * num_channel..(written + chunk_frames) * num_channel],
+            );
+            written += chunk_frames;
+            self.current_frame += chunk_frames as u64;
+        }
+        output
+    }
+
+    /// Current position in seconds
+    pub fn position(&self) -> f64 {
+        self.current_frame as f64 / self.sample_rate as f64
+    }
+
+    /// Length of the loaded MIDI in seconds, without the release tail
+    pub fn duration(&self) -> f64 {
+        self.events
+            .last()
+            .map_or(0.0, |(frame, _)| *frame as f64 / self.sample_rate as f64)
+    }
+
+    /// True once every event has been played and all voices have ended
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.events.len()
+            && self
+                .synth
+                .as_ref()
+                .is_none_or(|synth| synth.get_polyphony() == 0)
+    }
+}
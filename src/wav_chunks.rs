@@ -0,0 +1,136 @@
+use std::{
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// RIFF chunk id and payload
+pub type RiffChunk = ([u8; 4], Vec<u8>);
+
+/// Appends chunks to the end of a finalized RIFF/WAVE or RF64 file and fixes up the RIFF size.
+/// hound can't write chunks other than `fmt ` and `data`, so this runs after `finalize()`.
+pub fn append_riff_chunks(path: impl AsRef<Path>, chunks: &[RiffChunk]) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+    let mut header = [0u8; 16];
+    file.read_exact(&mut header)?;
+    let rf64 = &header[0..4] == b"RF64" && &header[12..16] == b"ds64";
+    if !(&header[0..4] == b"RIFF" || rf64) || &header[8..12] != b"WAVE" {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Not a RIFF/WAVE file",
+        ));
+    }
+
+    let mut end = file.seek(SeekFrom::End(0))?;
+    // The previous chunk must end on an even offset
+    if end % 2 == 1 {
+        file.write_all(&[0])?;
+        end += 1;
+    }
+
+    for (id, data) in chunks {
+        file.write_all(id)?;
+        file.write_all(&(data.len() as u32).to_le_bytes())?;
+        file.write_all(data)?;
+        end += 8 + data.len() as u64;
+        if data.len() % 2 == 1 {
+            file.write_all(&[0])?;
+            end += 1;
+        }
+    }
+
+    if rf64 {
+        // The RIFF size lives in the first field of the ds64 chunk
+        file.seek(SeekFrom::Start(20))?;
+        file.write_all(&(end - 8).to_le_bytes())?;
+        return file.flush();
+    }
+
+    let riff_size = u32::try_from(end - 8).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "File is too large for a RIFF size field",
+        )
+    })?;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&riff_size.to_le_bytes())?;
+    file.flush()
+}
+
+/// Builds a `cue ` chunk and a `LIST`/`adtl` chunk with a label for each cue point.
+/// `cue_points` are (frame offset, label) pairs.
+pub fn cue_chunks(cue_points: &[(u32, &str)]) -> Vec<RiffChunk> {
+    let mut cue = Vec::with_capacity(4 + cue_points.len() * 24);
+    cue.extend_from_slice(&(cue_points.len() as u32).to_le_bytes());
+
+    let mut adtl = Vec::new();
+    adtl.extend_from_slice(b"adtl");
+
+    for (i, (frame, label)) in cue_points.iter().enumerate() {
+        let id = i as u32 + 1;
+
+        cue.extend_from_slice(&id.to_le_bytes());
+        cue.extend_from_slice(&frame.to_le_bytes());
+        cue.extend_from_slice(b"data");
+        cue.extend_from_slice(&0u32.to_le_bytes());
+        cue.extend_from_slice(&0u32.to_le_bytes());
+        cue.extend_from_slice(&frame.to_le_bytes());
+
+        let mut labl = id.to_le_bytes().to_vec();
+        labl.extend_from_slice(label.as_bytes());
+        labl.push(0);
+        adtl.extend_from_slice(b"labl");
+        adtl.extend_from_slice(&(labl.len() as u32).to_le_bytes());
+        adtl.extend_from_slice(&labl);
+        if labl.len() % 2 == 1 {
+            adtl.push(0);
+        }
+    }
+
+    vec![(*b"cue ", cue), (*b"LIST", adtl)]
+}
+
+/// Maps a tag name to its RIFF INFO id. Raw four-character ids (e.g. `ISBJ`) are accepted as-is.
+pub fn info_chunk_id(key: &str) -> Option<[u8; 4]> {
+    let id = match key.to_lowercase().as_str() {
+        "title" => b"INAM",
+        "artist" => b"IART",
+        "album" => b"IPRD",
+        "comment" => b"ICMT",
+        "genre" => b"IGNR",
+        "date" | "year" => b"ICRD",
+        "copyright" => b"ICOP",
+        "software" => b"ISFT",
+        "engineer" => b"IENG",
+        "tracknumber" | "track" => b"ITRK",
+        _ => {
+            let bytes = key.as_bytes();
+            if bytes.len() == 4 && bytes.iter().all(|b| b.is_ascii_uppercase()) {
+                return Some([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            return None;
+        }
+    };
+
+    Some(*id)
+}
+
+/// Builds a `LIST`/`INFO` chunk from (INFO id, value) pairs
+pub fn info_chunk(tags: &[([u8; 4], &str)]) -> RiffChunk {
+    let mut info = Vec::new();
+    info.extend_from_slice(b"INFO");
+
+    for (id, value) in tags {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        info.extend_from_slice(id);
+        info.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        info.extend_from_slice(&data);
+        if data.len() % 2 == 1 {
+            info.push(0);
+        }
+    }
+
+    (*b"LIST", info)
+}
@@ -0,0 +1,14 @@
+//! Renders the bundled test MIDIs with `--self-test` and compares them against `tests/golden`.
+//! Regenerate the golden files after an intended output change with `--self-test --self-test-bless`.
+
+use std::process::Command;
+
+#[test]
+fn golden_output() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ksynth-midi-renderer"))
+        .arg("--self-test")
+        .output()
+        .expect("Failed to run self test!");
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    assert!(output.status.success(), "Self test failed");
+}